@@ -0,0 +1,295 @@
+use cosmwasm_std::{
+    to_binary, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Order as StorageOrder, Response, StdResult, Storage,
+    Timestamp, Uint128,
+};
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::helpers::{assert_not_frozen, assert_not_paused, consume_allowance, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::ibc::credit_account;
+use crate::msg::SwapOffersResponse;
+use crate::state::{BalanceInfo, PauseScope, SwapOffer, SwapStatus, BALANCES, SWAP_COUNT, SWAP_OFFERS, TOKENS, USER_SWAPS};
+
+fn load_swap(storage: &dyn Storage, id: u64) -> Result<SwapOffer, ContractError> {
+    match SWAP_OFFERS.may_load(storage, id)? {
+        Some(swap) => Ok(swap),
+        None => Err(ContractError::SwapDoesNotExist { id }),
+    }
+}
+
+// Checks the sender account holds amount of denom
+fn sender_balance(deps: Deps, info: &MessageInfo, denom: &str, amount: Uint128) -> Result<BalanceInfo, ContractError> {
+    let balance = match BALANCES.may_load(deps.storage, &info.sender)? {
+        Some(balance) => balance,
+        None => return Err(ContractError::AccountDoesNotExist { account: info.sender.to_string() }),
+    };
+    if balance.denom != denom {
+        return Err(ContractError::TokenAlreadyAssigned { denom: balance.denom });
+    }
+    if balance.amount < amount {
+        return Err(ContractError::NotEnoughBalance { required: amount, available: balance.amount });
+    }
+    assert_not_frozen(deps.storage, &info.sender, denom)?;
+    Ok(balance)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_propose_swap(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    counterparty: String,
+    give: Uint128,
+    want: Coin,
+    expires: Timestamp,
+    receiver: Option<String>,
+) -> Result<Response, ContractError> {
+    let counterparty = deps.api.addr_validate(&counterparty)?;
+    if give.is_zero() || want.amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    if counterparty == info.sender {
+        return Err(ContractError::InvalidSwap { reason: "cannot swap with oneself".to_string() });
+    }
+    if expires <= env.block.time {
+        return Err(ContractError::InvalidSwap { reason: "already expired".to_string() });
+    }
+    let denom = match BALANCES.may_load(deps.storage, &info.sender)? {
+        Some(balance) => balance.denom,
+        None => return Err(ContractError::AccountDoesNotExist { account: info.sender.to_string() }),
+    };
+    if denom == want.denom {
+        return Err(ContractError::InvalidSwap { reason: "cannot want the token given".to_string() });
+    }
+    if !TOKENS.has(deps.storage, want.denom.clone()) {
+        return Err(ContractError::TokenNotRegistered { denom: want.denom });
+    }
+    let balance = sender_balance(deps.as_ref(), &info, &denom, give)?;
+    assert_not_paused(deps.storage, &[
+        PauseScope::Denom { denom: denom.clone() },
+        PauseScope::Denom { denom: want.denom.clone() },
+    ])?;
+    let receiver = match receiver {
+        Some(receiver) => deps.api.addr_validate(&receiver)?,
+        None => info.sender.clone(),
+    };
+    assert_not_frozen(deps.storage, &receiver, &want.denom)?;
+    credit_account(deps.branch(), &receiver, &want.denom, Uint128::zero())?;
+    consume_allowance(deps.storage, &info.sender, &denom, give, env.block.time)?;
+
+    // escrow the given tokens until the swap is accepted or cancelled
+    BALANCES.save(deps.storage, &info.sender, &BalanceInfo { amount: balance.amount - give, denom: denom.clone() })?;
+    let id = SWAP_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    SWAP_COUNT.save(deps.storage, &id)?;
+    let swap = SwapOffer {
+        id,
+        proposer: info.sender,
+        counterparty,
+        give: Coin { denom, amount: give },
+        want,
+        receiver,
+        expires,
+        status: SwapStatus::Open,
+        created_at: env.block.time,
+    };
+    SWAP_OFFERS.save(deps.storage, id, &swap)?;
+    USER_SWAPS.save(deps.storage, (&swap.proposer, id), &())?;
+    USER_SWAPS.save(deps.storage, (&swap.counterparty, id), &())?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_swap")
+        .add_attribute("swap_id", id.to_string())
+        .add_attribute("counterparty", swap.counterparty)
+        .add_attribute("give", swap.give.to_string())
+        .add_attribute("want", swap.want.to_string()))
+}
+
+pub fn execute_accept_swap(mut deps: DepsMut, env: Env, info: MessageInfo, id: u64, receiver: Option<String>) -> Result<Response, ContractError> {
+    let mut swap = load_swap(deps.storage, id)?;
+    if info.sender != swap.counterparty {
+        return Err(ContractError::Unauthorized {});
+    }
+    if swap.status != SwapStatus::Open {
+        return Err(ContractError::SwapNotOpen { id });
+    }
+    if env.block.time >= swap.expires {
+        return Err(ContractError::SwapExpired { id });
+    }
+    let balance = sender_balance(deps.as_ref(), &info, &swap.want.denom, swap.want.amount)?;
+    assert_not_paused(deps.storage, &[
+        PauseScope::Denom { denom: swap.give.denom.clone() },
+        PauseScope::Denom { denom: swap.want.denom.clone() },
+    ])?;
+    assert_not_frozen(deps.storage, &swap.receiver, &swap.want.denom)?;
+    // the escrowed tokens go to an account of give.denom
+    let receiver = match receiver {
+        Some(receiver) => deps.api.addr_validate(&receiver)?,
+        None => info.sender.clone(),
+    };
+    assert_not_frozen(deps.storage, &receiver, &swap.give.denom)?;
+    credit_account(deps.branch(), &receiver, &swap.give.denom, Uint128::zero())?;
+    consume_allowance(deps.storage, &info.sender, &swap.want.denom, swap.want.amount, env.block.time)?;
+
+    BALANCES.save(deps.storage, &info.sender, &BalanceInfo { amount: balance.amount - swap.want.amount, denom: balance.denom })?;
+    credit_account(deps.branch(), &swap.receiver, &swap.want.denom, swap.want.amount)?;
+    credit_account(deps.branch(), &receiver, &swap.give.denom, swap.give.amount)?;
+    swap.status = SwapStatus::Accepted;
+    SWAP_OFFERS.save(deps.storage, id, &swap)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_swap")
+        .add_attribute("swap_id", id.to_string())
+        .add_attribute("give", swap.give.to_string())
+        .add_attribute("want", swap.want.to_string()))
+}
+
+// Expired swaps stay open until the proposer takes the escrow back
+pub fn execute_cancel_swap(mut deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+    let mut swap = load_swap(deps.storage, id)?;
+    if info.sender != swap.proposer {
+        return Err(ContractError::Unauthorized {});
+    }
+    if swap.status != SwapStatus::Open {
+        return Err(ContractError::SwapNotOpen { id });
+    }
+    credit_account(deps.branch(), &swap.proposer, &swap.give.denom, swap.give.amount)?;
+    swap.status = SwapStatus::Cancelled;
+    SWAP_OFFERS.save(deps.storage, id, &swap)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_swap")
+        .add_attribute("swap_id", id.to_string())
+        .add_attribute("refunded", swap.give.to_string()))
+}
+
+pub fn query_swap_offer(deps: Deps, id: u64) -> Result<Binary, ContractError> {
+    Ok(to_binary(&load_swap(deps.storage, id)?)?)
+}
+
+pub fn query_swap_offers(deps: Deps, address: String, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let swaps = USER_SWAPS
+        .prefix(&address)
+        .keys(deps.storage, start, None, StorageOrder::Ascending)
+        .take(limit)
+        .map(|id| SWAP_OFFERS.load(deps.storage, id?))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&SwapOffersResponse { swaps })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, query};
+    use crate::msg::{ExecuteMsg, QueryMsg};
+    use crate::testing::{balance, setup, MockDeps};
+
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{coin, from_binary};
+
+    fn propose(deps: &mut MockDeps, give: u128, want: Coin, expires: Timestamp) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::ProposeSwap {
+            counterparty: "addr0001".to_string(),
+            give: Uint128::from(give),
+            want,
+            expires,
+            receiver: Some("addr0002".to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg)
+    }
+
+    fn swap(deps: &MockDeps, id: u64) -> SwapOffer {
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::SwapOffer { id }).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn propose_and_accept() {
+        let mut deps = setup();
+        let expires = mock_env().block.time.plus_seconds(3600);
+
+        let err = propose(&mut deps, 500, coin(100, "RUB"), expires).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwap { .. }));
+        let err = propose(&mut deps, 500, coin(100, "USD"), mock_env().block.time).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwap { .. }));
+        let err = propose(&mut deps, 1001, coin(100, "USD"), expires).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+        propose(&mut deps, 500, coin(100, "USD"), expires).unwrap();
+        assert_eq!(Uint128::from(500u128), balance(&deps, "addr0000"));
+
+        let accept = |receiver: Option<&str>| ExecuteMsg::AcceptSwap { id: 1, receiver: receiver.map(str::to_string) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0002", &[]), accept(None)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        // the RUB cannot go to the USD account of the counterparty
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), accept(None)).unwrap_err();
+        assert!(matches!(err, ContractError::TokenAlreadyAssigned { .. }));
+        execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), accept(Some("addr0003"))).unwrap();
+        assert_eq!(Uint128::from(900u128), balance(&deps, "addr0001"));
+        assert_eq!(Uint128::from(100u128), balance(&deps, "addr0002"));
+        assert_eq!(Uint128::from(500u128), balance(&deps, "addr0003"));
+        assert_eq!(SwapStatus::Accepted, swap(&deps, 1).status);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), accept(Some("addr0003"))).unwrap_err();
+        assert!(matches!(err, ContractError::SwapNotOpen { id: 1 }));
+    }
+
+    #[test]
+    fn expired_swap_is_refunded() {
+        let mut deps = setup();
+        let expires = mock_env().block.time.plus_seconds(3600);
+        propose(&mut deps, 100, coin(20, "USD"), expires).unwrap();
+        propose(&mut deps, 100, coin(20, "USD"), expires).unwrap();
+
+        let mut env = mock_env();
+        env.block.time = expires;
+        let msg = ExecuteMsg::AcceptSwap { id: 1, receiver: Some("addr0003".to_string()) };
+        let err = execute(deps.as_mut(), env, mock_info("addr0001", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::SwapExpired { id: 1 }));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), ExecuteMsg::CancelSwap { id: 1 }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), ExecuteMsg::CancelSwap { id: 1 }).unwrap();
+        assert_eq!(Uint128::from(900u128), balance(&deps, "addr0000"));
+        assert_eq!(SwapStatus::Cancelled, swap(&deps, 1).status);
+
+        // both parties see their swaps
+        let swaps = |address: &str, start_after: Option<u64>| {
+            let msg = QueryMsg::SwapOffers { address: address.to_string(), start_after, limit: None };
+            from_binary::<SwapOffersResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+                .swaps.into_iter().map(|swap| swap.id).collect::<Vec<_>>()
+        };
+        assert_eq!(vec![1, 2], swaps("addr0000", None));
+        assert_eq!(vec![2], swaps("addr0001", Some(1)));
+        assert!(swaps("addr0002", None).is_empty());
+    }
+
+    #[test]
+    fn cancel_after_expiry() {
+        let mut deps = setup();
+        let expires = mock_env().block.time.plus_seconds(3600);
+        propose(&mut deps, 100, coin(20, "USD"), expires).unwrap();
+        propose(&mut deps, 100, coin(20, "USD"), expires).unwrap();
+        let at = |seconds: u64| {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(seconds);
+            env
+        };
+        let accept = |id: u64| ExecuteMsg::AcceptSwap { id, receiver: Some("addr0003".to_string()) };
+        let cancel = |id: u64| ExecuteMsg::CancelSwap { id };
+
+        // open until the second before it expires
+        execute(deps.as_mut(), at(3599), mock_info("addr0001", &[]), accept(1)).unwrap();
+        let err = execute(deps.as_mut(), at(3600), mock_info("addr0001", &[]), accept(2)).unwrap_err();
+        assert!(matches!(err, ContractError::SwapExpired { id: 2 }));
+
+        // the proposer takes back an expired swap once, an accepted one stays accepted
+        execute(deps.as_mut(), at(7200), mock_info("addr0000", &[]), cancel(2)).unwrap();
+        assert_eq!(Uint128::from(900u128), balance(&deps, "addr0000"));
+        assert_eq!(SwapStatus::Cancelled, swap(&deps, 2).status);
+        let err = execute(deps.as_mut(), at(7200), mock_info("addr0000", &[]), cancel(2)).unwrap_err();
+        assert!(matches!(err, ContractError::SwapNotOpen { id: 2 }));
+        let err = execute(deps.as_mut(), at(7200), mock_info("addr0000", &[]), cancel(1)).unwrap_err();
+        assert!(matches!(err, ContractError::SwapNotOpen { id: 1 }));
+        assert_eq!(Uint128::from(900u128), balance(&deps, "addr0000"));
+    }
+}
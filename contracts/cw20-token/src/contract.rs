@@ -1,59 +1,174 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128, to_binary};
+use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, to_binary, Order};
 use cw2::set_contract_version;
 
 use crate::error::ContractError;
 use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, TokenInfoResponse, TokenInfoMsg};
-use crate::state::{TOKENS, TokenInfo, BALANCES, BANKS, TRANSACTIONS, TransactionStatus, ExchangeRateInfo, EXCHANGE_RATES, BalanceInfo};
+use crate::state::{TOKENS, TokenInfo, BALANCES, BANKS, TRANSACTIONS, TransactionStatus, ExchangeRateInfo, EXCHANGE_RATES, BalanceInfo, Config, CONFIG, ORACLES, PRICE_SUBMISSIONS, CIRCUIT_BREAKERS, RATE_BREACHES, PauseScope, PAUSES, ROLES, FROZEN_ACCOUNTS, FREEZE_HISTORY, BankStatus};
+use crate::helpers::assert_not_paused;
+use crate::atomic_swaps;
+use crate::htlc;
+use crate::ibc_settlement;
+use crate::order_book;
+use crate::pools;
+use crate::standing_orders;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw20-trading";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+// default lifetime of an ICS-20 packet in seconds
+pub const DEFAULT_TIMEOUT: u64 = 600;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     _msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    CONFIG.save(deps.storage, &Config { admin: info.sender, guardian: None })?;
     Ok(Response::default())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
+    env: Env,
+    info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    // Only the pause management stays available while the contract is paused
+    if !matches!(msg, ExecuteMsg::Pause { .. } | ExecuteMsg::Unpause { .. } | ExecuteMsg::SetGuardian { .. }) {
+        assert_not_paused(deps.storage, &[])?;
+    }
+
     match msg {
         ExecuteMsg::CreateToken(token_info) => execute::execute_create_token(deps, token_info),
-        ExecuteMsg::CreateBank(bank_info) => execute::execute_create_bank(deps, bank_info),
-
-        ExecuteMsg::SetExchangeRate(exchange_rate) => execute::execute_set_exchange_rate(deps, exchange_rate),
-
-        ExecuteMsg::SendToBank(transaction_msg) => execute::execute_send_to_bank(deps, transaction_msg),
-        ExecuteMsg::SendToRecipient { transaction_id } => execute::execute_send_to_recipient(deps, transaction_id),
-}
+        ExecuteMsg::CreateBank(bank_info) => execute::execute_create_bank(deps, env, info, bank_info),
+
+        ExecuteMsg::SetExchangeRate(exchange_rate) => execute::execute_set_exchange_rate(deps, env, info, exchange_rate),
+
+        ExecuteMsg::SendToBank(transaction_msg) => execute::execute_send_to_bank(deps, env, info, transaction_msg),
+        ExecuteMsg::SendToRecipient { transaction_id, amount } => execute::execute_send_to_recipient(deps, env, transaction_id, amount),
+        ExecuteMsg::SendToBankBatch(batch) => execute::execute_send_to_bank_batch(deps, env, info, batch),
+        ExecuteMsg::SettleBatch { transaction_ids, mode } => execute::execute_settle_batch(deps, env, transaction_ids, mode),
+
+        ExecuteMsg::Transfer(transfer_msg) => execute::execute_transfer(deps, env, info, transfer_msg),
+        ExecuteMsg::SendToRemoteBank(transaction_msg) => execute::execute_send_to_remote_bank(deps, env, info, transaction_msg),
+        ExecuteMsg::SetBankChannel { bank_id, channel, counterparty_port } => ibc_settlement::execute_set_bank_channel(deps, info, bank_id, channel, counterparty_port),
+
+        ExecuteMsg::SetOracleConfig(oracle_config) => execute::execute_set_oracle_config(deps, info, oracle_config),
+        ExecuteMsg::SubmitPrice(submission) => execute::execute_submit_price(deps, env, info, submission),
+        ExecuteMsg::SetCircuitBreaker(circuit_breaker) => execute::execute_set_circuit_breaker(deps, env, info, circuit_breaker),
+        ExecuteMsg::ConfirmExchangeRate { id } => execute::execute_confirm_exchange_rate(deps, env, info, id),
+
+        ExecuteMsg::SetGuardian { guardian } => execute::execute_set_guardian(deps, info, guardian),
+        ExecuteMsg::Pause { scope } => execute::execute_pause(deps, env, info, scope),
+        ExecuteMsg::Unpause { scope } => execute::execute_unpause(deps, info, scope),
+
+        ExecuteMsg::GrantRole { role, address } => execute::execute_grant_role(deps, info, role, address),
+        ExecuteMsg::RevokeRole { role, address } => execute::execute_revoke_role(deps, info, role, address),
+        ExecuteMsg::Freeze { address, denom, reason } => execute::execute_freeze(deps, env, info, address, denom, reason),
+        ExecuteMsg::Unfreeze { address, denom, reason } => execute::execute_unfreeze(deps, env, info, address, denom, reason),
+        ExecuteMsg::SetKycTier { address, tier } => execute::execute_set_kyc_tier(deps, info, address, tier),
+        ExecuteMsg::SetTierLimits { tier, denom, limits } => execute::execute_set_tier_limits(deps, info, tier, denom, limits),
+        ExecuteMsg::SetKycRequired { denom, required } => execute::execute_set_kyc_required(deps, info, denom, required),
+        ExecuteMsg::SetComplianceCheckers { bank_id, checkers } => execute::execute_set_compliance_checkers(deps, info, bank_id, checkers),
+        ExecuteMsg::SetBankOperator { bank_id, operator } => execute::execute_set_bank_operator(deps, info, bank_id, operator),
+        ExecuteMsg::SetReviewThreshold { bank_id, denom, threshold } => execute::execute_set_review_threshold(deps, info, bank_id, denom, threshold),
+        ExecuteMsg::FlagTransaction { transaction_id, note } => execute::execute_flag_transaction(deps, env, info, transaction_id, note),
+        ExecuteMsg::ApproveTransaction { transaction_id, note } => execute::execute_approve_transaction(deps, env, info, transaction_id, note),
+        ExecuteMsg::RejectTransaction { transaction_id, note } => execute::execute_reject_transaction(deps, env, info, transaction_id, note),
+        ExecuteMsg::SetCancellationPolicy { bank_id, policy } => execute::execute_set_cancellation_policy(deps, info, bank_id, policy),
+        ExecuteMsg::AcknowledgeTransaction { transaction_id } => execute::execute_acknowledge_transaction(deps, env, info, transaction_id),
+        ExecuteMsg::CancelTransaction { transaction_id } => execute::execute_cancel_transaction(deps, info, transaction_id),
+        ExecuteMsg::CloseClearingCycle {} => execute::execute_close_clearing_cycle(deps, env, info),
+        ExecuteMsg::UpdateBank { bank_id, name, operator } => execute::execute_update_bank(deps, info, bank_id, name, operator),
+        ExecuteMsg::SuspendBank { bank_id } => execute::execute_set_bank_status(deps, info, bank_id, BankStatus::Suspended),
+        ExecuteMsg::ResumeBank { bank_id } => execute::execute_set_bank_status(deps, info, bank_id, BankStatus::Active),
+        ExecuteMsg::CloseBank { bank_id } => execute::execute_set_bank_status(deps, info, bank_id, BankStatus::Closed),
+        ExecuteMsg::SetCollateralConfig(config) => execute::execute_set_collateral_config(deps, info, config),
+        ExecuteMsg::PostCollateral { bank_id, amount } => execute::execute_post_collateral(deps, env, info, bank_id, amount),
+        ExecuteMsg::WithdrawCollateral { bank_id, amount } => execute::execute_withdraw_collateral(deps, info, bank_id, amount),
+        ExecuteMsg::SetSettlementSla { bank_id, seconds } => execute::execute_set_settlement_sla(deps, info, bank_id, seconds),
+        ExecuteMsg::SetBankFee { bank_id, fee_bps } => execute::execute_set_bank_fee(deps, info, bank_id, fee_bps),
+        ExecuteMsg::ClaimDefault { transaction_id, receiver } => execute::execute_claim_default(deps, env, info, transaction_id, receiver),
+        ExecuteMsg::PlaceOrder { buy_denom, amount, price, receiver } => order_book::execute_place_order(deps, env, info, buy_denom, amount, price, receiver),
+        ExecuteMsg::CancelOrder { order_id } => order_book::execute_cancel_order(deps, info, order_id),
+        ExecuteMsg::CreatePool { denom_a, denom_b, fee_bps } => pools::execute_create_pool(deps, info, denom_a, denom_b, fee_bps),
+        ExecuteMsg::AddLiquidity { pool_id, amount, min_shares, other_amount } => pools::execute_add_liquidity(deps, env, info, pool_id, amount, min_shares, other_amount),
+        ExecuteMsg::CancelPoolSeed { pool_id } => pools::execute_cancel_pool_seed(deps, info, pool_id),
+        ExecuteMsg::RemoveLiquidity { pool_id, shares, receiver } => pools::execute_remove_liquidity(deps, env, info, pool_id, shares, receiver),
+        ExecuteMsg::Swap { pool_id, amount, min_received, receiver } => pools::execute_swap(deps, env, info, pool_id, amount, min_received, receiver),
+        ExecuteMsg::SetBankPoolRouting { bank_id, max_slippage_bps } => pools::execute_set_bank_pool_routing(deps, info, bank_id, max_slippage_bps),
+        ExecuteMsg::ProposeSwap { counterparty, give, want, expires, receiver } => atomic_swaps::execute_propose_swap(deps, env, info, counterparty, give, want, expires, receiver),
+        ExecuteMsg::AcceptSwap { id, receiver } => atomic_swaps::execute_accept_swap(deps, env, info, id, receiver),
+        ExecuteMsg::CancelSwap { id } => atomic_swaps::execute_cancel_swap(deps, info, id),
+        ExecuteMsg::LockWithHash { recipient, amount, denom, hashlock, timelock } => htlc::execute_lock_with_hash(deps, env, info, recipient, amount, denom, hashlock, timelock),
+        ExecuteMsg::Claim { id, preimage } => htlc::execute_claim(deps, env, id, preimage),
+        ExecuteMsg::Refund { id } => htlc::execute_refund(deps, env, id),
+        ExecuteMsg::CreateStandingOrder(order) => standing_orders::execute_create_standing_order(deps, env, info, order),
+        ExecuteMsg::CancelStandingOrder { id } => standing_orders::execute_cancel_standing_order(deps, info, id),
+        ExecuteMsg::Crank { limit } => standing_orders::execute_crank(deps, env, info, limit),
+        ExecuteMsg::FundBank { bank_id, amount } => execute::execute_fund_bank(deps, env, info, bank_id, amount),
+        ExecuteMsg::DefundBank { bank_id, denom, amount, receiver } => execute::execute_defund_bank(deps, info, bank_id, denom, amount, receiver),
+    }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
     match msg {
         QueryMsg::Balance { address } => query::query_balance(deps, address),
         QueryMsg::TokenInfo { denom } => query::query_token_info(deps, denom),
         QueryMsg::BankInfo { id } => query::query_bank_info(deps, id),
         QueryMsg::TransactionInfo { id } => query::query_transaction_info(deps, id),
         QueryMsg::ExchangeRateInfo { id } => query::query_exchange_rate_info(deps, id),
+        QueryMsg::ChannelInfo { id } => query::query_channel_info(deps, id),
+        QueryMsg::Config {} => query::query_config(deps),
+        QueryMsg::OracleConfig { id } => query::query_oracle_config(deps, id),
+        QueryMsg::PriceSubmissions { id } => query::query_price_submissions(deps, id, env),
+        QueryMsg::CircuitBreaker { id } => query::query_circuit_breaker(deps, id),
+        QueryMsg::RateBreaches { id, start_after, limit } => query::query_rate_breaches(deps, id, start_after, limit),
+        QueryMsg::Pauses {} => query::query_pauses(deps),
+        QueryMsg::RoleMembers { role, start_after, limit } => query::query_role_members(deps, role, start_after, limit),
+        QueryMsg::FrozenAccounts { start_after, limit } => query::query_frozen_accounts(deps, start_after, limit),
+        QueryMsg::FreezeHistory { address, start_after, limit } => query::query_freeze_history(deps, address, start_after, limit),
+        QueryMsg::RemainingAllowance { address, denom } => query::query_remaining_allowance(deps, env, address, denom),
+        QueryMsg::ReviewQueue { start_after, limit } => query::query_review_queue(deps, start_after, limit),
+        QueryMsg::NetObligations {} => query::query_net_obligations(deps, env),
+        QueryMsg::ClearingCycles { start_after, limit } => query::query_clearing_cycles(deps, start_after, limit),
+        QueryMsg::Solvency { start_after, limit } => query::query_solvency(deps, start_after, limit),
+        QueryMsg::BankDefaults { bank_id, start_after, limit } => query::query_bank_defaults(deps, bank_id, start_after, limit),
+        QueryMsg::BankStats { bank_id } => query::query_bank_stats(deps, bank_id),
+        QueryMsg::RoutePreview { from, to, amount } => query::query_route_preview(deps, from, to, amount),
+        QueryMsg::OrderBook { sell_denom, buy_denom, limit } => order_book::query_order_book(deps, sell_denom, buy_denom, limit),
+        QueryMsg::OpenOrders { owner, start_after, limit } => order_book::query_open_orders(deps, owner, start_after, limit),
+        QueryMsg::Pool { pool_id } => pools::query_pool(deps, pool_id),
+        QueryMsg::PoolSeed { pool_id } => pools::query_pool_seed(deps, pool_id),
+        QueryMsg::SpotPrice { pool_id, denom_from } => pools::query_spot_price(deps, pool_id, denom_from),
+        QueryMsg::LiquidityShares { pool_id, address } => pools::query_liquidity_shares(deps, pool_id, address),
+        QueryMsg::PoolPrices { pool_id, start_after, limit } => pools::query_pool_prices(deps, pool_id, start_after, limit),
+        QueryMsg::SwapOffer { id } => atomic_swaps::query_swap_offer(deps, id),
+        QueryMsg::SwapOffers { address, start_after, limit } => atomic_swaps::query_swap_offers(deps, address, start_after, limit),
+        QueryMsg::HashLock { id } => htlc::query_hash_lock(deps, id),
+        QueryMsg::HashLocksByHash { hashlock, start_after, limit } => htlc::query_hash_locks_by_hash(deps, hashlock, start_after, limit),
+        QueryMsg::HashLocks { address, start_after, limit } => htlc::query_hash_locks(deps, address, start_after, limit),
+        QueryMsg::StandingOrder { id } => standing_orders::query_standing_order(deps, id),
+        QueryMsg::StandingOrders { owner, start_after, limit } => standing_orders::query_standing_orders(deps, owner, start_after, limit),
+        QueryMsg::UpcomingExecutions { limit } => standing_orders::query_upcoming_executions(deps, limit),
+        QueryMsg::StandingOrderHistory { id, start_after, limit } => standing_orders::query_standing_order_history(deps, id, start_after, limit),
+        QueryMsg::BankRanking { metric, limit } => query::query_bank_ranking(deps, metric, limit),
     }
 }
 
 pub mod execute {
 
-    use crate::{state::{TransactionInfo, BankInfo}, msg::{ExchangeRateMsg, TransactionMsg}, helpers::create_accounts};
+    use cosmwasm_std::{from_binary, Addr, Event, IbcMsg, IbcTimeout, Storage, Timestamp, Uint128};
+    use cw_storage_plus::Map;
+
+    use crate::{state::{TransactionInfo, BankInfo, CHANNEL_INFO, OracleConfig, PriceSubmission, CircuitBreakerInfo, RatePoint, RateBreach, BreachKind, PauseInfo, Role, FrozenAccount, FreezeAction, FreezeRecord, FREEZE_HISTORY_COUNT, TierLimits, KYC_TIERS, KYC_REQUIRED, TIER_LIMITS, BANK_CHECKERS, BANK_OPERATORS, REVIEW_THRESHOLDS, REVIEW_QUEUE, ReviewQueueEntry, ReviewRecord, ReviewAction, TRANSACTION_COUNT, IDEMPOTENCY_KEYS, CancellationPolicy, CANCELLATION_POLICIES, Tranche, ClearingCycle, ClearingCycleInfo, CLEARING_CYCLE, CLEARING_HISTORY, BANK_RESERVES, BANK_OUTSTANDING, BANK_STATUSES, CollateralConfig, COLLATERAL_CONFIG, BANK_COLLATERAL, SETTLEMENT_SLAS, BANK_DEFAULTS, DefaultRecord, BANK_FEES}, msg::{ComplianceStage, TransactionIdResponse, BatchTransactionMsg, BatchMode, BatchItemResult, BatchResponse, ExchangeRateMsg, TransactionMsg, TransferMsg, RemoteTransactionMsg, OracleConfigMsg, PriceSubmissionMsg, CircuitBreakerMsg}, helpers::{create_accounts, exchange, assert_admin, assert_admin_or_guardian, median, aggregate, deviation_bps, assert_rate_confirmed, settlement_scopes, assert_role, assert_not_frozen, assert_allowlisted, consume_allowance, check_compliance, assert_bank_operator, assert_bank_active, assert_bank_open, assert_within_exposure, bank_exposure, to_collateral, record_accepted, update_bank_stats, route_transaction, after_fee, next_transaction_id, record_obligation, current_cycle, net_positions, net_transfers}, ibc::{Ics20Packet, increase_channel_balance, credit_account, ICS20_VERSION}, ibc_settlement::{TradingPacket, SettlementInstruction}};
 
     use super::*;
 
@@ -62,6 +177,7 @@ pub mod execute {
         if TOKENS.has(deps.storage, denom.clone()) {
             return Err(ContractError::TokenAlreadyRegistered { denom });
         }
+        assert_not_paused(deps.storage, &[PauseScope::Denom { denom: denom.clone() }])?;
         
         let total_supply = create_accounts(&mut deps, &token_info.initial_balances, denom.clone())?;
 
@@ -76,22 +192,57 @@ pub mod execute {
         Ok(Response::default())
     }
 
-    pub fn execute_create_bank(deps: DepsMut, bank_info: BankInfo) -> Result<Response, ContractError> {
+    pub fn execute_create_bank(deps: DepsMut, env: Env, info: MessageInfo, bank_info: BankInfo) -> Result<Response, ContractError> {
         let bank_id = bank_info.id.clone();
         if BANKS.has(deps.storage, bank_id.clone()) {
             return Err(ContractError::BankAlreadyExists { id: bank_id.clone() });
         }
+        assert_not_paused(deps.storage, &[PauseScope::Bank { id: bank_id.clone() }])?;
+        // the creator locks the required collateral
+        if let Some(config) = COLLATERAL_CONFIG.may_load(deps.storage)? {
+            if !config.amount.is_zero() {
+                lock_collateral(deps.storage, &info.sender, &bank_id, &config.denom, config.amount, env.block.time)?;
+            }
+        }
         BANKS.save(deps.storage, bank_id,  &bank_info)?;
         Ok(Response::default())
     }
 
-    pub fn execute_set_exchange_rate(deps: DepsMut, exchange_rate: ExchangeRateMsg) -> Result<Response, ContractError> {
+    pub fn execute_set_exchange_rate(deps: DepsMut, env: Env, info: MessageInfo, exchange_rate: ExchangeRateMsg) -> Result<Response, ContractError> {
+        assert_role(deps.as_ref(), &info.sender, Role::Oracle)?;
+        let exchange_rate_id = exchange_rate.denom_from.to_owned() + exchange_rate.denom_to.as_str();
+        let exchange_rate_id_verse = exchange_rate.denom_to.to_owned() + exchange_rate.denom_from.as_str();
+
+        // Rates fed by oracles can only change through aggregation
+        if ORACLES.has(deps.storage, exchange_rate_id.clone()) || ORACLES.has(deps.storage, exchange_rate_id_verse) {
+            return Err(ContractError::ExchangeRateManagedByOracle { id: exchange_rate_id });
+        }
+        if exchange_rate.rate == 0 {
+            return Err(ContractError::ZeroAmount {});
+        }
+        assert_not_paused(deps.storage, &PauseScope::exchange_rates(&exchange_rate.denom_from, &exchange_rate.denom_to))?;
+
+        let breach = save_exchange_rate(deps, env.block.time, &exchange_rate)?;
+        Ok(Response::new().add_events(breach))
+    }
+
+    // Returns the event of a tripped circuit breaker, if the new rate moved too far
+    fn save_exchange_rate(deps: DepsMut, now: Timestamp, exchange_rate: &ExchangeRateMsg) -> Result<Option<Event>, ContractError> {
         let exchange_rate_id = exchange_rate.denom_from.to_owned() + exchange_rate.denom_to.as_str();
         let exchange_rate_id_verse = exchange_rate.denom_to.to_owned() + exchange_rate.denom_from.as_str();
 
         // We cannot store f64 in the state
-        let base = 10_u128.pow(exchange_rate.precision);
-        let verse_rate = base * base / exchange_rate.rate as u128;
+        let base = match 10_u128.checked_pow(exchange_rate.precision * 2) {
+            Some(base) => base,
+            None => return Err(ContractError::InvalidExchangeRate { reason: "precision is too large".to_string() }),
+        };
+        // the inverse has to be representable, a zero one would pay nothing the other way
+        let verse_rate = match u64::try_from(base / exchange_rate.rate as u128) {
+            Ok(0) | Err(_) => return Err(ContractError::InvalidExchangeRate {
+                reason: "the inverse rate does not fit the precision".to_string(),
+            }),
+            Ok(verse_rate) => verse_rate,
+        };
 
         let exchange_rate_state = ExchangeRateInfo {
             id: exchange_rate_id.clone(),
@@ -107,25 +258,296 @@ pub mod execute {
             denom_from: exchange_rate.denom_to.clone(),
             denom_to: exchange_rate.denom_from.clone(),
             precision: exchange_rate.precision,
-            rate: verse_rate as u64,
+            rate: verse_rate,
         };
+        // Check the move against the previous rates before overwriting them
+        let mut breach = None;
+        for state in [&exchange_rate_state, &exchange_rate_verse_state] {
+            if let Some(mut breaker) = CIRCUIT_BREAKERS.may_load(deps.storage, state.id.clone())? {
+                let previous = EXCHANGE_RATES.may_load(deps.storage, state.id.clone())?;
+                breach = check_circuit_breaker(deps.storage, &mut breaker, previous, state, now)?;
+                CIRCUIT_BREAKERS.save(deps.storage, breaker.id.clone(), &breaker)?;
+            }
+        }
+
         EXCHANGE_RATES.save(deps.storage, exchange_rate_id,  &exchange_rate_state)?;
         EXCHANGE_RATES.save(deps.storage, exchange_rate_id_verse,  &exchange_rate_verse_state)?;
-        Ok(Response::default())
+        Ok(breach)
     }
 
-    pub fn execute_send_to_bank(deps: DepsMut, transaction_info: TransactionMsg) -> Result<Response, ContractError> {
-        let bank_id = transaction_info.bank_id.clone();
-        let transaction_id = transaction_info.id.clone();
+    fn check_circuit_breaker(
+        storage: &mut dyn Storage,
+        breaker: &mut CircuitBreakerInfo,
+        previous: Option<ExchangeRateInfo>,
+        new_rate: &ExchangeRateInfo,
+        now: Timestamp,
+    ) -> Result<Option<Event>, ContractError> {
+        let point = RatePoint { rate: new_rate.rate, precision: new_rate.precision, timestamp: now };
+
+        // Forget rates older than the window
+        breaker.history.retain(|old| old.timestamp.plus_seconds(breaker.window) >= now);
+
+        let mut breach = None;
+        if let Some(previous) = previous {
+            let previous = RatePoint { rate: previous.rate, precision: previous.precision, timestamp: now };
+            let precision = previous.precision.max(point.precision);
+            let deviation = deviation_bps(previous.scaled(precision), point.scaled(precision));
+            if deviation > breaker.max_update_deviation_bps as u128 {
+                breach = Some(RateBreach {
+                    kind: BreachKind::Update,
+                    reference_rate: previous.rate,
+                    new_rate: point.rate,
+                    deviation_bps: deviation as u64,
+                    timestamp: now,
+                });
+            }
+        }
+        if breach.is_none() {
+            for old in breaker.history.iter() {
+                let precision = old.precision.max(point.precision);
+                let deviation = deviation_bps(old.scaled(precision), point.scaled(precision));
+                if deviation > breaker.max_window_deviation_bps as u128 {
+                    breach = Some(RateBreach {
+                        kind: BreachKind::Window,
+                        reference_rate: old.rate,
+                        new_rate: point.rate,
+                        deviation_bps: deviation as u64,
+                        timestamp: now,
+                    });
+                    break;
+                }
+            }
+        }
+        breaker.history.push(point);
+
+        let breach = match breach {
+            Some(breach) => breach,
+            None => return Ok(None),
+        };
+        RATE_BREACHES.save(storage, (breaker.id.clone(), breaker.breach_count), &breach)?;
+        breaker.breach_count += 1;
+        breaker.breach = Some(breach.clone());
+
+        Ok(Some(Event::new("circuit_breaker_tripped")
+            .add_attribute("id", breaker.id.clone())
+            .add_attribute("kind", match breach.kind {
+                BreachKind::Update => "update",
+                BreachKind::Window => "window",
+            })
+            .add_attribute("reference_rate", breach.reference_rate.to_string())
+            .add_attribute("new_rate", breach.new_rate.to_string())
+            .add_attribute("deviation_bps", breach.deviation_bps.to_string())))
+    }
+
+    pub fn execute_set_circuit_breaker(deps: DepsMut, env: Env, info: MessageInfo, circuit_breaker: CircuitBreakerMsg) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let id = circuit_breaker.denom_from.to_owned() + circuit_breaker.denom_to.as_str();
+        let id_verse = circuit_breaker.denom_to.to_owned() + circuit_breaker.denom_from.as_str();
+        if CIRCUIT_BREAKERS.has(deps.storage, id_verse) {
+            return Err(ContractError::InvalidCircuitBreaker { reason: "circuit breaker is configured for the verse rate".to_string() });
+        }
+        if circuit_breaker.window == 0 {
+            return Err(ContractError::InvalidCircuitBreaker { reason: "window must not be zero".to_string() });
+        }
+
+        // Keep the history and the pending breach when updating the thresholds
+        let breaker = match CIRCUIT_BREAKERS.may_load(deps.storage, id.clone())? {
+            Some(breaker) => CircuitBreakerInfo {
+                max_update_deviation_bps: circuit_breaker.max_update_deviation_bps,
+                max_window_deviation_bps: circuit_breaker.max_window_deviation_bps,
+                window: circuit_breaker.window,
+                ..breaker
+            },
+            None => CircuitBreakerInfo {
+                id: id.clone(),
+                denom_from: circuit_breaker.denom_from,
+                denom_to: circuit_breaker.denom_to,
+                max_update_deviation_bps: circuit_breaker.max_update_deviation_bps,
+                max_window_deviation_bps: circuit_breaker.max_window_deviation_bps,
+                window: circuit_breaker.window,
+                history: EXCHANGE_RATES.may_load(deps.storage, id.clone())?
+                    .map(|rate| RatePoint { rate: rate.rate, precision: rate.precision, timestamp: env.block.time })
+                    .into_iter()
+                    .collect(),
+                breach: None,
+                breach_count: 0,
+            },
+        };
+        CIRCUIT_BREAKERS.save(deps.storage, id.clone(), &breaker)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_circuit_breaker")
+            .add_attribute("id", id))
+    }
+
+    pub fn execute_confirm_exchange_rate(deps: DepsMut, env: Env, info: MessageInfo, id: String) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let mut breaker = match CIRCUIT_BREAKERS.load(deps.storage, id.clone()) {
+            Ok(breaker) => breaker,
+            Err(_) => return Err(ContractError::CircuitBreakerDoesNotExist { id }),
+        };
+        // The confirmed rate becomes the new reference
+        let rate = EXCHANGE_RATES.load(deps.storage, id.clone())?;
+        breaker.breach = None;
+        breaker.history = vec![RatePoint { rate: rate.rate, precision: rate.precision, timestamp: env.block.time }];
+        CIRCUIT_BREAKERS.save(deps.storage, id.clone(), &breaker)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "confirm_exchange_rate")
+            .add_attribute("id", id)
+            .add_attribute("rate", rate.rate.to_string()))
+    }
+
+    pub fn execute_set_oracle_config(deps: DepsMut, info: MessageInfo, oracle_config: OracleConfigMsg) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let id = oracle_config.denom_from.to_owned() + oracle_config.denom_to.as_str();
+        let id_verse = oracle_config.denom_to.to_owned() + oracle_config.denom_from.as_str();
+        if ORACLES.has(deps.storage, id_verse) {
+            return Err(ContractError::InvalidOracleConfig { reason: "oracle is configured for the verse rate".to_string() });
+        }
+        if oracle_config.denom_from == oracle_config.denom_to {
+            return Err(ContractError::InvalidOracleConfig { reason: "denoms must differ".to_string() });
+        }
+        let mut feeders = oracle_config.feeders.iter()
+            .map(|feeder| deps.api.addr_validate(feeder))
+            .collect::<StdResult<Vec<_>>>()?;
+        feeders.sort();
+        feeders.dedup();
+        if oracle_config.quorum == 0 || oracle_config.quorum as usize > feeders.len() {
+            return Err(ContractError::InvalidOracleConfig { reason: "quorum must be between 1 and the number of feeders".to_string() });
+        }
+        if oracle_config.max_age == 0 {
+            return Err(ContractError::InvalidOracleConfig { reason: "max_age must not be zero".to_string() });
+        }
+
+        // Forget the prices of feeders that are no longer registered
+        let removed = PRICE_SUBMISSIONS
+            .prefix(id.clone())
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?
+            .into_iter()
+            .filter(|feeder| !feeders.contains(feeder))
+            .collect::<Vec<_>>();
+        for feeder in removed {
+            PRICE_SUBMISSIONS.remove(deps.storage, (id.clone(), &feeder));
+        }
+
+        let oracle = OracleConfig {
+            id: id.clone(),
+            denom_from: oracle_config.denom_from,
+            denom_to: oracle_config.denom_to,
+            feeders,
+            quorum: oracle_config.quorum,
+            precision: oracle_config.precision,
+            max_age: oracle_config.max_age,
+            max_deviation_bps: oracle_config.max_deviation_bps,
+            aggregation: oracle_config.aggregation,
+        };
+        ORACLES.save(deps.storage, id.clone(), &oracle)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_oracle_config")
+            .add_attribute("id", id))
+    }
+
+    pub fn execute_submit_price(deps: DepsMut, env: Env, info: MessageInfo, submission: PriceSubmissionMsg) -> Result<Response, ContractError> {
+        let id = submission.denom_from.to_owned() + submission.denom_to.as_str();
+        let oracle = match ORACLES.load(deps.storage, id.clone()) {
+            Ok(oracle) => oracle,
+            Err(_) => return Err(ContractError::OracleDoesNotExist { id }),
+        };
+        if !oracle.feeders.contains(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        assert_not_paused(deps.storage, &PauseScope::exchange_rates(&oracle.denom_from, &oracle.denom_to))?;
+        if submission.rate == 0 {
+            return Err(ContractError::ZeroAmount {});
+        }
+
+        // Reject prices observed too long ago or in the future
+        let now = env.block.time;
+        if submission.timestamp > now || submission.timestamp.plus_seconds(oracle.max_age) < now {
+            return Err(ContractError::StalePrice { id });
+        }
+
+        let fresh = fresh_submissions(deps.as_ref(), &oracle, now)?;
+        let others = fresh.iter()
+            .filter(|price| price.feeder != info.sender)
+            .map(|price| price.rate)
+            .collect::<Vec<_>>();
+        // A single other price is not enough to tell who is the outlier
+        if others.len() >= 2 {
+            let median = median(&others);
+            if deviation_bps(median as u128, submission.rate as u128) > oracle.max_deviation_bps as u128 {
+                return Err(ContractError::OutlierPrice { rate: submission.rate, median });
+            }
+        }
+
+        PRICE_SUBMISSIONS.save(deps.storage, (id.clone(), &info.sender), &PriceSubmission {
+            feeder: info.sender.clone(),
+            rate: submission.rate,
+            timestamp: submission.timestamp,
+        })?;
+
+        let mut response = Response::new()
+            .add_attribute("action", "submit_price")
+            .add_attribute("id", id)
+            .add_attribute("feeder", info.sender.clone());
+
+        let mut rates = others;
+        rates.push(submission.rate);
+        if rates.len() >= oracle.quorum as usize {
+            let rate = aggregate(&rates, &oracle.aggregation);
+            let breach = save_exchange_rate(deps, now, &ExchangeRateMsg {
+                denom_from: oracle.denom_from,
+                denom_to: oracle.denom_to,
+                precision: oracle.precision,
+                rate,
+            })?;
+            response = response
+                .add_attribute("rate", rate.to_string())
+                .add_events(breach);
+        }
+
+        Ok(response)
+    }
+
+    pub fn fresh_submissions(deps: Deps, oracle: &OracleConfig, now: Timestamp) -> StdResult<Vec<PriceSubmission>> {
+        let submissions = PRICE_SUBMISSIONS
+            .prefix(oracle.id.clone())
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, price)| price))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(submissions.into_iter()
+            .filter(|price| price.timestamp.plus_seconds(oracle.max_age) >= now)
+            .collect())
+    }
+
+    pub fn execute_send_to_bank(deps: DepsMut, env: Env, info: MessageInfo, mut transaction_info: TransactionMsg) -> Result<Response, ContractError> {
+        // A retry returns the transaction created by the first attempt
+        if let Some(response) = idempotent_response(deps.as_ref(), &info.sender, &transaction_info.idempotency_key)? {
+            return Ok(response);
+        }
+        let (transaction_index, transaction_id) = next_transaction_id(deps.storage)?;
+        let route = match transaction_info.bank_id {
+            Some(_) => None,
+            None => Some(route_transaction(deps.storage, &transaction_info.from, &transaction_info.to, transaction_info.amount)?),
+        };
+        let bank_id = match &route {
+            Some(route) => route.chosen.bank_id.clone(),
+            None => transaction_info.bank_id.clone().unwrap_or_default(),
+        };
+        // checkers see the bank the transaction goes to
+        transaction_info.bank_id = Some(bank_id.clone());
         // Validations
         // Check if bank exists
         if !BANKS.has(deps.storage, bank_id.clone()) {
             return Err(ContractError::BankNotRegistered { id: bank_id.clone() });
         }
-        // Check if transaction exists
-        if TRANSACTIONS.has(deps.storage, transaction_id.clone()) {
-            return Err(ContractError::TransactionAlreadyExists { id: transaction_id.clone()});
-        }
+        assert_bank_active(deps.storage, &bank_id)?;
 
         if !BALANCES.has(deps.storage, &transaction_info.from) {
             return Err(ContractError::AccountDoesNotExist{account: transaction_info.from.to_string()});
@@ -139,6 +561,26 @@ pub mod execute {
         if balance_from.amount < transaction_info.amount {
             return Err(ContractError::NotEnoughBalance { required: transaction_info.amount, available: balance_from.amount});
         }
+        let balance_to = BALANCES.load(deps.storage, &transaction_info.to)?;
+        assert_not_paused(deps.storage, &[
+            PauseScope::Bank { id: bank_id.clone() },
+            PauseScope::Denom { denom: balance_from.denom.clone() },
+            PauseScope::Denom { denom: balance_to.denom.clone() },
+        ])?;
+        assert_not_frozen(deps.storage, &transaction_info.from, &balance_from.denom)?;
+        assert_not_frozen(deps.storage, &transaction_info.to, &balance_to.denom)?;
+        assert_allowlisted(deps.storage, &transaction_info.to, &balance_to.denom)?;
+        assert_within_exposure(deps.storage, &bank_id, &balance_from.denom, transaction_info.amount)?;
+        let compliance_review = check_compliance(deps.as_ref(), &transaction_id, &transaction_info, ComplianceStage::SendToBank)?;
+        // last check and first write, best-effort batches rely on failures leaving no trace
+        consume_allowance(deps.storage, &transaction_info.from, &balance_from.denom, transaction_info.amount, env.block.time)?;
+        let threshold = REVIEW_THRESHOLDS.may_load(deps.storage, (bank_id.clone(), balance_from.denom.clone()))?;
+        let review = match compliance_review {
+            Some((checker, reason)) => Some(format!("compliance checker {}: {}", checker, reason)),
+            None => threshold
+                .filter(|threshold| transaction_info.amount >= *threshold)
+                .map(|threshold| format!("amount rule: at least {} {}", threshold, balance_from.denom)),
+        };
         // decrease sender balance
         BALANCES.update(deps.storage, &transaction_info.from, |balance| -> StdResult<_> {
             Ok(
@@ -151,25 +593,443 @@ pub mod execute {
         BANKS.update(deps.storage, bank_id.clone(), |bank| -> StdResult<_> {
             Ok(bank.unwrap().income(transaction_info.amount))
         })?;
-
-        let balance_to = BALANCES.load(deps.storage, &transaction_info.to)?;
+        update_outstanding(deps.storage, &bank_id, &balance_from.denom, |outstanding| outstanding + transaction_info.amount)?;
 
         let transaction = TransactionInfo {
             id: transaction_id.clone(),
             bank_id: bank_id.clone(),
             from: transaction_info.from.clone(),
             to: transaction_info.to.clone(),
-            amount: transaction_info.amount.clone(),
+            amount: transaction_info.amount,
             denom_from: balance_from.denom.clone(),
             denom_to: balance_to.denom.clone(),
             status: TransactionStatus::SentToBank,
+            channel_id: None,
+            reviews: vec![],
+            acknowledged_at: None,
+            settled: Uint128::zero(),
+            compensated: Uint128::zero(),
+            tranches: vec![],
+            deadline: SETTLEMENT_SLAS.may_load(deps.storage, bank_id.clone())?.map(|seconds| env.block.time.plus_seconds(seconds)),
+            created_at: Some(env.block.time),
+            route,
         };
+        record_accepted(deps.storage, &bank_id, &transaction.denom_from, transaction.amount)?;
         // Update transaction status
-        TRANSACTIONS.save(deps.storage, transaction_id, &transaction)?;
-        Ok(Response::default())
+        match review {
+            Some(note) => flag_for_review(deps.storage, transaction, env.contract.address, note, env.block.time)?,
+            None => TRANSACTIONS.save(deps.storage, transaction_id.clone(), &transaction)?,
+        }
+        save_transaction_id(deps.storage, transaction_index, &info.sender, transaction_info.idempotency_key)?;
+        Ok(Response::new()
+            .set_data(to_binary(&TransactionIdResponse { transaction_id: transaction_id.clone() })?)
+            .add_attribute("action", "send_to_bank")
+            .add_attribute("transaction_id", transaction_id))
+    }
+
+    pub fn execute_send_to_bank_batch(mut deps: DepsMut, env: Env, info: MessageInfo, batch: BatchTransactionMsg) -> Result<Response, ContractError> {
+        if batch.payments.is_empty() {
+            return Err(ContractError::EmptyBatch {});
+        }
+        // only the sender or the admin running its payouts spends from the account
+        if batch.from != info.sender {
+            assert_admin(deps.as_ref(), &info.sender)?;
+        }
+        // retries are recognised whoever submits them
+        let info = MessageInfo { sender: batch.from.clone(), funds: info.funds };
+        let mut results = vec![];
+        for payment in batch.payments {
+            let transaction_info = TransactionMsg {
+                bank_id: Some(batch.bank_id.clone()),
+                from: batch.from.clone(),
+                to: payment.to,
+                amount: payment.amount,
+                idempotency_key: payment.idempotency_key,
+            };
+            let result = execute_send_to_bank(deps.branch(), env.clone(), info.clone(), transaction_info)
+                .and_then(|res| Ok(from_binary::<TransactionIdResponse>(&res.data.unwrap_or_default())?.transaction_id));
+            results.push(batch_item_result(result, None, &batch.mode)?);
+        }
+        batch_response("send_to_bank_batch", results)
+    }
+
+    pub fn execute_settle_batch(mut deps: DepsMut, env: Env, transaction_ids: Vec<String>, mode: BatchMode) -> Result<Response, ContractError> {
+        if transaction_ids.is_empty() {
+            return Err(ContractError::EmptyBatch {});
+        }
+        let mut results = vec![];
+        for transaction_id in transaction_ids {
+            let result = execute_send_to_recipient(deps.branch(), env.clone(), transaction_id.clone(), None)
+                .map(|_| transaction_id.clone());
+            results.push(batch_item_result(result, Some(transaction_id), &mode)?);
+        }
+        batch_response("settle_batch", results)
+    }
+
+    // Failed items abort an all-or-nothing batch and are reported in a best-effort one
+    fn batch_item_result(result: Result<String, ContractError>, transaction_id: Option<String>, mode: &BatchMode) -> Result<BatchItemResult, ContractError> {
+        match (result, mode) {
+            (Ok(transaction_id), _) => Ok(BatchItemResult { transaction_id: Some(transaction_id), error: None }),
+            (Err(err), BatchMode::AllOrNothing) => Err(err),
+            (Err(err), BatchMode::BestEffort) => Ok(BatchItemResult { transaction_id, error: Some(err.to_string()) }),
+        }
+    }
+
+    fn batch_response(action: &str, results: Vec<BatchItemResult>) -> Result<Response, ContractError> {
+        let failed = results.iter().filter(|result| result.error.is_some()).count();
+        Ok(Response::new()
+            .set_data(to_binary(&BatchResponse { results: results.clone() })?)
+            .add_attribute("action", action)
+            .add_attribute("succeeded", (results.len() - failed).to_string())
+            .add_attribute("failed", failed.to_string()))
+    }
+
+    pub fn execute_close_clearing_cycle(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let cycle = current_cycle(deps.storage, env.block.time)?;
+        let positions = net_positions(deps.storage, cycle.id)?;
+        let transfers = net_transfers(&positions);
+        CLEARING_HISTORY.save(deps.storage, cycle.id, &ClearingCycleInfo {
+            id: cycle.id,
+            opened_at: cycle.opened_at,
+            closed_at: env.block.time,
+            positions,
+            transfers: transfers.clone(),
+        })?;
+        CLEARING_CYCLE.save(deps.storage, &ClearingCycle { id: cycle.id + 1, opened_at: env.block.time })?;
+
+        Ok(Response::new()
+            .add_attribute("action", "close_clearing_cycle")
+            .add_attribute("cycle", cycle.id.to_string())
+            .add_attribute("transfers", transfers.len().to_string()))
+    }
+
+    fn update_outstanding(storage: &mut dyn Storage, bank_id: &str, denom: &str, action: impl FnOnce(Uint128) -> Uint128) -> StdResult<()> {
+        let key = (bank_id.to_string(), denom.to_string());
+        let outstanding = BANK_OUTSTANDING.may_load(storage, key.clone())?.unwrap_or_default();
+        BANK_OUTSTANDING.save(storage, key, &action(outstanding))
+    }
+
+    pub fn execute_set_collateral_config(deps: DepsMut, info: MessageInfo, config: CollateralConfig) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        if !TOKENS.has(deps.storage, config.denom.clone()) {
+            return Err(ContractError::TokenNotRegistered { denom: config.denom });
+        }
+        COLLATERAL_CONFIG.save(deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_collateral_config")
+            .add_attribute("denom", config.denom)
+            .add_attribute("amount", config.amount)
+            .add_attribute("exposure_multiple", config.exposure_multiple.to_string()))
+    }
+
+    pub fn execute_post_collateral(deps: DepsMut, env: Env, info: MessageInfo, bank_id: String, amount: Uint128) -> Result<Response, ContractError> {
+        if amount.is_zero() {
+            return Err(ContractError::ZeroAmount {});
+        }
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        assert_bank_operator(deps.as_ref(), &info.sender, &bank_id)?;
+        assert_bank_open(deps.storage, &bank_id)?;
+        let config = match COLLATERAL_CONFIG.may_load(deps.storage)? {
+            Some(config) => config,
+            None => return Err(ContractError::CollateralNotConfigured {}),
+        };
+        lock_collateral(deps.storage, &info.sender, &bank_id, &config.denom, amount, env.block.time)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "post_collateral")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("amount", amount))
+    }
+
+    pub fn execute_withdraw_collateral(mut deps: DepsMut, info: MessageInfo, bank_id: String, amount: Uint128) -> Result<Response, ContractError> {
+        if amount.is_zero() {
+            return Err(ContractError::ZeroAmount {});
+        }
+        assert_operator_only(deps.as_ref(), &info.sender, &bank_id)?;
+        let config = match COLLATERAL_CONFIG.may_load(deps.storage)? {
+            Some(config) => config,
+            None => return Err(ContractError::CollateralNotConfigured {}),
+        };
+
+        let collateral = BANK_COLLATERAL.may_load(deps.storage, bank_id.clone())?.unwrap_or_default();
+        if collateral < amount {
+            return Err(ContractError::NotEnoughBalance { required: amount, available: collateral });
+        }
+        let remaining = collateral - amount;
+        // a closed bank gets all of its collateral back
+        if BANK_STATUSES.may_load(deps.storage, bank_id.clone())? != Some(BankStatus::Closed) {
+            if remaining < config.amount {
+                return Err(ContractError::NotEnoughBalance { required: config.amount, available: remaining });
+            }
+            let cap = remaining * Uint128::from(config.exposure_multiple);
+            let exposure = bank_exposure(deps.storage, &bank_id, &config.denom)?;
+            if exposure > cap {
+                return Err(ContractError::ExposureCapExceeded { exposure, cap });
+            }
+        }
+
+        BANK_COLLATERAL.save(deps.storage, bank_id.clone(), &remaining)?;
+        credit_account(deps.branch(), &info.sender, &config.denom, amount)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "withdraw_collateral")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("amount", amount))
+    }
+
+    pub fn execute_set_settlement_sla(deps: DepsMut, info: MessageInfo, bank_id: String, seconds: Option<u64>) -> Result<Response, ContractError> {
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        assert_bank_operator(deps.as_ref(), &info.sender, &bank_id)?;
+
+        match seconds {
+            Some(seconds) => SETTLEMENT_SLAS.save(deps.storage, bank_id.clone(), &seconds)?,
+            None => SETTLEMENT_SLAS.remove(deps.storage, bank_id.clone()),
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "set_settlement_sla")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("seconds", seconds.map(|seconds| seconds.to_string()).unwrap_or_default()))
+    }
+
+    pub fn execute_set_bank_fee(deps: DepsMut, info: MessageInfo, bank_id: String, fee_bps: u64) -> Result<Response, ContractError> {
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        assert_bank_operator(deps.as_ref(), &info.sender, &bank_id)?;
+        if fee_bps > 10_000 {
+            return Err(ContractError::InvalidFee { fee_bps });
+        }
+        BANK_FEES.save(deps.storage, bank_id.clone(), &fee_bps)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_bank_fee")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("fee_bps", fee_bps.to_string()))
+    }
+
+    pub fn execute_claim_default(mut deps: DepsMut, env: Env, info: MessageInfo, transaction_id: String, receiver: Option<String>) -> Result<Response, ContractError> {
+        let mut transaction = match TRANSACTIONS.may_load(deps.storage, transaction_id.clone())? {
+            Some(transaction) => transaction,
+            None => return Err(ContractError::TransactionDoesNotExist { id: transaction_id }),
+        };
+        let pending = matches!(transaction.status, TransactionStatus::SentToBank | TransactionStatus::PartiallySettled);
+        if !pending || transaction.channel_id.is_some() {
+            return Err(ContractError::InvalidTransactionStatus { id: transaction_id });
+        }
+        match transaction.deadline {
+            Some(deadline) if env.block.time > deadline => {}
+            _ => return Err(ContractError::SettlementNotOverdue { id: transaction_id }),
+        }
+        let config = match COLLATERAL_CONFIG.may_load(deps.storage)? {
+            Some(config) => config,
+            None => return Err(ContractError::CollateralNotConfigured {}),
+        };
+        // the compensation goes to the sender, or to the receiver only the sender names
+        let payee = match receiver {
+            Some(_) if info.sender != transaction.from => return Err(ContractError::Unauthorized {}),
+            Some(receiver) => deps.api.addr_validate(&receiver)?,
+            None => transaction.from.clone(),
+        };
+        let keeper = if info.sender == transaction.from { payee.clone() } else { info.sender.clone() };
+
+        // the sender gets the outstanding amount back out of what it paid in, the bank never settled it
+        let bank_id = transaction.bank_id.clone();
+        let refunded = transaction.outstanding();
+        // and the collateral worth as much, the bounty comes on top of the compensation
+        let collateral = BANK_COLLATERAL.may_load(deps.storage, bank_id.clone())?.unwrap_or_default();
+        let value = to_collateral(deps.storage, &transaction.denom_from, &config.denom, refunded)?;
+        let compensation = value.min(collateral);
+        let bounty = value
+            .multiply_ratio(config.keeper_bounty_bps, 10_000u128)
+            .min(collateral - compensation);
+        let slashed = compensation + bounty;
+
+        if !compensation.is_zero() {
+            credit_account(deps.branch(), &payee, &config.denom, compensation)?;
+        }
+        if !bounty.is_zero() {
+            credit_account(deps.branch(), &keeper, &config.denom, bounty)?;
+        }
+        BANK_COLLATERAL.save(deps.storage, bank_id.clone(), &(collateral - slashed))?;
+        let mut bank = BANKS.load(deps.storage, bank_id.clone())?;
+        BANKS.save(deps.storage, bank_id.clone(), &bank.outcome(refunded))?;
+        update_outstanding(deps.storage, &bank_id, &transaction.denom_from, |bank_outstanding| bank_outstanding - refunded)?;
+        BALANCES.update(deps.storage, &transaction.from, |balance| -> StdResult<_> {
+            let balance = balance.unwrap();
+            Ok(BalanceInfo { amount: balance.amount + refunded, denom: balance.denom })
+        })?;
+        transaction.compensated += refunded;
+        update_bank_stats(deps.storage, &bank_id, |stats| stats.defaulted += 1)?;
+        transaction.status = TransactionStatus::Defaulted;
+        TRANSACTIONS.save(deps.storage, transaction_id.clone(), &transaction)?;
+        BANK_DEFAULTS.save(deps.storage, (bank_id.clone(), transaction_id.clone()), &DefaultRecord {
+            transaction_id: transaction_id.clone(),
+            compensation,
+            refunded,
+            slashed,
+            bounty,
+            keeper: info.sender,
+            timestamp: env.block.time,
+        })?;
+
+        Ok(Response::new()
+            .add_event(Event::new("bank_default")
+                .add_attribute("bank_id", bank_id)
+                .add_attribute("transaction_id", transaction_id)
+                .add_attribute("compensation", compensation)
+                .add_attribute("refunded", refunded)
+                .add_attribute("slashed", slashed)
+                .add_attribute("bounty", bounty))
+            .add_attribute("action", "claim_default"))
+    }
+
+    fn lock_collateral(storage: &mut dyn Storage, sender: &Addr, bank_id: &str, denom: &str, amount: Uint128, now: Timestamp) -> Result<(), ContractError> {
+        let balance = match BALANCES.may_load(storage, sender)? {
+            Some(balance) => balance,
+            None => return Err(ContractError::AccountDoesNotExist { account: sender.to_string() }),
+        };
+        if balance.denom != denom {
+            return Err(ContractError::TokenAlreadyAssigned { denom: balance.denom });
+        }
+        if balance.amount < amount {
+            return Err(ContractError::NotEnoughBalance { required: amount, available: balance.amount });
+        }
+        consume_allowance(storage, sender, denom, amount, now)?;
+        BALANCES.save(storage, sender, &BalanceInfo { amount: balance.amount - amount, denom: balance.denom })?;
+        BANK_COLLATERAL.update(storage, bank_id.to_string(), |collateral| -> StdResult<_> {
+            Ok(collateral.unwrap_or_default() + amount)
+        })?;
+        Ok(())
+    }
+
+    pub fn execute_fund_bank(deps: DepsMut, env: Env, info: MessageInfo, bank_id: String, amount: Uint128) -> Result<Response, ContractError> {
+        if amount.is_zero() {
+            return Err(ContractError::ZeroAmount {});
+        }
+        assert_operator_only(deps.as_ref(), &info.sender, &bank_id)?;
+        assert_bank_open(deps.storage, &bank_id)?;
+
+        let balance = match BALANCES.may_load(deps.storage, &info.sender)? {
+            Some(balance) => balance,
+            None => return Err(ContractError::AccountDoesNotExist { account: info.sender.to_string() }),
+        };
+        if balance.amount < amount {
+            return Err(ContractError::NotEnoughBalance { required: amount, available: balance.amount });
+        }
+        assert_not_paused(deps.storage, &[
+            PauseScope::Bank { id: bank_id.clone() },
+            PauseScope::Denom { denom: balance.denom.clone() },
+        ])?;
+        consume_allowance(deps.storage, &info.sender, &balance.denom, amount, env.block.time)?;
+
+        BALANCES.save(deps.storage, &info.sender, &BalanceInfo { amount: balance.amount - amount, denom: balance.denom.clone() })?;
+        BANK_RESERVES.update(deps.storage, (bank_id.clone(), balance.denom.clone()), |reserve| -> StdResult<_> {
+            Ok(reserve.unwrap_or_default() + amount)
+        })?;
+
+        Ok(Response::new()
+            .add_attribute("action", "fund_bank")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("denom", balance.denom)
+            .add_attribute("amount", amount))
+    }
+
+    pub fn execute_defund_bank(mut deps: DepsMut, info: MessageInfo, bank_id: String, denom: String, amount: Uint128, receiver: Option<String>) -> Result<Response, ContractError> {
+        if amount.is_zero() {
+            return Err(ContractError::ZeroAmount {});
+        }
+        assert_operator_only(deps.as_ref(), &info.sender, &bank_id)?;
+        assert_bank_open(deps.storage, &bank_id)?;
+        assert_not_paused(deps.storage, &[
+            PauseScope::Bank { id: bank_id.clone() },
+            PauseScope::Denom { denom: denom.clone() },
+        ])?;
+
+        let reserve = BANK_RESERVES.may_load(deps.storage, (bank_id.clone(), denom.clone()))?.unwrap_or_default();
+        if reserve < amount {
+            return Err(ContractError::NotEnoughBalance { required: amount, available: reserve });
+        }
+        // the reserves keep covering what pending transactions may still claim back
+        let outstanding = BANK_OUTSTANDING.may_load(deps.storage, (bank_id.clone(), denom.clone()))?.unwrap_or_default();
+        if reserve - amount < outstanding {
+            return Err(ContractError::BelowOutstandingObligations { outstanding });
+        }
+        // reserves pile up in several denoms, the operator account holds only one of them
+        let receiver = match receiver {
+            Some(receiver) => deps.api.addr_validate(&receiver)?,
+            None => info.sender,
+        };
+        credit_account(deps.branch(), &receiver, &denom, Uint128::zero())?;
+
+        BANK_RESERVES.save(deps.storage, (bank_id.clone(), denom.clone()), &(reserve - amount))?;
+        credit_account(deps.branch(), &receiver, &denom, amount)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "defund_bank")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("receiver", receiver)
+            .add_attribute("denom", denom)
+            .add_attribute("amount", amount))
+    }
+
+    // Reserves move from and to the operator's own balance, the admin has no say
+    fn assert_operator_only(deps: Deps, sender: &Addr, bank_id: &str) -> Result<(), ContractError> {
+        if !BANKS.has(deps.storage, bank_id.to_string()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id.to_string() });
+        }
+        if BANK_OPERATORS.may_load(deps.storage, bank_id.to_string())?.as_ref() != Some(sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        Ok(())
+    }
+
+    fn idempotent_response(deps: Deps, sender: &Addr, idempotency_key: &Option<String>) -> Result<Option<Response>, ContractError> {
+        let transaction_id = match idempotency_key {
+            Some(key) => IDEMPOTENCY_KEYS.may_load(deps.storage, (sender, key.clone()))?,
+            None => None,
+        };
+        Ok(match transaction_id {
+            Some(transaction_id) => Some(Response::new()
+                .set_data(to_binary(&TransactionIdResponse { transaction_id: transaction_id.clone() })?)
+                .add_attribute("action", "idempotent_retry")
+                .add_attribute("transaction_id", transaction_id)),
+            None => None,
+        })
+    }
+
+    fn save_transaction_id(storage: &mut dyn Storage, index: u64, sender: &Addr, idempotency_key: Option<String>) -> StdResult<()> {
+        TRANSACTION_COUNT.save(storage, &index)?;
+        if let Some(key) = idempotency_key {
+            IDEMPOTENCY_KEYS.save(storage, (sender, key), &index.to_string())?;
+        }
+        Ok(())
+    }
+
+    // Moves the transaction into the review queue, the funds stay with the bank
+    fn flag_for_review(storage: &mut dyn Storage, mut transaction: TransactionInfo, actor: Addr, note: String, now: Timestamp) -> StdResult<()> {
+        REVIEW_QUEUE.save(storage, (now.seconds(), transaction.id.clone()), &ReviewQueueEntry {
+            transaction_id: transaction.id.clone(),
+            bank_id: transaction.bank_id.clone(),
+            amount: transaction.amount,
+            denom_from: transaction.denom_from.clone(),
+            flagged_at: now,
+            note: note.clone(),
+        })?;
+        transaction.status = TransactionStatus::UnderReview;
+        transaction.reviews.push(ReviewRecord { action: ReviewAction::Flagged, actor, note, timestamp: now });
+        TRANSACTIONS.save(storage, transaction.id.clone(), &transaction)
     }
 
-    pub fn execute_send_to_recipient(deps: DepsMut, transaction_id: String) -> Result<Response, ContractError> {
+    pub fn execute_send_to_recipient(deps: DepsMut, env: Env, transaction_id: String, amount: Option<Uint128>) -> Result<Response, ContractError> {
         // Check if transaction exists
 
         if !TRANSACTIONS.has(deps.storage, transaction_id.clone()) {
@@ -177,52 +1037,719 @@ pub mod execute {
         }
 
         let transaction_info = TRANSACTIONS.load(deps.storage, transaction_id.clone())?;
+        let pending = matches!(transaction_info.status, TransactionStatus::SentToBank | TransactionStatus::PartiallySettled);
+        if !pending || transaction_info.channel_id.is_some() {
+            return Err(ContractError::InvalidTransactionStatus { id: transaction_id });
+        }
+        assert_bank_open(deps.storage, &transaction_info.bank_id)?;
+        let outstanding = transaction_info.outstanding();
+        let tranche = amount.unwrap_or(outstanding);
+        if tranche.is_zero() {
+            return Err(ContractError::ZeroAmount {});
+        }
+        if tranche > outstanding {
+            return Err(ContractError::SettlementExceedsOutstanding { amount: tranche, outstanding });
+        }
 
-        // Check that exchange rate exists
-        let exchange_rate_id = transaction_info.denom_from.to_owned() + transaction_info.denom_to.as_str();
+        // Banks that opted in convert through the pool of the pair instead of the exchange rate
+        let mut pool = pools::routed_pool(deps.storage, &transaction_info.bank_id, &transaction_info.denom_from, &transaction_info.denom_to)?;
 
-        if !EXCHANGE_RATES.has(deps.storage, exchange_rate_id.clone()) {
-            return Err(ContractError::ExchangeRateDoesNotExist { id: exchange_rate_id.clone()});
-        }
+        // Check that exchange rate exists, it also bounds what a pool pays
+        let exchange_rate_id = transaction_info.denom_from.to_owned() + transaction_info.denom_to.as_str();
+        let exchange_rate = match EXCHANGE_RATES.may_load(deps.storage, exchange_rate_id.clone())? {
+            Some(exchange_rate) => exchange_rate,
+            None => return Err(ContractError::ExchangeRateDoesNotExist { id: exchange_rate_id }),
+        };
+        assert_rate_confirmed(deps.storage, &transaction_info.denom_from, &transaction_info.denom_to)?;
 
         // Check if bank exists
         let bank_id = transaction_info.bank_id.clone();
         if !BANKS.has(deps.storage, bank_id.clone()) {
             return Err(ContractError::BankNotRegistered { id: bank_id.clone() });
         }
-
-        let exchange_rate = EXCHANGE_RATES.load(deps.storage, exchange_rate_id)?; // TODO: check if it is correct
+        assert_not_paused(deps.storage, &settlement_scopes(&bank_id, &transaction_info.denom_from, &transaction_info.denom_to))?;
+        assert_not_frozen(deps.storage, &transaction_info.to, &transaction_info.denom_to)?;
+        let review = check_compliance(deps.as_ref(), &transaction_id, &TransactionMsg {
+            bank_id: Some(bank_id.clone()),
+            from: transaction_info.from.clone(),
+            to: transaction_info.to.clone(),
+            amount: tranche,
+            idempotency_key: None,
+        }, ComplianceStage::Settlement)?;
+        // approved transactions do not go back to the queue
+        if let Some((checker, reason)) = review.filter(|_| !transaction_info.is_approved()) {
+            let note = format!("compliance checker {}: {}", checker, reason);
+            flag_for_review(deps.storage, transaction_info, env.contract.address, note, env.block.time)?;
+            return Ok(Response::new()
+                .add_attribute("action", "send_to_recipient")
+                .add_attribute("transaction_id", transaction_id)
+                .add_attribute("status", "under_review"));
+        }
 
         let bank = BANKS.load(deps.storage, bank_id.clone())?; // TODO: check if it is correct
 
-        if bank.balance < transaction_info.amount {
-            return Err(ContractError::NotEnoughBalance {available: bank.balance, required: transaction_info.amount});
+        if bank.balance < tranche {
+            return Err(ContractError::NotEnoughBalance {available: bank.balance, required: tranche});
         }
-        
+        // the bank pays the recipient out of its reserves in denom_to, a pool pays on its own
+        // but no less than the exchange rate minus the slippage the bank accepts
+        let reserves = BANK_RESERVES.may_load(deps.storage, (bank_id.clone(), transaction_info.denom_to.clone()))?.unwrap_or_default();
+        let expected = exchange(tranche, &exchange_rate);
+        match &pool {
+            Some((pool, max_slippage_bps)) => {
+                let min = expected - expected.multiply_ratio(*max_slippage_bps, 10_000u128);
+                let quote = pool.swap_out(&transaction_info.denom_from, tranche);
+                // the swap below cannot fail once the quote is checked, so every write comes after this
+                if quote.is_zero() {
+                    return Err(ContractError::InsufficientLiquidity { id: pool.id.clone() });
+                }
+                if quote < min {
+                    return Err(ContractError::SlippageExceeded { received: quote, min });
+                }
+            },
+            None => {
+                let amount = after_fee(deps.storage, &bank_id, expected)?;
+                if reserves < amount {
+                    return Err(ContractError::NotEnoughBalance { available: reserves, required: amount });
+                }
+            },
+        }
+
         // decrease bank balance
-        BANKS.update(deps.storage, bank_id, |bank| -> StdResult<_> {
-            Ok(bank.unwrap().outcome(transaction_info.amount))
+        BANKS.update(deps.storage, bank_id.clone(), |bank| -> StdResult<_> {
+            Ok(bank.unwrap().outcome(tranche))
+        })?;
+        update_outstanding(deps.storage, &bank_id, &transaction_info.denom_from, |outstanding| outstanding - tranche)?;
+
+        // Calculate balance due to exchange rate or the pool, the bank keeps its fee
+        let (converted, rate, precision, events) = match pool.as_mut() {
+            Some((pool, _)) => {
+                let (received, event) = pools::swap(deps.storage, env.block.time, pool, &transaction_info.denom_from, tranche)?;
+                let rate = received.multiply_ratio(10_u128.pow(pools::PRICE_PRECISION), tranche);
+                (received, u64::try_from(rate.u128()).unwrap_or(u64::MAX), pools::PRICE_PRECISION, vec![event])
+            },
+            None => (expected, exchange_rate.rate, exchange_rate.precision, vec![]),
+        };
+        let amount = after_fee(deps.storage, &bank_id, converted)?;
+        // the bank keeps what the sender paid in and gives away denom_to,
+        // through a pool it gave away the tranche and keeps the fee out of what the pool paid
+        if pool.is_none() {
+            BANK_RESERVES.save(deps.storage, (bank_id.clone(), transaction_info.denom_to.clone()), &(reserves - amount))?;
+            BANK_RESERVES.update(deps.storage, (bank_id.clone(), transaction_info.denom_from.clone()), |reserve| -> StdResult<_> {
+                Ok(reserve.unwrap_or_default() + tranche)
+            })?;
+        } else {
+            BANK_RESERVES.save(deps.storage, (bank_id.clone(), transaction_info.denom_to.clone()), &(reserves + converted - amount))?;
+        }
+
+        BALANCES.update(deps.storage, &transaction_info.to, |balance| -> StdResult<_> {
+            Ok(
+                BalanceInfo {
+                    amount: balance.unwrap().amount + amount,
+                    denom: transaction_info.denom_to.clone(),
+            })
+        })?;
+
+        // the pool paid the recipient, the bank owes nothing to clear
+        if pool.is_none() {
+            record_obligation(deps.storage, env.block.time, &transaction_info.bank_id, &transaction_info.denom_from, tranche, &transaction_info.denom_to, amount)?;
+        }
+
+        let mut transaction = transaction_info;
+        transaction.settled += tranche;
+        transaction.tranches.push(Tranche {
+            amount: tranche,
+            received: amount,
+            rate,
+            precision,
+            timestamp: env.block.time,
+        });
+        let status = if transaction.outstanding().is_zero() {
+            let elapsed = transaction.created_at.map(|created_at| env.block.time.seconds() - created_at.seconds()).unwrap_or_default();
+            update_bank_stats(deps.storage, &bank_id, |stats| {
+                stats.settled += 1;
+                stats.total_settlement_time += elapsed;
+            })?;
+            TransactionStatus::SentToRecipient
+        } else {
+            TransactionStatus::PartiallySettled
+        };
+        // Update transaction status
+        TRANSACTIONS.save(deps.storage, transaction_id, &transaction.update_status(status))?;
+        Ok(Response::new().add_events(events))
+    }
+
+    pub fn execute_transfer(deps: DepsMut, env: Env, info: MessageInfo, transfer_msg: TransferMsg) -> Result<Response, ContractError> {
+        if transfer_msg.amount.is_zero() {
+            return Err(ContractError::ZeroAmount {});
+        }
+        // Check if channel exists
+        let channel = match CHANNEL_INFO.load(deps.storage, transfer_msg.channel.clone()) {
+            Ok(channel) => channel,
+            Err(_) => return Err(ContractError::ChannelDoesNotExist { id: transfer_msg.channel }),
+        };
+        if channel.version != ICS20_VERSION {
+            return Err(ContractError::InvalidIbcVersion { version: channel.version });
+        }
+
+        if !BALANCES.has(deps.storage, &info.sender) {
+            return Err(ContractError::AccountDoesNotExist{account: info.sender.to_string()});
+        }
+        // Only registered tokens can leave the chain
+        let balance = BALANCES.load(deps.storage, &info.sender)?;
+        if !TOKENS.has(deps.storage, balance.denom.clone()) {
+            return Err(ContractError::TokenNotRegistered { denom: balance.denom });
+        }
+        assert_not_paused(deps.storage, &[PauseScope::Denom { denom: balance.denom.clone() }])?;
+        assert_not_frozen(deps.storage, &info.sender, &balance.denom)?;
+        if balance.amount < transfer_msg.amount {
+            return Err(ContractError::NotEnoughBalance { required: transfer_msg.amount, available: balance.amount});
+        }
+        consume_allowance(deps.storage, &info.sender, &balance.denom, transfer_msg.amount, env.block.time)?;
+
+        // escrow tokens on the channel until the packet is acknowledged
+        BALANCES.save(deps.storage, &info.sender, &BalanceInfo {
+            amount: balance.amount - transfer_msg.amount,
+            denom: balance.denom.clone(),
+        })?;
+        increase_channel_balance(deps, &transfer_msg.channel, &balance.denom, transfer_msg.amount)?;
+
+        let packet = Ics20Packet::new(transfer_msg.amount, &balance.denom, info.sender.as_str(), &transfer_msg.remote_address);
+        let timeout = env.block.time.plus_seconds(transfer_msg.timeout.unwrap_or(DEFAULT_TIMEOUT));
+        let msg = IbcMsg::SendPacket {
+            channel_id: transfer_msg.channel.clone(),
+            data: to_binary(&packet)?,
+            timeout: IbcTimeout::with_timestamp(timeout),
+        };
+
+        Ok(Response::new()
+            .add_message(msg)
+            .add_attribute("action", "transfer")
+            .add_attribute("sender", info.sender)
+            .add_attribute("receiver", transfer_msg.remote_address)
+            .add_attribute("denom", balance.denom)
+            .add_attribute("amount", transfer_msg.amount)
+            .add_attribute("channel", transfer_msg.channel))
+    }
+
+    pub fn execute_send_to_remote_bank(mut deps: DepsMut, env: Env, info: MessageInfo, transaction_info: RemoteTransactionMsg) -> Result<Response, ContractError> {
+        if let Some(response) = idempotent_response(deps.as_ref(), &info.sender, &transaction_info.idempotency_key)? {
+            return Ok(response);
+        }
+        let (transaction_index, transaction_id) = next_transaction_id(deps.storage)?;
+        if transaction_info.amount.is_zero() {
+            return Err(ContractError::ZeroAmount {});
+        }
+        // Check if channel speaks the settlement protocol
+        let channel = match CHANNEL_INFO.load(deps.storage, transaction_info.channel.clone()) {
+            Ok(channel) => channel,
+            Err(_) => return Err(ContractError::ChannelDoesNotExist { id: transaction_info.channel }),
+        };
+        if channel.version == ICS20_VERSION {
+            return Err(ContractError::InvalidIbcVersion { version: channel.version });
+        }
+
+        if !BALANCES.has(deps.storage, &info.sender) {
+            return Err(ContractError::AccountDoesNotExist{account: info.sender.to_string()});
+        }
+        // Check if sender has enough balance
+        let balance_from = BALANCES.load(deps.storage, &info.sender)?;
+        if balance_from.amount < transaction_info.amount {
+            return Err(ContractError::NotEnoughBalance { required: transaction_info.amount, available: balance_from.amount});
+        }
+        assert_not_paused(deps.storage, &[
+            PauseScope::Denom { denom: balance_from.denom.clone() },
+            PauseScope::Denom { denom: transaction_info.denom_to.clone() },
+        ])?;
+        assert_not_frozen(deps.storage, &info.sender, &balance_from.denom)?;
+        consume_allowance(deps.storage, &info.sender, &balance_from.denom, transaction_info.amount, env.block.time)?;
+
+        // escrow tokens on the channel until the remote bank settles
+        BALANCES.save(deps.storage, &info.sender, &BalanceInfo {
+            amount: balance_from.amount - transaction_info.amount,
+            denom: balance_from.denom.clone(),
+        })?;
+        increase_channel_balance(deps.branch(), &transaction_info.channel, &balance_from.denom, transaction_info.amount)?;
+
+        let transaction = TransactionInfo {
+            id: transaction_id.clone(),
+            bank_id: transaction_info.bank_id.clone(),
+            from: info.sender.clone(),
+            to: Addr::unchecked(&transaction_info.to),
+            amount: transaction_info.amount,
+            denom_from: balance_from.denom.clone(),
+            denom_to: transaction_info.denom_to.clone(),
+            status: TransactionStatus::SentToBank,
+            channel_id: Some(transaction_info.channel.clone()),
+            reviews: vec![],
+            acknowledged_at: None,
+            settled: Uint128::zero(),
+            compensated: Uint128::zero(),
+            tranches: vec![],
+            // the packet timeout bounds a remote settlement
+            deadline: None,
+            created_at: Some(env.block.time),
+            route: None,
+        };
+        TRANSACTIONS.save(deps.storage, transaction_id.clone(), &transaction)?;
+        save_transaction_id(deps.storage, transaction_index, &info.sender, transaction_info.idempotency_key)?;
+
+        let packet = TradingPacket::settle(SettlementInstruction {
+            transaction_id: transaction_id.clone(),
+            bank_id: transaction_info.bank_id,
+            sender: info.sender.to_string(),
+            recipient: transaction_info.to,
+            amount: transaction_info.amount,
+            denom_from: balance_from.denom,
+            denom_to: transaction_info.denom_to,
+        });
+        let timeout = env.block.time.plus_seconds(transaction_info.timeout.unwrap_or(DEFAULT_TIMEOUT));
+        let msg = IbcMsg::SendPacket {
+            channel_id: transaction_info.channel.clone(),
+            data: to_binary(&packet)?,
+            timeout: IbcTimeout::with_timestamp(timeout),
+        };
+
+        Ok(Response::new()
+            .add_message(msg)
+            .set_data(to_binary(&TransactionIdResponse { transaction_id: transaction_id.clone() })?)
+            .add_attribute("action", "send_to_remote_bank")
+            .add_attribute("transaction_id", transaction_id)
+            .add_attribute("channel", transaction_info.channel))
+    }
+
+    pub fn execute_set_guardian(deps: DepsMut, info: MessageInfo, guardian: Option<String>) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let guardian = guardian.map(|guardian| deps.api.addr_validate(&guardian)).transpose()?;
+        CONFIG.update(deps.storage, |config| -> StdResult<_> {
+            Ok(Config { guardian: guardian.clone(), ..config })
+        })?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_guardian")
+            .add_attribute("guardian", guardian.map(String::from).unwrap_or_default()))
+    }
+
+    pub fn execute_pause(deps: DepsMut, env: Env, info: MessageInfo, scope: PauseScope) -> Result<Response, ContractError> {
+        assert_admin_or_guardian(deps.as_ref(), &info.sender)?;
+
+        let (kind, id) = scope.key();
+        PAUSES.save(deps.storage, scope.key(), &PauseInfo {
+            scope,
+            paused_by: info.sender,
+            paused_at: env.block.time,
         })?;
 
-        // Calculate balance due to exchange rate
-        let amount = transaction_info.amount.u128() * exchange_rate.rate as u128 / 10_u128.pow(exchange_rate.precision);
+        Ok(Response::new()
+            .add_attribute("action", "pause")
+            .add_attribute("scope", kind)
+            .add_attribute("id", id))
+    }
+
+    pub fn execute_unpause(deps: DepsMut, info: MessageInfo, scope: PauseScope) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let (kind, id) = scope.key();
+        PAUSES.remove(deps.storage, scope.key());
+
+        Ok(Response::new()
+            .add_attribute("action", "unpause")
+            .add_attribute("scope", kind)
+            .add_attribute("id", id))
+    }
+
+    pub fn execute_grant_role(deps: DepsMut, info: MessageInfo, role: Role, address: String) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let address = deps.api.addr_validate(&address)?;
+        ROLES.save(deps.storage, (role.key(), &address), &true)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "grant_role")
+            .add_attribute("role", role.key())
+            .add_attribute("address", address))
+    }
+
+    pub fn execute_revoke_role(deps: DepsMut, info: MessageInfo, role: Role, address: String) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let address = deps.api.addr_validate(&address)?;
+        ROLES.remove(deps.storage, (role.key(), &address));
+
+        Ok(Response::new()
+            .add_attribute("action", "revoke_role")
+            .add_attribute("role", role.key())
+            .add_attribute("address", address))
+    }
+
+    pub fn execute_freeze(deps: DepsMut, env: Env, info: MessageInfo, address: String, denom: Option<String>, reason: String) -> Result<Response, ContractError> {
+        assert_role(deps.as_ref(), &info.sender, Role::Compliance)?;
+
+        let address = deps.api.addr_validate(&address)?;
+        let mut frozen = FROZEN_ACCOUNTS.may_load(deps.storage, &address)?.unwrap_or(FrozenAccount {
+            address: address.clone(),
+            all: false,
+            denoms: vec![],
+        });
+        match &denom {
+            Some(denom) => {
+                if !frozen.denoms.contains(denom) {
+                    frozen.denoms.push(denom.clone());
+                }
+            },
+            None => frozen.all = true,
+        }
+        FROZEN_ACCOUNTS.save(deps.storage, &address, &frozen)?;
+        record_freeze(deps, env, info.sender, address.clone(), denom.clone(), FreezeAction::Freeze, reason)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "freeze")
+            .add_attribute("address", address)
+            .add_attribute("denom", denom.unwrap_or_default()))
+    }
+
+    pub fn execute_unfreeze(deps: DepsMut, env: Env, info: MessageInfo, address: String, denom: Option<String>, reason: String) -> Result<Response, ContractError> {
+        assert_role(deps.as_ref(), &info.sender, Role::Compliance)?;
+
+        let address = deps.api.addr_validate(&address)?;
+        let mut frozen = match FROZEN_ACCOUNTS.may_load(deps.storage, &address)? {
+            Some(frozen) => frozen,
+            None => return Err(ContractError::AccountNotFrozen { account: address.to_string() }),
+        };
+        match &denom {
+            Some(denom) => {
+                if !frozen.denoms.contains(denom) {
+                    return Err(ContractError::AccountNotFrozen { account: address.to_string() });
+                }
+                frozen.denoms.retain(|frozen| frozen != denom);
+            },
+            // lifts the full freeze together with every denom freeze
+            None => {
+                frozen.all = false;
+                frozen.denoms.clear();
+            },
+        }
+        if !frozen.all && frozen.denoms.is_empty() {
+            FROZEN_ACCOUNTS.remove(deps.storage, &address);
+        } else {
+            FROZEN_ACCOUNTS.save(deps.storage, &address, &frozen)?;
+        }
+        record_freeze(deps, env, info.sender, address.clone(), denom.clone(), FreezeAction::Unfreeze, reason)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "unfreeze")
+            .add_attribute("address", address)
+            .add_attribute("denom", denom.unwrap_or_default()))
+    }
+
+    pub fn execute_set_kyc_tier(deps: DepsMut, info: MessageInfo, address: String, tier: u32) -> Result<Response, ContractError> {
+        assert_role(deps.as_ref(), &info.sender, Role::Attestor)?;
+
+        let address = deps.api.addr_validate(&address)?;
+        KYC_TIERS.save(deps.storage, &address, &tier)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_kyc_tier")
+            .add_attribute("address", address)
+            .add_attribute("tier", tier.to_string()))
+    }
+
+    pub fn execute_set_tier_limits(deps: DepsMut, info: MessageInfo, tier: u32, denom: String, limits: TierLimits) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        if !TOKENS.has(deps.storage, denom.clone()) {
+            return Err(ContractError::TokenNotRegistered { denom });
+        }
+        TIER_LIMITS.save(deps.storage, (tier, denom.clone()), &limits)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_tier_limits")
+            .add_attribute("tier", tier.to_string())
+            .add_attribute("denom", denom))
+    }
+
+    // Not tied to a registered token so the initial balances of a new one are checked too
+    pub fn execute_set_kyc_required(deps: DepsMut, info: MessageInfo, denom: String, required: bool) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        if required {
+            KYC_REQUIRED.save(deps.storage, denom.clone(), &())?;
+        } else {
+            KYC_REQUIRED.remove(deps.storage, denom.clone());
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "set_kyc_required")
+            .add_attribute("denom", denom)
+            .add_attribute("required", required.to_string()))
+    }
+
+    pub fn execute_set_compliance_checkers(deps: DepsMut, info: MessageInfo, bank_id: String, checkers: Vec<String>) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        let checkers = checkers.iter()
+            .map(|checker| deps.api.addr_validate(checker))
+            .collect::<StdResult<Vec<_>>>()?;
+        BANK_CHECKERS.save(deps.storage, bank_id.clone(), &checkers)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_compliance_checkers")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("checkers", checkers.len().to_string()))
+    }
+
+    pub fn execute_set_bank_operator(deps: DepsMut, info: MessageInfo, bank_id: String, operator: Option<String>) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        let operator = operator.map(|operator| deps.api.addr_validate(&operator)).transpose()?;
+        match &operator {
+            Some(operator) => BANK_OPERATORS.save(deps.storage, bank_id.clone(), operator)?,
+            None => BANK_OPERATORS.remove(deps.storage, bank_id.clone()),
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "set_bank_operator")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("operator", operator.map(String::from).unwrap_or_default()))
+    }
+
+    pub fn execute_update_bank(deps: DepsMut, info: MessageInfo, bank_id: String, name: Option<String>, operator: Option<String>) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        let mut bank = match BANKS.may_load(deps.storage, bank_id.clone())? {
+            Some(bank) => bank,
+            None => return Err(ContractError::BankNotRegistered { id: bank_id }),
+        };
+        assert_bank_open(deps.storage, &bank_id)?;
+        if let Some(name) = name {
+            bank.name = name;
+        }
+        BANKS.save(deps.storage, bank_id.clone(), &bank)?;
+        if let Some(operator) = operator {
+            BANK_OPERATORS.save(deps.storage, bank_id.clone(), &deps.api.addr_validate(&operator)?)?;
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "update_bank")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("name", bank.name))
+    }
+
+    pub fn execute_set_bank_status(deps: DepsMut, info: MessageInfo, bank_id: String, status: BankStatus) -> Result<Response, ContractError> {
+        assert_admin(deps.as_ref(), &info.sender)?;
+
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        assert_bank_open(deps.storage, &bank_id)?;
+        if status == BankStatus::Closed {
+            let has_balance = |map: &Map<(String, String), Uint128>| -> StdResult<bool> {
+                Ok(map
+                    .prefix(bank_id.clone())
+                    .range(deps.storage, None, None, Order::Ascending)
+                    .collect::<StdResult<Vec<_>>>()?
+                    .iter()
+                    .any(|(_, amount)| !amount.is_zero()))
+            };
+            if has_balance(&BANK_OUTSTANDING)? || has_balance(&BANK_RESERVES)? {
+                return Err(ContractError::BankNotSettled { id: bank_id });
+            }
+        }
+        BANK_STATUSES.save(deps.storage, bank_id.clone(), &status)?;
+
+        let action = match status {
+            BankStatus::Active => "resume_bank",
+            BankStatus::Suspended => "suspend_bank",
+            BankStatus::Closed => "close_bank",
+        };
+        Ok(Response::new()
+            .add_attribute("action", action)
+            .add_attribute("bank_id", bank_id))
+    }
+
+    pub fn execute_set_review_threshold(deps: DepsMut, info: MessageInfo, bank_id: String, denom: String, threshold: Option<Uint128>) -> Result<Response, ContractError> {
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        assert_bank_operator(deps.as_ref(), &info.sender, &bank_id)?;
+
+        match threshold {
+            Some(threshold) => REVIEW_THRESHOLDS.save(deps.storage, (bank_id.clone(), denom.clone()), &threshold)?,
+            None => REVIEW_THRESHOLDS.remove(deps.storage, (bank_id.clone(), denom.clone())),
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "set_review_threshold")
+            .add_attribute("bank_id", bank_id)
+            .add_attribute("denom", denom)
+            .add_attribute("threshold", threshold.unwrap_or_default()))
+    }
+
+    pub fn execute_flag_transaction(deps: DepsMut, env: Env, info: MessageInfo, transaction_id: String, note: String) -> Result<Response, ContractError> {
+        let transaction = match TRANSACTIONS.may_load(deps.storage, transaction_id.clone())? {
+            Some(transaction) => transaction,
+            None => return Err(ContractError::TransactionDoesNotExist { id: transaction_id }),
+        };
+        assert_bank_operator(deps.as_ref(), &info.sender, &transaction.bank_id)?;
+        if transaction.status != TransactionStatus::SentToBank || transaction.channel_id.is_some() {
+            return Err(ContractError::InvalidTransactionStatus { id: transaction_id });
+        }
+
+        flag_for_review(deps.storage, transaction, info.sender, note, env.block.time)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "flag_transaction")
+            .add_attribute("transaction_id", transaction_id))
+    }
+
+    pub fn execute_approve_transaction(mut deps: DepsMut, env: Env, info: MessageInfo, transaction_id: String, note: String) -> Result<Response, ContractError> {
+        let mut transaction = take_from_review(deps.branch(), &info.sender, &transaction_id)?;
+        transaction.status = if transaction.settled.is_zero() {
+            TransactionStatus::SentToBank
+        } else {
+            TransactionStatus::PartiallySettled
+        };
+        transaction.reviews.push(ReviewRecord { action: ReviewAction::Approved, actor: info.sender, note, timestamp: env.block.time });
+        TRANSACTIONS.save(deps.storage, transaction_id.clone(), &transaction)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "approve_transaction")
+            .add_attribute("transaction_id", transaction_id))
+    }
+
+    pub fn execute_reject_transaction(mut deps: DepsMut, env: Env, info: MessageInfo, transaction_id: String, note: String) -> Result<Response, ContractError> {
+        let mut transaction = take_from_review(deps.branch(), &info.sender, &transaction_id)?;
+        refund_sender(deps.storage, &transaction)?;
+
+        update_bank_stats(deps.storage, &transaction.bank_id, |stats| stats.rejected += 1)?;
+        transaction.status = TransactionStatus::RejectedByBank;
+        transaction.reviews.push(ReviewRecord { action: ReviewAction::Rejected, actor: info.sender, note, timestamp: env.block.time });
+        TRANSACTIONS.save(deps.storage, transaction_id.clone(), &transaction)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "reject_transaction")
+            .add_attribute("transaction_id", transaction_id))
+    }
+
+    pub fn execute_set_cancellation_policy(deps: DepsMut, info: MessageInfo, bank_id: String, policy: CancellationPolicy) -> Result<Response, ContractError> {
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        assert_bank_operator(deps.as_ref(), &info.sender, &bank_id)?;
+
+        CANCELLATION_POLICIES.save(deps.storage, bank_id.clone(), &policy)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_cancellation_policy")
+            .add_attribute("bank_id", bank_id))
+    }
+
+    pub fn execute_acknowledge_transaction(deps: DepsMut, env: Env, info: MessageInfo, transaction_id: String) -> Result<Response, ContractError> {
+        let mut transaction = match TRANSACTIONS.may_load(deps.storage, transaction_id.clone())? {
+            Some(transaction) => transaction,
+            None => return Err(ContractError::TransactionDoesNotExist { id: transaction_id }),
+        };
+        assert_bank_operator(deps.as_ref(), &info.sender, &transaction.bank_id)?;
+        if transaction.status != TransactionStatus::SentToBank || transaction.channel_id.is_some() || transaction.acknowledged_at.is_some() {
+            return Err(ContractError::InvalidTransactionStatus { id: transaction_id });
+        }
+        assert_bank_open(deps.storage, &transaction.bank_id)?;
+
+        transaction.acknowledged_at = Some(env.block.time);
+        TRANSACTIONS.save(deps.storage, transaction_id.clone(), &transaction)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "acknowledge_transaction")
+            .add_attribute("transaction_id", transaction_id))
+    }
+
+    pub fn execute_cancel_transaction(deps: DepsMut, info: MessageInfo, transaction_id: String) -> Result<Response, ContractError> {
+        let mut transaction = match TRANSACTIONS.may_load(deps.storage, transaction_id.clone())? {
+            Some(transaction) => transaction,
+            None => return Err(ContractError::TransactionDoesNotExist { id: transaction_id }),
+        };
+        if info.sender != transaction.from {
+            return Err(ContractError::Unauthorized {});
+        }
+        // remote banks settle on their own chain
+        if transaction.status != TransactionStatus::SentToBank || transaction.channel_id.is_some() {
+            return Err(ContractError::InvalidTransactionStatus { id: transaction_id });
+        }
+        assert_bank_open(deps.storage, &transaction.bank_id)?;
+        let policy = CANCELLATION_POLICIES.may_load(deps.storage, transaction.bank_id.clone())?.unwrap_or_default();
+        if policy == CancellationPolicy::UntilAcknowledged && transaction.acknowledged_at.is_some() {
+            return Err(ContractError::CancellationNotAllowed { id: transaction_id });
+        }
+
+        refund_sender(deps.storage, &transaction)?;
+        transaction.status = TransactionStatus::Cancelled;
+        TRANSACTIONS.save(deps.storage, transaction_id.clone(), &transaction)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "cancel_transaction")
+            .add_attribute("transaction_id", transaction_id)
+            .add_attribute("amount", transaction.amount))
+    }
+
+    // Gives the funds still held by the bank back to the sender
+    fn refund_sender(storage: &mut dyn Storage, transaction: &TransactionInfo) -> StdResult<()> {
+        let outstanding = transaction.outstanding();
+        BANKS.update(storage, transaction.bank_id.clone(), |bank| -> StdResult<_> {
+            Ok(bank.unwrap().outcome(outstanding))
+        })?;
+        update_outstanding(storage, &transaction.bank_id, &transaction.denom_from, |bank_outstanding| bank_outstanding - outstanding)?;
+        BALANCES.update(storage, &transaction.from, |balance| -> StdResult<_> {
+            let balance = balance.unwrap();
+            Ok(BalanceInfo { amount: balance.amount + outstanding, denom: balance.denom })
+        })?;
+        Ok(())
+    }
+
+    fn take_from_review(deps: DepsMut, sender: &Addr, transaction_id: &str) -> Result<TransactionInfo, ContractError> {
+        assert_role(deps.as_ref(), sender, Role::Reviewer)?;
 
-        BALANCES.update(deps.storage, &transaction_info.to, |balance| -> StdResult<_> {
-            Ok(
-                BalanceInfo {
-                    amount: balance.unwrap().amount + Uint128::from(amount),
-                    denom: transaction_info.denom_to.clone(),
-            })
-        })?;
+        let transaction = match TRANSACTIONS.may_load(deps.storage, transaction_id.to_string())? {
+            Some(transaction) => transaction,
+            None => return Err(ContractError::TransactionDoesNotExist { id: transaction_id.to_string() }),
+        };
+        let flagged_at = match (&transaction.status, transaction.flagged_at()) {
+            (TransactionStatus::UnderReview, Some(flagged_at)) => flagged_at,
+            _ => return Err(ContractError::InvalidTransactionStatus { id: transaction_id.to_string() }),
+        };
+        assert_bank_open(deps.storage, &transaction.bank_id)?;
+        REVIEW_QUEUE.remove(deps.storage, (flagged_at.seconds(), transaction_id.to_string()));
+        Ok(transaction)
+    }
 
-        let mut transaction = transaction_info;
-        // Update transaction status
-        TRANSACTIONS.save(deps.storage, transaction_id, &transaction.update_status(TransactionStatus::SentToRecipient))?;
-        Ok(Response::default())
+    fn record_freeze(deps: DepsMut, env: Env, actor: Addr, address: Addr, denom: Option<String>, action: FreezeAction, reason: String) -> StdResult<()> {
+        let index = FREEZE_HISTORY_COUNT.may_load(deps.storage)?.unwrap_or_default();
+        FREEZE_HISTORY.save(deps.storage, (&address, index), &FreezeRecord {
+            address: address.clone(),
+            denom,
+            action,
+            reason,
+            actor,
+            timestamp: env.block.time,
+        })?;
+        FREEZE_HISTORY_COUNT.save(deps.storage, &(index + 1))
     }
 }
 pub mod query {
-    use crate::msg::{BankInfoResponse, TransactionInfoResponse, BalanceResponse, ExchangeRateInfoResponse};
+    use crate::msg::{BankChannel, BankDefaultsResponse, BankInfoResponse, BankRankingResponse, BankStatsResponse, RankingMetric, BankSolvency, SolvencyResponse, TransactionInfoResponse, BalanceResponse, ExchangeRateInfoResponse, ChannelInfoResponse, ConfigResponse, OracleConfigResponse, PriceSubmissionsResponse, CircuitBreakerResponse, RateBreachesResponse, RateBreachResponse, PausesResponse, RoleMembersResponse, FrozenAccountsResponse, FreezeHistoryResponse, FreezeRecordResponse, ReviewQueueResponse, NetObligationsResponse, ClearingCyclesResponse};
+    use crate::state::{Role, BANK_CHANNELS, BANK_CHECKERS, BANK_OPERATORS, BANK_POOL_ROUTING, BANK_OUTSTANDING, BANK_RESERVES, BANK_STATUSES, BANK_COLLATERAL, BANK_DEFAULTS, BANK_FEES, BANK_STATS, BANK_VOLUMES, CANCELLATION_POLICIES, CLEARING_HISTORY, COLLATERAL_CONFIG, REVIEW_QUEUE, SETTLEMENT_SLAS};
+    use crate::helpers::{bank_exposure, route_transaction, current_cycle, net_positions, net_transfers, remaining_allowance, DEFAULT_LIMIT, MAX_LIMIT};
+    use cosmwasm_std::Uint128;
+    use cw_storage_plus::Bound;
+    use std::cmp::Reverse;
+
+    use crate::state::{CHANNEL_INFO, CHANNEL_STATE};
 
     use super::*;
 
@@ -258,13 +1785,35 @@ pub mod query {
         match BANKS.load(deps.storage, id.clone()) {
             Ok(item) => {
                 let res = BankInfoResponse {
+                    operator: BANK_OPERATORS.may_load(deps.storage, id.clone())?,
+                    cancellation_policy: CANCELLATION_POLICIES.may_load(deps.storage, id.clone())?.unwrap_or_default(),
+                    status: BANK_STATUSES.may_load(deps.storage, id.clone())?.unwrap_or_default(),
+                    settlement_sla: SETTLEMENT_SLAS.may_load(deps.storage, id.clone())?,
+                    fee_bps: BANK_FEES.may_load(deps.storage, id.clone())?.unwrap_or_default(),
+                    reserves: BANK_RESERVES
+                        .prefix(id.clone())
+                        .range(deps.storage, None, None, Order::Ascending)
+                        .map(|item| item.map(|(denom, amount)| BalanceResponse { amount, denom }))
+                        .collect::<StdResult<Vec<_>>>()?,
+                    outstanding: BANK_OUTSTANDING
+                        .prefix(id.clone())
+                        .range(deps.storage, None, None, Order::Ascending)
+                        .map(|item| item.map(|(denom, amount)| BalanceResponse { amount, denom }))
+                        .collect::<StdResult<Vec<_>>>()?,
+                    pool_routing: BANK_POOL_ROUTING.may_load(deps.storage, id.clone())?,
+                    channels: BANK_CHANNELS
+                        .prefix(id.clone())
+                        .range(deps.storage, None, None, Order::Ascending)
+                        .map(|item| item.map(|(channel, counterparty_port)| BankChannel { channel, counterparty_port }))
+                        .collect::<StdResult<Vec<_>>>()?,
+                    compliance_checkers: BANK_CHECKERS.may_load(deps.storage, id)?.unwrap_or_default(),
                     id: item.id,
                     name: item.name,
                     balance: item.balance,
                 };
                 Ok(to_binary(&res)?)
             },
-            Err(_) => return Err(ContractError::BankNotRegistered { id }), 
+            Err(_) => Err(ContractError::BankNotRegistered { id }), 
         }
         
     }
@@ -273,6 +1822,7 @@ pub mod query {
         match TRANSACTIONS.load(deps.storage, id.clone()) {
             Ok(item) => {
                 let res = TransactionInfoResponse {
+                    outstanding: item.outstanding(),
                     id: item.id,
                     bank_id: item.bank_id,
                     from: item.from,
@@ -281,6 +1831,14 @@ pub mod query {
                     denom_from: item.denom_from,
                     denom_to: item.denom_to,
                     status: item.status,
+                    channel_id: item.channel_id,
+                    reviews: item.reviews,
+                    acknowledged_at: item.acknowledged_at,
+                    settled: item.settled,
+                    compensated: item.compensated,
+                    tranches: item.tranches,
+                    deadline: item.deadline,
+                    route: item.route,
                 };
                 Ok(to_binary(&res)?)
             },
@@ -303,16 +1861,286 @@ pub mod query {
             Err(_) => Err(ContractError::ExchangeRateDoesNotExist { id }), 
         }
     }
+
+    pub fn query_channel_info(deps: Deps, id: String) -> Result<Binary, ContractError> {
+        let info = match CHANNEL_INFO.load(deps.storage, id.clone()) {
+            Ok(info) => info,
+            Err(_) => return Err(ContractError::ChannelDoesNotExist { id }),
+        };
+        let state = CHANNEL_STATE
+            .prefix(id)
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let balances = state.iter()
+            .map(|(denom, state)| BalanceResponse { amount: state.outstanding, denom: denom.clone() })
+            .collect();
+        let total_sent = state.into_iter()
+            .map(|(denom, state)| BalanceResponse { amount: state.total_sent, denom })
+            .collect();
+
+        Ok(to_binary(&ChannelInfoResponse {
+            id: info.id,
+            counterparty_endpoint: info.counterparty_endpoint,
+            connection_id: info.connection_id,
+            balances,
+            total_sent,
+        })?)
+    }
+
+    pub fn query_config(deps: Deps) -> Result<Binary, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        Ok(to_binary(&ConfigResponse { admin: config.admin, guardian: config.guardian })?)
+    }
+
+    pub fn query_oracle_config(deps: Deps, id: String) -> Result<Binary, ContractError> {
+        match ORACLES.load(deps.storage, id.clone()) {
+            Ok(item) => {
+                let res = OracleConfigResponse {
+                    id: item.id,
+                    denom_from: item.denom_from,
+                    denom_to: item.denom_to,
+                    feeders: item.feeders,
+                    quorum: item.quorum,
+                    precision: item.precision,
+                    max_age: item.max_age,
+                    max_deviation_bps: item.max_deviation_bps,
+                    aggregation: item.aggregation,
+                };
+                Ok(to_binary(&res)?)
+            },
+            Err(_) => Err(ContractError::OracleDoesNotExist { id }),
+        }
+    }
+
+    pub fn query_price_submissions(deps: Deps, id: String, env: Env) -> Result<Binary, ContractError> {
+        let oracle = match ORACLES.load(deps.storage, id.clone()) {
+            Ok(oracle) => oracle,
+            Err(_) => return Err(ContractError::OracleDoesNotExist { id }),
+        };
+        // Stale prices are not part of the aggregation anymore
+        let submissions = execute::fresh_submissions(deps, &oracle, env.block.time)?;
+        Ok(to_binary(&PriceSubmissionsResponse { id, submissions })?)
+    }
+
+    pub fn query_circuit_breaker(deps: Deps, id: String) -> Result<Binary, ContractError> {
+        match CIRCUIT_BREAKERS.load(deps.storage, id.clone()) {
+            Ok(item) => {
+                let res = CircuitBreakerResponse {
+                    id: item.id,
+                    denom_from: item.denom_from,
+                    denom_to: item.denom_to,
+                    max_update_deviation_bps: item.max_update_deviation_bps,
+                    max_window_deviation_bps: item.max_window_deviation_bps,
+                    window: item.window,
+                    history: item.history,
+                    breach: item.breach,
+                };
+                Ok(to_binary(&res)?)
+            },
+            Err(_) => Err(ContractError::CircuitBreakerDoesNotExist { id }),
+        }
+    }
+
+    pub fn query_rate_breaches(deps: Deps, id: String, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+        let breaches = RATE_BREACHES
+            .prefix(id)
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(index, breach)| RateBreachResponse { index, breach }))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(to_binary(&RateBreachesResponse { breaches })?)
+    }
+
+    pub fn query_pauses(deps: Deps) -> Result<Binary, ContractError> {
+        let pauses = PAUSES
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, pause)| pause))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(to_binary(&PausesResponse { pauses })?)
+    }
+
+    pub fn query_role_members(deps: Deps, role: Role, start_after: Option<String>, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start_after = start_after.map(|address| deps.api.addr_validate(&address)).transpose()?;
+        let start = start_after.as_ref().map(Bound::exclusive);
+        let members = ROLES
+            .prefix(role.key())
+            .keys(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(to_binary(&RoleMembersResponse { role, members })?)
+    }
+
+    pub fn query_frozen_accounts(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start_after = start_after.map(|address| deps.api.addr_validate(&address)).transpose()?;
+        let start = start_after.as_ref().map(Bound::exclusive);
+        let accounts = FROZEN_ACCOUNTS
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, account)| account))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(to_binary(&FrozenAccountsResponse { accounts })?)
+    }
+
+    pub fn query_freeze_history(deps: Deps, address: String, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let address = deps.api.addr_validate(&address)?;
+        let start = start_after.map(Bound::exclusive);
+        let records = FREEZE_HISTORY
+            .prefix(&address)
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(index, record)| FreezeRecordResponse { index, record }))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(to_binary(&FreezeHistoryResponse { records })?)
+    }
+
+    pub fn query_remaining_allowance(deps: Deps, env: Env, address: String, denom: String) -> Result<Binary, ContractError> {
+        let address = deps.api.addr_validate(&address)?;
+        Ok(to_binary(&remaining_allowance(deps.storage, &address, &denom, env.block.time)?)?)
+    }
+
+    pub fn query_review_queue(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = match start_after {
+            Some(id) => match TRANSACTIONS.may_load(deps.storage, id.clone())?.and_then(|transaction| transaction.flagged_at()) {
+                Some(flagged_at) => Some(Bound::exclusive((flagged_at.seconds(), id))),
+                None => return Err(ContractError::TransactionDoesNotExist { id }),
+            },
+            None => None,
+        };
+        let entries = REVIEW_QUEUE
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, entry)| entry))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(to_binary(&ReviewQueueResponse { entries })?)
+    }
+
+    pub fn query_net_obligations(deps: Deps, env: Env) -> Result<Binary, ContractError> {
+        let cycle = current_cycle(deps.storage, env.block.time)?;
+        let positions = net_positions(deps.storage, cycle.id)?;
+        let transfers = net_transfers(&positions);
+        Ok(to_binary(&NetObligationsResponse { cycle: cycle.id, opened_at: cycle.opened_at, positions, transfers })?)
+    }
+
+    pub fn query_solvency(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let config = COLLATERAL_CONFIG.may_load(deps.storage)?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+        let bank_ids = BANKS
+            .keys(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+        let banks = bank_ids
+            .into_iter()
+            .map(|bank_id| {
+                let collateral = BANK_COLLATERAL.may_load(deps.storage, bank_id.clone())?.unwrap_or_default();
+                let (exposure, cap) = match &config {
+                    Some(config) => (
+                        bank_exposure(deps.storage, &bank_id, &config.denom)?,
+                        collateral * Uint128::from(config.exposure_multiple),
+                    ),
+                    None => Default::default(),
+                };
+                Ok(BankSolvency { bank_id, collateral, exposure, cap })
+            })
+            .collect::<Result<Vec<_>, ContractError>>()?;
+        Ok(to_binary(&SolvencyResponse { denom: config.map(|config| config.denom), banks })?)
+    }
+
+    fn bank_stats(deps: Deps, bank_id: String) -> StdResult<BankStatsResponse> {
+        let stats = BANK_STATS.may_load(deps.storage, bank_id.clone())?.unwrap_or_default();
+        let volumes = BANK_VOLUMES
+            .prefix(bank_id.clone())
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(denom, amount)| BalanceResponse { amount, denom }))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(BankStatsResponse {
+            bank_id,
+            accepted: stats.accepted,
+            settled: stats.settled,
+            rejected: stats.rejected,
+            defaulted: stats.defaulted,
+            average_settlement_time: stats.average_settlement_time(),
+            settlement_rate_bps: stats.settlement_rate_bps(),
+            default_rate_bps: stats.default_rate_bps(),
+            volumes,
+        })
+    }
+
+    pub fn query_bank_stats(deps: Deps, bank_id: String) -> Result<Binary, ContractError> {
+        if !BANKS.has(deps.storage, bank_id.clone()) {
+            return Err(ContractError::BankNotRegistered { id: bank_id });
+        }
+        Ok(to_binary(&bank_stats(deps, bank_id)?)?)
+    }
+
+    pub fn query_route_preview(deps: Deps, from: String, to: String, amount: Uint128) -> Result<Binary, ContractError> {
+        let from = deps.api.addr_validate(&from)?;
+        let to = deps.api.addr_validate(&to)?;
+        Ok(to_binary(&route_transaction(deps.storage, &from, &to, amount)?)?)
+    }
+
+    pub fn query_bank_ranking(deps: Deps, metric: RankingMetric, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let mut banks = BANKS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .map(|bank_id| {
+                let bank_id = bank_id?;
+                let status = BANK_STATUSES.may_load(deps.storage, bank_id.clone())?.unwrap_or_default();
+                Ok((status, bank_id))
+            })
+            .filter(|item| !matches!(item, Ok((BankStatus::Closed, _))))
+            .map(|item: StdResult<_>| item.and_then(|(_, bank_id)| bank_stats(deps, bank_id)))
+            .collect::<StdResult<Vec<_>>>()?;
+        // stable sort, ties stay in bank id order
+        match metric {
+            RankingMetric::SettlementRate => banks.sort_by_key(|bank| Reverse(bank.settlement_rate_bps.unwrap_or_default())),
+            RankingMetric::DefaultRate => banks.sort_by_key(|bank| bank.default_rate_bps.unwrap_or_default()),
+            RankingMetric::AverageSettlementTime => banks.sort_by_key(|bank| bank.average_settlement_time.unwrap_or(u64::MAX)),
+            RankingMetric::Settled => banks.sort_by_key(|bank| Reverse(bank.settled)),
+        }
+        banks.truncate(limit);
+        Ok(to_binary(&BankRankingResponse { banks })?)
+    }
+
+    pub fn query_bank_defaults(deps: Deps, bank_id: String, start_after: Option<String>, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+        let defaults = BANK_DEFAULTS
+            .prefix(bank_id)
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, record)| record))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(to_binary(&BankDefaultsResponse { defaults })?)
+    }
+
+    pub fn query_clearing_cycles(deps: Deps, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+        let cycles = CLEARING_HISTORY
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, cycle)| cycle))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(to_binary(&ClearingCyclesResponse { cycles })?)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::msg::{TransactionMsg, TokenInfoResponse, ExchangeRateMsg, BankInfoResponse, TransactionInfoResponse, BalanceResponse, ExchangeRateInfoResponse};
-    use crate::state::BankInfo;
+    use crate::msg::{TransactionMsg, TransactionIdResponse, BatchTransactionMsg, PaymentMsg, BatchMode, BatchResponse, TokenInfoResponse, ExchangeRateMsg, BankInfoResponse, TransactionInfoResponse, BalanceResponse, ExchangeRateInfoResponse, OracleConfigMsg, PriceSubmissionMsg, PriceSubmissionsResponse, CircuitBreakerMsg, CircuitBreakerResponse, RateBreachesResponse, PausesResponse, RoleMembersResponse, FrozenAccountsResponse, FreezeHistoryResponse, AllowanceResponse, ReviewQueueResponse, NetObligationsResponse, ClearingCyclesResponse, BankSolvency, SolvencyResponse, BankDefaultsResponse, BankStatsResponse, BankRankingResponse, RankingMetric};
+    use crate::testing;
+    use crate::state::{CollateralConfig, RouteDecision, RouteQuote, NetTransfer, Aggregation, BankInfo, BreachKind, FreezeAction, Role, TierLimits, ReviewAction, CancellationPolicy};
 
     use super::*;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary, Addr};
+    use cosmwasm_std::{coins, from_binary, Addr, Decimal, Timestamp, Uint128};
     use cw20::Cw20Coin;
 
     #[test]
@@ -384,6 +2212,11 @@ mod tests {
             rate: 200,
         });
 
+        // only the admin and the oracle role set rates
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), exchange_rate.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let grant = ExecuteMsg::GrantRole { role: Role::Oracle, address: "addr0000".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), grant).unwrap();
         let res = execute(deps.as_mut(), mock_env(), info, exchange_rate).unwrap();
         assert_eq!(0, res.messages.len());
 
@@ -413,6 +2246,20 @@ mod tests {
         assert_eq!("USD", value.denom_from);
         assert_eq!("RUB", value.denom_to);
         assert_eq!(5000, value.rate);
+
+        // the inverse of 10000.00 rounds to 0.00 and would pay nothing
+        for (precision, rate) in [(2, 1_000_000), (20, 1)] {
+            let exchange_rate = ExecuteMsg::SetExchangeRate(ExchangeRateMsg {
+                denom_from: "RUB".to_string(),
+                denom_to: "USD".to_string(),
+                precision,
+                rate,
+            });
+            let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), exchange_rate).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidExchangeRate { .. }));
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ExchangeRateInfo { id: "USDRUB".to_string() }).unwrap();
+        assert_eq!(5000, from_binary::<ExchangeRateInfoResponse>(&res).unwrap().rate);
     }
 
     #[test]
@@ -522,6 +2369,9 @@ mod tests {
             initial_balances: vec![Cw20Coin {
                 address: "addr0001".to_string(),
                 amount: Uint128::from(2000000u128),
+            }, Cw20Coin {
+                address: "treasury".to_string(),
+                amount: Uint128::from(200000u128),
             }],
         };
 
@@ -540,6 +2390,7 @@ mod tests {
         });
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         assert_eq!(0, res.messages.len());
+        fund_usd_reserves(deps.as_mut(), mock_env(), "bank0000", 200000);
         
         // means that real rate is 0.2
         let exchange_rate = ExecuteMsg::SetExchangeRate(ExchangeRateMsg {
@@ -553,23 +2404,25 @@ mod tests {
         assert_eq!(0, res.messages.len());
 
         let transaction = TransactionMsg {
-            id: "transaction0000".to_string(),
-            bank_id: "bank0000".to_string(),
+            bank_id: Some("bank0000".to_string()),
             from: Addr::unchecked("addr0000"),
             to: Addr::unchecked("addr0001"),
             amount: Uint128::from(1000000u128),
+            idempotency_key: None,
         };
         let msg = ExecuteMsg::SendToBank(transaction);
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         assert_eq!(0, res.messages.len());
+        let data: TransactionIdResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!("1", data.transaction_id);
 
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::TransactionInfo { id: ("transaction0000".to_string()) } 
+            QueryMsg::TransactionInfo { id: ("1".to_string()) } 
         ).unwrap();
         let tinfo: TransactionInfoResponse = from_binary(&res).unwrap();
-        assert_eq!("transaction0000", tinfo.id);
+        assert_eq!("1", tinfo.id);
         assert_eq!("bank0000", tinfo.bank_id);
         assert_eq!("addr0000", tinfo.from);
         assert_eq!("addr0001", tinfo.to);
@@ -578,17 +2431,17 @@ mod tests {
         assert_eq!("USD", tinfo.denom_to);
         assert_eq!(TransactionStatus::SentToBank, tinfo.status);
 
-        let msg = ExecuteMsg::SendToRecipient { transaction_id: "transaction0000".to_string() };
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         assert_eq!(0, res.messages.len());
 
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::TransactionInfo { id: ("transaction0000".to_string()) } 
+            QueryMsg::TransactionInfo { id: ("1".to_string()) } 
         ).unwrap();
         let tinfo: TransactionInfoResponse = from_binary(&res).unwrap();
-        assert_eq!("transaction0000", tinfo.id);
+        assert_eq!("1", tinfo.id);
         assert_eq!("bank0000", tinfo.bank_id);
         assert_eq!("addr0000", tinfo.from);
         assert_eq!("addr0001", tinfo.to);
@@ -622,4 +2475,1253 @@ mod tests {
         let value: BankInfoResponse = from_binary(&res).unwrap();
         assert_eq!(Uint128::from(1000000u128), value.balance);
     }
+
+    fn oracle_config(feeders: &[&str], quorum: u32, aggregation: Aggregation) -> ExecuteMsg {
+        ExecuteMsg::SetOracleConfig(OracleConfigMsg {
+            denom_from: "RUB".to_string(),
+            denom_to: "USD".to_string(),
+            feeders: feeders.iter().map(|feeder| feeder.to_string()).collect(),
+            quorum,
+            precision: 3,
+            max_age: 60,
+            max_deviation_bps: 1000,
+            aggregation,
+        })
+    }
+
+    fn submit_price(rate: u64, timestamp: Timestamp) -> ExecuteMsg {
+        ExecuteMsg::SubmitPrice(PriceSubmissionMsg {
+            denom_from: "RUB".to_string(),
+            denom_to: "USD".to_string(),
+            rate,
+            timestamp,
+        })
+    }
+
+    fn query_rate(deps: Deps, id: &str) -> u64 {
+        let res = query(deps, mock_env(), QueryMsg::ExchangeRateInfo { id: id.to_string() }).unwrap();
+        let value: ExchangeRateInfoResponse = from_binary(&res).unwrap();
+        value.rate
+    }
+
+    #[test]
+    fn oracle_aggregates_median() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let now = env.block.time;
+
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), env.clone(), info.clone(), InstantiateMsg {}).unwrap();
+
+        // only the admin registers feeders
+        let msg = oracle_config(&["feeder0", "feeder1", "feeder2"], 2, Aggregation::Median);
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), submit_price(200, now)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // no quorum yet
+        execute(deps.as_mut(), env.clone(), mock_info("feeder0", &[]), submit_price(200, now)).unwrap();
+        let err = query(deps.as_ref(), env.clone(), QueryMsg::ExchangeRateInfo { id: "RUBUSD".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::ExchangeRateDoesNotExist { .. }));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("feeder1", &[]), submit_price(210, now)).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "rate" && a.value == "205"));
+        assert_eq!(205, query_rate(deps.as_ref(), "RUBUSD"));
+        assert_eq!(4878, query_rate(deps.as_ref(), "USDRUB"));
+
+        execute(deps.as_mut(), env.clone(), mock_info("feeder2", &[]), submit_price(215, now)).unwrap();
+        assert_eq!(210, query_rate(deps.as_ref(), "RUBUSD"));
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::PriceSubmissions { id: "RUBUSD".to_string() }).unwrap();
+        let value: PriceSubmissionsResponse = from_binary(&res).unwrap();
+        assert_eq!(3, value.submissions.len());
+
+        // the rate can not be written directly anymore, in any direction
+        let msg = ExecuteMsg::SetExchangeRate(ExchangeRateMsg {
+            denom_from: "USD".to_string(),
+            denom_to: "RUB".to_string(),
+            precision: 3,
+            rate: 1,
+        });
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::ExchangeRateManagedByOracle { .. }));
+    }
+
+    #[test]
+    fn oracle_rejects_stale_and_outlier_prices() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let now = env.block.time;
+
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), env.clone(), info.clone(), InstantiateMsg {}).unwrap();
+        let msg = oracle_config(&["feeder0", "feeder1", "feeder2", "feeder3"], 3, Aggregation::TrimmedMean { trim: 1 });
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("feeder0", &[]), submit_price(200, now.minus_seconds(61))).unwrap_err();
+        assert!(matches!(err, ContractError::StalePrice { .. }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("feeder0", &[]), submit_price(200, now.plus_seconds(1))).unwrap_err();
+        assert!(matches!(err, ContractError::StalePrice { .. }));
+
+        execute(deps.as_mut(), env.clone(), mock_info("feeder0", &[]), submit_price(200, now)).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("feeder1", &[]), submit_price(220, now)).unwrap();
+
+        // more than 10% away from the median of the others
+        let err = execute(deps.as_mut(), env.clone(), mock_info("feeder2", &[]), submit_price(240, now)).unwrap_err();
+        assert!(matches!(err, ContractError::OutlierPrice { rate: 240, median: 210 }));
+
+        execute(deps.as_mut(), env.clone(), mock_info("feeder2", &[]), submit_price(230, now)).unwrap();
+        // with 3 prices trimming one on each side leaves the middle one
+        assert_eq!(220, query_rate(deps.as_ref(), "RUBUSD"));
+        execute(deps.as_mut(), env.clone(), mock_info("feeder3", &[]), submit_price(210, now)).unwrap();
+        assert_eq!(215, query_rate(deps.as_ref(), "RUBUSD"));
+
+        // old prices do not count towards the quorum anymore
+        env.block.time = now.plus_seconds(61);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::PriceSubmissions { id: "RUBUSD".to_string() }).unwrap();
+        let value: PriceSubmissionsResponse = from_binary(&res).unwrap();
+        assert!(value.submissions.is_empty());
+        let res = execute(deps.as_mut(), env.clone(), mock_info("feeder0", &[]), submit_price(300, env.block.time)).unwrap();
+        assert!(!res.attributes.iter().any(|a| a.key == "rate"));
+        assert_eq!(215, query_rate(deps.as_ref(), "RUBUSD"));
+    }
+
+    fn set_rate(deps: DepsMut, env: Env, rate: u64) -> Response {
+        let msg = ExecuteMsg::SetExchangeRate(ExchangeRateMsg {
+            denom_from: "RUB".to_string(),
+            denom_to: "USD".to_string(),
+            precision: 3,
+            rate,
+        });
+        execute(deps, env, mock_info("creator", &[]), msg).unwrap()
+    }
+
+    // RUB holder addr0000, USD holder addr0001, bank0000 and a RUBUSD rate of 0.2
+    fn setup_trading(mut deps: DepsMut, env: Env) {
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), env.clone(), info.clone(), InstantiateMsg {}).unwrap();
+        testing::create_token(deps.branch(), env.clone(), "RUB", &["addr0000"]);
+        testing::create_token(deps.branch(), env.clone(), "USD", &["addr0001", "treasury"]);
+        let msg = ExecuteMsg::CreateBank(BankInfo {
+            id: "bank0000".to_string(),
+            name: "Bank".to_string(),
+            balance: Uint128::from(1000u128),
+        });
+        execute(deps.branch(), env.clone(), info.clone(), msg).unwrap();
+        fund_usd_reserves(deps.branch(), env.clone(), "bank0000", 1000);
+        set_rate(deps, env, 200);
+    }
+
+    // The treasury pays the recipients on behalf of the bank
+    fn fund_usd_reserves(mut deps: DepsMut, env: Env, bank_id: &str, amount: u128) {
+        let msg = ExecuteMsg::SetBankOperator { bank_id: bank_id.to_string(), operator: Some("treasury".to_string()) };
+        execute(deps.branch(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::FundBank { bank_id: bank_id.to_string(), amount: Uint128::from(amount) };
+        execute(deps, env, mock_info("treasury", &[]), msg).unwrap();
+    }
+
+    fn send_to_bank_msg(amount: u128) -> ExecuteMsg {
+        ExecuteMsg::SendToBank(TransactionMsg {
+            bank_id: Some("bank0000".to_string()),
+            from: Addr::unchecked("addr0000"),
+            to: Addr::unchecked("addr0001"),
+            amount: Uint128::from(amount),
+            idempotency_key: None,
+        })
+    }
+
+    #[test]
+    fn circuit_breaker_pauses_settlement() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let info = mock_info("creator", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+
+        let msg = ExecuteMsg::SetCircuitBreaker(CircuitBreakerMsg {
+            denom_from: "RUB".to_string(),
+            denom_to: "USD".to_string(),
+            max_update_deviation_bps: 1000,
+            max_window_deviation_bps: 1500,
+            window: 3600,
+        });
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // small moves within the window
+        env.block.time = env.block.time.plus_seconds(60);
+        let res = set_rate(deps.as_mut(), env.clone(), 210);
+        assert!(res.events.is_empty());
+        env.block.time = env.block.time.plus_seconds(60);
+        let res = set_rate(deps.as_mut(), env.clone(), 230);
+        assert!(res.events.is_empty());
+
+        // each update is fine but the rate moved 16% within the window
+        env.block.time = env.block.time.plus_seconds(60);
+        let res = set_rate(deps.as_mut(), env.clone(), 232);
+        assert_eq!(1, res.events.len());
+        assert_eq!("circuit_breaker_tripped", res.events[0].ty);
+        assert!(res.events[0].attributes.iter().any(|a| a.key == "kind" && a.value == "window"));
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::CircuitBreaker { id: "RUBUSD".to_string() }).unwrap();
+        let value: CircuitBreakerResponse = from_binary(&res).unwrap();
+        let breach = value.breach.unwrap();
+        assert_eq!(BreachKind::Window, breach.kind);
+        assert_eq!(200, breach.reference_rate);
+        assert_eq!(232, breach.new_rate);
+        assert_eq!(1600, breach.deviation_bps);
+
+        execute(deps.as_mut(), env.clone(), info.clone(), send_to_bank_msg(500)).unwrap();
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::CircuitBreakerTripped { .. }));
+
+        let confirm = ExecuteMsg::ConfirmExchangeRate { id: "RUBUSD".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), confirm.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), info.clone(), confirm).unwrap();
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Balance { address: "addr0001".to_string() }).unwrap();
+        let value: BalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::from(1116u128), value.amount);
+
+        // a jump in the verse direction is a single update breach
+        env.block.time = env.block.time.plus_seconds(60);
+        let msg = ExecuteMsg::SetExchangeRate(ExchangeRateMsg {
+            denom_from: "USD".to_string(),
+            denom_to: "RUB".to_string(),
+            precision: 3,
+            rate: 2000,
+        });
+        let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+        assert!(res.events[0].attributes.iter().any(|a| a.key == "kind" && a.value == "update"));
+
+        let res = query(deps.as_ref(), env, QueryMsg::RateBreaches { id: "RUBUSD".to_string(), start_after: None, limit: None }).unwrap();
+        let value: RateBreachesResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.breaches.len());
+        assert_eq!(BreachKind::Update, value.breaches[1].breach.kind);
+        assert_eq!(232, value.breaches[1].breach.reference_rate);
+        assert_eq!(500, value.breaches[1].breach.new_rate);
+    }
+
+    #[test]
+    fn pause_scopes() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let guardian = mock_info("guardian", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+
+        let msg = ExecuteMsg::SetGuardian { guardian: Some("guardian".to_string()) };
+        let err = execute(deps.as_mut(), env.clone(), guardian.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+        let pause = |scope: PauseScope| ExecuteMsg::Pause { scope };
+        let unpause = |scope: PauseScope| ExecuteMsg::Unpause { scope };
+        let rub = PauseScope::Denom { denom: "RUB".to_string() };
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), pause(rub.clone())).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), guardian.clone(), pause(rub.clone())).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap_err();
+        assert_eq!("Token RUB is paused", err.to_string());
+
+        // queries keep working
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Pauses {}).unwrap();
+        let value: PausesResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.pauses.len());
+        assert_eq!(rub, value.pauses[0].scope);
+        assert_eq!(Addr::unchecked("guardian"), value.pauses[0].paused_by);
+        query(deps.as_ref(), env.clone(), QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+
+        // only the admin unpauses
+        let err = execute(deps.as_mut(), env.clone(), guardian.clone(), unpause(rub.clone())).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), unpause(rub)).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+
+        // pausing the pair in one direction stops the settlement in the other
+        let pair = PauseScope::ExchangeRate { id: "USDRUB".to_string() };
+        execute(deps.as_mut(), env.clone(), guardian.clone(), pause(pair.clone())).unwrap();
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), msg.clone()).unwrap_err();
+        assert_eq!("Exchange rate USDRUB is paused", err.to_string());
+        execute(deps.as_mut(), env.clone(), admin.clone(), unpause(pair)).unwrap();
+
+        let bank = PauseScope::Bank { id: "bank0000".to_string() };
+        execute(deps.as_mut(), env.clone(), guardian.clone(), pause(bank.clone())).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), msg.clone()).unwrap_err();
+        assert_eq!("Bank bank0000 is paused", err.to_string());
+        execute(deps.as_mut(), env.clone(), admin.clone(), unpause(bank)).unwrap();
+
+        // the global pause stops every handler
+        execute(deps.as_mut(), env.clone(), guardian, pause(PauseScope::Global)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), msg.clone()).unwrap_err();
+        assert_eq!("Contract is paused", err.to_string());
+        let create_bank = ExecuteMsg::CreateBank(BankInfo {
+            id: "bank0001".to_string(),
+            name: "Bank".to_string(),
+            balance: Uint128::zero(),
+        });
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), create_bank).unwrap_err();
+        assert!(matches!(err, ContractError::ContractPaused {}));
+
+        execute(deps.as_mut(), env.clone(), admin.clone(), unpause(PauseScope::Global)).unwrap();
+        execute(deps.as_mut(), env, admin, msg).unwrap();
+    }
+
+    #[test]
+    fn freeze_accounts() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let compliance = mock_info("compliance", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+
+        let freeze = |address: &str, denom: Option<&str>| ExecuteMsg::Freeze {
+            address: address.to_string(),
+            denom: denom.map(String::from),
+            reason: "sanctions screening".to_string(),
+        };
+        let unfreeze = |address: &str, denom: Option<&str>| ExecuteMsg::Unfreeze {
+            address: address.to_string(),
+            denom: denom.map(String::from),
+            reason: "cleared".to_string(),
+        };
+
+        let err = execute(deps.as_mut(), env.clone(), compliance.clone(), freeze("addr0000", None)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let grant = ExecuteMsg::GrantRole { role: Role::Compliance, address: "compliance".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), compliance.clone(), grant.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), grant).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::RoleMembers { role: Role::Compliance, start_after: None, limit: None }).unwrap();
+        let value: RoleMembersResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![Addr::unchecked("compliance")], value.members);
+
+        // the sender is frozen for every denom
+        execute(deps.as_mut(), env.clone(), compliance.clone(), freeze("addr0000", None)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap_err();
+        assert_eq!("Account addr0000 is frozen", err.to_string());
+        execute(deps.as_mut(), env.clone(), compliance.clone(), unfreeze("addr0000", None)).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+
+        // freezing another denom of the recipient has no effect
+        execute(deps.as_mut(), env.clone(), compliance.clone(), freeze("addr0001", Some("RUB"))).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+
+        // settlement to a recipient frozen for the target denom fails
+        execute(deps.as_mut(), env.clone(), compliance.clone(), freeze("addr0001", Some("USD"))).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap_err();
+        assert!(matches!(err, ContractError::AccountFrozen { .. }));
+        let settle = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), settle.clone()).unwrap_err();
+        assert_eq!("Account addr0001 is frozen", err.to_string());
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::FrozenAccounts { start_after: None, limit: None }).unwrap();
+        let value: FrozenAccountsResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.accounts.len());
+        assert!(!value.accounts[0].all);
+        assert_eq!(vec!["RUB".to_string(), "USD".to_string()], value.accounts[0].denoms);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::FrozenAccounts { start_after: Some("addr0001".to_string()), limit: None }).unwrap();
+        let value: FrozenAccountsResponse = from_binary(&res).unwrap();
+        assert!(value.accounts.is_empty());
+
+        let err = execute(deps.as_mut(), env.clone(), compliance.clone(), unfreeze("addr0000", Some("RUB"))).unwrap_err();
+        assert!(matches!(err, ContractError::AccountNotFrozen { .. }));
+        execute(deps.as_mut(), env.clone(), compliance.clone(), unfreeze("addr0001", Some("USD"))).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), settle).unwrap();
+
+        // a full unfreeze lifts the remaining denom freezes
+        execute(deps.as_mut(), env.clone(), compliance.clone(), unfreeze("addr0001", None)).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::FrozenAccounts { start_after: None, limit: None }).unwrap();
+        let value: FrozenAccountsResponse = from_binary(&res).unwrap();
+        assert!(value.accounts.is_empty());
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::FreezeHistory { address: "addr0001".to_string(), start_after: None, limit: None }).unwrap();
+        let value: FreezeHistoryResponse = from_binary(&res).unwrap();
+        assert_eq!(4, value.records.len());
+        assert_eq!(FreezeAction::Freeze, value.records[0].record.action);
+        assert_eq!(Some("RUB".to_string()), value.records[0].record.denom);
+        assert_eq!("sanctions screening", value.records[0].record.reason);
+        assert_eq!(Addr::unchecked("compliance"), value.records[0].record.actor);
+        assert_eq!(FreezeAction::Unfreeze, value.records[3].record.action);
+        assert_eq!(None, value.records[3].record.denom);
+        assert_eq!("cleared", value.records[3].record.reason);
+
+        // revoked officers lose access
+        let revoke = ExecuteMsg::RevokeRole { role: Role::Compliance, address: "compliance".to_string() };
+        execute(deps.as_mut(), env.clone(), admin, revoke).unwrap();
+        let err = execute(deps.as_mut(), env, compliance, freeze("addr0000", None)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn kyc_tier_limits() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let attestor = mock_info("attestor", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+
+        // unattested accounts may not send, verified ones have daily and monthly caps
+        let set_limits = |tier: u32, limits: TierLimits| ExecuteMsg::SetTierLimits { tier, denom: "RUB".to_string(), limits };
+        let blocked = TierLimits { max_transfer: Some(Uint128::zero()), daily_volume: None, monthly_volume: None };
+        let verified = TierLimits {
+            max_transfer: Some(Uint128::from(100u128)),
+            daily_volume: Some(Uint128::from(150u128)),
+            monthly_volume: Some(Uint128::from(250u128)),
+        };
+        let err = execute(deps.as_mut(), env.clone(), attestor.clone(), set_limits(0, blocked.clone())).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), set_limits(0, blocked)).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), set_limits(1, verified)).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(10)).unwrap_err();
+        assert_eq!("Transfer exceeds the single transfer limit, 0 remaining", err.to_string());
+
+        let set_tier = ExecuteMsg::SetKycTier { address: "addr0000".to_string(), tier: 1 };
+        let err = execute(deps.as_mut(), env.clone(), attestor.clone(), set_tier.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let grant = ExecuteMsg::GrantRole { role: Role::Attestor, address: "attestor".to_string() };
+        execute(deps.as_mut(), env.clone(), admin.clone(), grant).unwrap();
+        execute(deps.as_mut(), env.clone(), attestor, set_tier).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(101)).unwrap_err();
+        assert!(matches!(err, ContractError::TransferLimitExceeded { .. }));
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(60)).unwrap_err();
+        assert_eq!("Transfer exceeds the daily limit, 50 remaining", err.to_string());
+
+        let allowance = |deps: Deps, env: Env| -> AllowanceResponse {
+            let msg = QueryMsg::RemainingAllowance { address: "addr0000".to_string(), denom: "RUB".to_string() };
+            from_binary(&query(deps, env, msg).unwrap()).unwrap()
+        };
+        let value = allowance(deps.as_ref(), env.clone());
+        assert_eq!(1, value.tier);
+        assert_eq!(Some(Uint128::from(100u128)), value.max_transfer);
+        assert_eq!(Some(Uint128::from(50u128)), value.daily_remaining);
+        assert_eq!(Some(Uint128::from(150u128)), value.monthly_remaining);
+
+        // the daily window rolls over, the monthly one does not
+        env.block.time = env.block.time.plus_seconds(86_400);
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        env.block.time = env.block.time.plus_seconds(86_400);
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap_err();
+        assert_eq!("Transfer exceeds the monthly limit, 50 remaining", err.to_string());
+
+        env.block.time = env.block.time.plus_seconds(28 * 86_400);
+        let value = allowance(deps.as_ref(), env.clone());
+        assert_eq!(Some(Uint128::from(150u128)), value.monthly_remaining);
+        execute(deps.as_mut(), env, admin, send_to_bank_msg(100)).unwrap();
+    }
+
+    #[test]
+    fn kyc_allowlist() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let attestor = mock_info("attestor", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let grant = ExecuteMsg::GrantRole { role: Role::Attestor, address: "attestor".to_string() };
+        execute(deps.as_mut(), env.clone(), admin.clone(), grant).unwrap();
+
+        // the limits hold on every way out of an account, not only SendToBank
+        let blocked = TierLimits { max_transfer: Some(Uint128::zero()), daily_volume: None, monthly_volume: None };
+        let msg = ExecuteMsg::SetTierLimits { tier: 0, denom: "RUB".to_string(), limits: blocked };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let place_order = ExecuteMsg::PlaceOrder {
+            buy_denom: "USD".to_string(),
+            amount: Uint128::from(100u128),
+            price: Decimal::from_ratio(1u128, 5u128),
+            receiver: Some("addr0002".to_string()),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), place_order).unwrap_err();
+        assert!(matches!(err, ContractError::TransferLimitExceeded { .. }));
+        // funding a bank moves tokens out too, they could be defunded to anyone
+        let blocked = TierLimits { max_transfer: Some(Uint128::zero()), daily_volume: None, monthly_volume: None };
+        let msg = ExecuteMsg::SetTierLimits { tier: 0, denom: "USD".to_string(), limits: blocked };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0000".to_string(), operator: Some("addr0001".to_string()) };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::FundBank { bank_id: "bank0000".to_string(), amount: Uint128::from(10u128) };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0001", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::TransferLimitExceeded { .. }));
+        let msg = ExecuteMsg::SetTierLimits { tier: 0, denom: "RUB".to_string(), limits: TierLimits { max_transfer: None, daily_volume: None, monthly_volume: None } };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+        // only attested accounts send or receive a token requiring KYC
+        let required = |denom: &str, required: bool| ExecuteMsg::SetKycRequired { denom: denom.to_string(), required };
+        let err = execute(deps.as_mut(), env.clone(), attestor.clone(), required("RUB", true)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), required("RUB", true)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap_err();
+        assert_eq!("Account addr0000 has no KYC tier required for RUB", err.to_string());
+        let msg = QueryMsg::RemainingAllowance { address: "addr0000".to_string(), denom: "RUB".to_string() };
+        assert!(!from_binary::<AllowanceResponse>(&query(deps.as_ref(), env.clone(), msg.clone()).unwrap()).unwrap().allowed);
+        let set_tier = ExecuteMsg::SetKycTier { address: "addr0000".to_string(), tier: 0 };
+        execute(deps.as_mut(), env.clone(), attestor.clone(), set_tier).unwrap();
+        assert!(from_binary::<AllowanceResponse>(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap().allowed);
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+
+        // a token can require KYC before it exists, its initial balances included
+        execute(deps.as_mut(), env.clone(), admin.clone(), required("EUR", true)).unwrap();
+        let create = ExecuteMsg::CreateToken(TokenInfoMsg {
+            name: "EUR".to_string(),
+            denom: "EUR".to_string(),
+            initial_balances: vec![Cw20Coin { address: "addr0003".to_string(), amount: Uint128::from(1000u128) }],
+        });
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), create.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted { .. }));
+        let set_tier = ExecuteMsg::SetKycTier { address: "addr0003".to_string(), tier: 1 };
+        execute(deps.as_mut(), env.clone(), attestor, set_tier).unwrap();
+        execute(deps.as_mut(), env, admin, create).unwrap();
+    }
+
+    #[test]
+    fn review_queue() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let operator = mock_info("operator", &[]);
+        let reviewer = mock_info("reviewer", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0000".to_string(), operator: Some("operator".to_string()) };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::GrantRole { role: Role::Reviewer, address: "reviewer".to_string() };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetReviewThreshold { bank_id: "bank0000".to_string(), denom: "RUB".to_string(), threshold: Some(Uint128::from(300u128)) };
+        let err = execute(deps.as_mut(), env.clone(), reviewer.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), operator.clone(), msg).unwrap();
+
+        // the amount rule holds the first one, the operator flags the second one later
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(300)).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        env.block.time = env.block.time.plus_seconds(60);
+        let flag = ExecuteMsg::FlagTransaction { transaction_id: "2".to_string(), note: "unusual pattern".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), reviewer.clone(), flag.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), operator, flag).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ReviewQueue { start_after: None, limit: None }).unwrap();
+        let value: ReviewQueueResponse = from_binary(&res).unwrap();
+        let ids: Vec<_> = value.entries.iter().map(|entry| entry.transaction_id.as_str()).collect();
+        assert_eq!(vec!["1", "2"], ids);
+        assert_eq!("amount rule: at least 300 RUB", value.entries[0].note);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ReviewQueue { start_after: Some("1".to_string()), limit: None }).unwrap();
+        let value: ReviewQueueResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.entries.len());
+
+        // funds stay locked while under review
+        let settle = |id: &str| ExecuteMsg::SendToRecipient { transaction_id: id.to_string(), amount: None };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), settle("1")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransactionStatus { .. }));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(500u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+
+        let approve = ExecuteMsg::ApproveTransaction { transaction_id: "1".to_string(), note: "documents checked".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::ApproveTransaction { transaction_id: "3".to_string(), note: String::new() }).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransactionStatus { .. }));
+        execute(deps.as_mut(), env.clone(), reviewer.clone(), approve).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), settle("1")).unwrap();
+
+        let reject = ExecuteMsg::RejectTransaction { transaction_id: "2".to_string(), note: "no explanation".to_string() };
+        execute(deps.as_mut(), env.clone(), reviewer, reject).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(600u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(1100u128), from_binary::<BankInfoResponse>(&res).unwrap().balance);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::TransactionInfo { id: "2".to_string() }).unwrap();
+        let value: TransactionInfoResponse = from_binary(&res).unwrap();
+        assert_eq!(TransactionStatus::RejectedByBank, value.status);
+        let actions: Vec<_> = value.reviews.iter().map(|review| review.action.clone()).collect();
+        assert_eq!(vec![ReviewAction::Flagged, ReviewAction::Rejected], actions);
+        assert_eq!(Addr::unchecked("operator"), value.reviews[0].actor);
+        assert_eq!("no explanation", value.reviews[1].note);
+
+        let res = query(deps.as_ref(), env, QueryMsg::ReviewQueue { start_after: None, limit: None }).unwrap();
+        let value: ReviewQueueResponse = from_binary(&res).unwrap();
+        assert!(value.entries.is_empty());
+    }
+
+    #[test]
+    fn idempotent_send_to_bank() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        setup_trading(deps.as_mut(), env.clone());
+
+        let msg = |key: &str| ExecuteMsg::SendToBank(TransactionMsg {
+            bank_id: Some("bank0000".to_string()),
+            from: Addr::unchecked("addr0000"),
+            to: Addr::unchecked("addr0001"),
+            amount: Uint128::from(100u128),
+            idempotency_key: Some(key.to_string()),
+        });
+        let transaction_id = |res: Response| from_binary::<TransactionIdResponse>(&res.data.unwrap()).unwrap().transaction_id;
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg("order-1")).unwrap();
+        assert_eq!("1", transaction_id(res));
+        // the retry is not charged twice
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg("order-1")).unwrap();
+        assert_eq!("1", transaction_id(res));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(900u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+
+        // keys are scoped per sender
+        let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg("order-1")).unwrap();
+        assert_eq!("2", transaction_id(res));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg("order-2")).unwrap();
+        assert_eq!("3", transaction_id(res));
+        let res = query(deps.as_ref(), env, QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(700u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+    }
+
+    #[test]
+    fn cancel_transaction() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let sender = mock_info("addr0000", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+
+        let cancel = |id: &str| ExecuteMsg::CancelTransaction { transaction_id: id.to_string() };
+        let acknowledge = |id: &str| ExecuteMsg::AcknowledgeTransaction { transaction_id: id.to_string() };
+        let balance = |deps: Deps| -> Uint128 {
+            let res = query(deps, mock_env(), QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+            from_binary::<BalanceResponse>(&res).unwrap().amount
+        };
+        for _ in 0..3 {
+            execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        }
+
+        // only the sender cancels, and the bank income is reversed
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), cancel("1")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), sender.clone(), cancel("1")).unwrap();
+        assert_eq!(Uint128::from(800u128), balance(deps.as_ref()));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(1200u128), from_binary::<BankInfoResponse>(&res).unwrap().balance);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::TransactionInfo { id: "1".to_string() }).unwrap();
+        assert_eq!(TransactionStatus::Cancelled, from_binary::<TransactionInfoResponse>(&res).unwrap().status);
+
+        let err = execute(deps.as_mut(), env.clone(), sender.clone(), cancel("1")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransactionStatus { .. }));
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransactionStatus { .. }));
+
+        // by default an acknowledged transaction can still be cancelled
+        execute(deps.as_mut(), env.clone(), admin.clone(), acknowledge("2")).unwrap();
+        execute(deps.as_mut(), env.clone(), sender.clone(), cancel("2")).unwrap();
+
+        let msg = ExecuteMsg::SetCancellationPolicy { bank_id: "bank0000".to_string(), policy: CancellationPolicy::UntilAcknowledged };
+        let err = execute(deps.as_mut(), env.clone(), sender.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        execute(deps.as_mut(), env.clone(), admin, acknowledge("3")).unwrap();
+        let err = execute(deps.as_mut(), env, sender, cancel("3")).unwrap_err();
+        assert!(matches!(err, ContractError::CancellationNotAllowed { .. }));
+        assert_eq!(Uint128::from(900u128), balance(deps.as_ref()));
+    }
+
+    #[test]
+    fn partial_settlement() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let admin = mock_info("creator", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(500)).unwrap();
+
+        let settle = |amount: Option<u128>| ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: amount.map(Uint128::from) };
+        let transaction = |deps: Deps| -> TransactionInfoResponse {
+            let res = query(deps, mock_env(), QueryMsg::TransactionInfo { id: "1".to_string() }).unwrap();
+            from_binary(&res).unwrap()
+        };
+
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), settle(Some(0))).unwrap_err();
+        assert!(matches!(err, ContractError::ZeroAmount {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), settle(Some(200))).unwrap();
+        let value = transaction(deps.as_ref());
+        assert_eq!(TransactionStatus::PartiallySettled, value.status);
+        assert_eq!(Uint128::from(200u128), value.settled);
+        assert_eq!(Uint128::from(300u128), value.outstanding);
+
+        // a partially settled transaction cannot be cancelled
+        let cancel = ExecuteMsg::CancelTransaction { transaction_id: "1".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), cancel).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransactionStatus { .. }));
+
+        // the next tranche goes at the new rate
+        env.block.time = env.block.time.plus_seconds(60);
+        set_rate(deps.as_mut(), env.clone(), 210);
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), settle(Some(301))).unwrap_err();
+        assert_eq!("Settlement of 301 exceeds the outstanding 300", err.to_string());
+        execute(deps.as_mut(), env.clone(), admin.clone(), settle(None)).unwrap();
+
+        let value = transaction(deps.as_ref());
+        assert_eq!(TransactionStatus::SentToRecipient, value.status);
+        assert!(value.outstanding.is_zero());
+        assert_eq!(2, value.tranches.len());
+        assert_eq!((Uint128::from(200u128), Uint128::from(40u128), 200), (value.tranches[0].amount, value.tranches[0].received, value.tranches[0].rate));
+        assert_eq!((Uint128::from(300u128), Uint128::from(63u128), 210), (value.tranches[1].amount, value.tranches[1].received, value.tranches[1].rate));
+        assert_eq!(env.block.time, value.tranches[1].timestamp);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Balance { address: "addr0001".to_string() }).unwrap();
+        assert_eq!(Uint128::from(1103u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+        let err = execute(deps.as_mut(), env, admin, settle(None)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransactionStatus { .. }));
+    }
+
+    #[test]
+    fn batch_send_and_settle() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        for address in ["addr0002", "addr0003"] {
+            BALANCES.save(deps.as_mut().storage, &Addr::unchecked(address), &BalanceInfo { amount: Uint128::zero(), denom: "USD".to_string() }).unwrap();
+        }
+
+        let batch = |recipients: &[(&str, u128)], mode: BatchMode| ExecuteMsg::SendToBankBatch(BatchTransactionMsg {
+            bank_id: "bank0000".to_string(),
+            from: Addr::unchecked("addr0000"),
+            payments: recipients.iter().map(|(to, amount)| PaymentMsg {
+                to: Addr::unchecked(*to),
+                amount: Uint128::from(*amount),
+                idempotency_key: None,
+            }).collect(),
+            mode,
+        });
+        let batch_results = |res: Response| from_binary::<BatchResponse>(&res.data.unwrap()).unwrap().results;
+
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), batch(&[], BatchMode::BestEffort)).unwrap_err();
+        assert!(matches!(err, ContractError::EmptyBatch {}));
+        // nobody else spends from the sender account
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0001", &[]), batch(&[("addr0001", 100)], BatchMode::BestEffort)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), batch(&[("unknown", 100)], BatchMode::AllOrNothing)).unwrap_err();
+        assert!(matches!(err, ContractError::AccountDoesNotExist { .. }));
+
+        // the unknown recipient is skipped, the others get transaction ids
+        let msg = batch(&[("addr0001", 100), ("unknown", 100), ("addr0002", 200), ("addr0003", 300)], BatchMode::BestEffort);
+        let res = execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "failed" && a.value == "1"));
+        let results = batch_results(res);
+        assert_eq!(4, results.len());
+        assert_eq!(Some("1".to_string()), results[0].transaction_id);
+        assert_eq!(None, results[1].transaction_id);
+        assert_eq!(Some("Account does not exist".to_string()), results[1].error);
+        assert_eq!(Some("3".to_string()), results[3].transaction_id);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(400u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+
+        // a retry by the admin of the batch the sender submitted returns the same transaction
+        let keyed = ExecuteMsg::SendToBankBatch(BatchTransactionMsg {
+            bank_id: "bank0000".to_string(),
+            from: Addr::unchecked("addr0000"),
+            payments: vec![PaymentMsg { to: Addr::unchecked("addr0001"), amount: Uint128::from(50u128), idempotency_key: Some("payroll".to_string()) }],
+            mode: BatchMode::AllOrNothing,
+        });
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), keyed.clone()).unwrap();
+        assert_eq!(Some("4".to_string()), batch_results(res)[0].transaction_id);
+        let res = execute(deps.as_mut(), env.clone(), admin.clone(), keyed).unwrap();
+        assert_eq!(Some("4".to_string()), batch_results(res)[0].transaction_id);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(350u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+
+        let settle = |ids: &[&str], mode: BatchMode| ExecuteMsg::SettleBatch { transaction_ids: ids.iter().map(|id| id.to_string()).collect(), mode };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), settle(&["1", "9"], BatchMode::AllOrNothing)).unwrap_err();
+        assert!(matches!(err, ContractError::TransactionDoesNotExist { .. }));
+        execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), ExecuteMsg::CancelTransaction { transaction_id: "2".to_string() }).unwrap();
+
+        let res = execute(deps.as_mut(), env.clone(), admin, settle(&["2", "3"], BatchMode::BestEffort)).unwrap();
+        let results = batch_results(res);
+        assert!(results[0].error.is_some());
+        assert_eq!(None, results[1].error);
+        let res = query(deps.as_ref(), env, QueryMsg::Balance { address: "addr0003".to_string() }).unwrap();
+        assert_eq!(Uint128::from(60u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+    }
+
+    #[test]
+    fn clearing_cycle_nets_obligations() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let admin = mock_info("creator", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let msg = ExecuteMsg::CreateBank(BankInfo { id: "bank0001".to_string(), name: "Bank".to_string(), balance: Uint128::from(1000u128) });
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0001".to_string(), operator: Some("addr0000".to_string()) };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::FundBank { bank_id: "bank0001".to_string(), amount: Uint128::from(500u128) };
+        execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg).unwrap();
+
+        // bank0000 takes 500 RUB and pays 100 USD, bank0001 takes 100 USD and pays 500 RUB
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(500)).unwrap();
+        let msg = ExecuteMsg::SendToBank(TransactionMsg {
+            bank_id: Some("bank0001".to_string()),
+            from: Addr::unchecked("addr0001"),
+            to: Addr::unchecked("addr0000"),
+            amount: Uint128::from(100u128),
+            idempotency_key: None,
+        });
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SettleBatch { transaction_ids: vec!["1".to_string(), "2".to_string()], mode: BatchMode::AllOrNothing };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::NetObligations {}).unwrap();
+        let value: NetObligationsResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.cycle);
+        assert_eq!(4, value.positions.len());
+        let expected = vec![
+            NetTransfer { denom: "RUB".to_string(), from_bank: "bank0000".to_string(), to_bank: "bank0001".to_string(), amount: Uint128::from(500u128) },
+            NetTransfer { denom: "USD".to_string(), from_bank: "bank0001".to_string(), to_bank: "bank0000".to_string(), amount: Uint128::from(100u128) },
+        ];
+        assert_eq!(expected, value.transfers);
+
+        // only the admin closes a cycle, a rejected close leaves it open
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), ExecuteMsg::CloseClearingCycle {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::NetObligations {}).unwrap();
+        assert_eq!(1, from_binary::<NetObligationsResponse>(&res).unwrap().cycle);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ClearingCycles { start_after: None, limit: None }).unwrap();
+        assert!(from_binary::<ClearingCyclesResponse>(&res).unwrap().cycles.is_empty());
+        env.block.time = env.block.time.plus_seconds(3600);
+        execute(deps.as_mut(), env.clone(), admin, ExecuteMsg::CloseClearingCycle {}).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::NetObligations {}).unwrap();
+        let value: NetObligationsResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.cycle);
+        assert_eq!(env.block.time, value.opened_at);
+        assert!(value.positions.is_empty() && value.transfers.is_empty());
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ClearingCycles { start_after: None, limit: None }).unwrap();
+        let value: ClearingCyclesResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.cycles.len());
+        assert_eq!(env.block.time, value.cycles[0].closed_at);
+        assert_eq!(expected, value.cycles[0].transfers);
+    }
+
+    #[test]
+    fn fund_and_defund_bank() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let operator = mock_info("addr0000", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0000".to_string(), operator: Some("addr0000".to_string()) };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+        let fund = |amount: u128| ExecuteMsg::FundBank { bank_id: "bank0000".to_string(), amount: Uint128::from(amount) };
+        let defund = |amount: u128| ExecuteMsg::DefundBank { bank_id: "bank0000".to_string(), denom: "RUB".to_string(), amount: Uint128::from(amount), receiver: None };
+        let bank = |deps: Deps| -> BankInfoResponse {
+            let res = query(deps, mock_env(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+            from_binary(&res).unwrap()
+        };
+        let reserves = |rub: u128, usd: u128| vec![
+            BalanceResponse { amount: Uint128::from(rub), denom: "RUB".to_string() },
+            BalanceResponse { amount: Uint128::from(usd), denom: "USD".to_string() },
+        ];
+        let balance = |deps: Deps, address: &str| -> Uint128 {
+            let res = query(deps, mock_env(), QueryMsg::Balance { address: address.to_string() }).unwrap();
+            from_binary::<BalanceResponse>(&res).unwrap().amount
+        };
+
+        // only the operator moves reserves, not even the admin
+        let err = execute(deps.as_mut(), env.clone(), admin, fund(500)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(deps.as_mut(), env.clone(), operator.clone(), fund(1500)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+        execute(deps.as_mut(), env.clone(), operator.clone(), fund(500)).unwrap();
+        // reserves are not counted in the funds the bank holds for pending transactions
+        let value = bank(deps.as_ref());
+        assert_eq!(Uint128::from(1000u128), value.balance);
+        assert_eq!(reserves(500, 1000), value.reserves);
+
+        // a pending transaction holds back part of the reserves
+        execute(deps.as_mut(), env.clone(), operator.clone(), send_to_bank_msg(300)).unwrap();
+        assert_eq!(vec![BalanceResponse { amount: Uint128::from(300u128), denom: "RUB".to_string() }], bank(deps.as_ref()).outstanding);
+        let err = execute(deps.as_mut(), env.clone(), operator.clone(), defund(300)).unwrap_err();
+        assert!(matches!(err, ContractError::BelowOutstandingObligations { .. }));
+        execute(deps.as_mut(), env.clone(), operator.clone(), defund(200)).unwrap();
+
+        // the bank pays 60 USD out of its reserves and keeps the 300 RUB
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        execute(deps.as_mut(), env.clone(), operator.clone(), msg).unwrap();
+        let value = bank(deps.as_ref());
+        assert_eq!(vec![BalanceResponse { amount: Uint128::zero(), denom: "RUB".to_string() }], value.outstanding);
+        assert_eq!(reserves(600, 940), value.reserves);
+        assert_eq!(Uint128::from(1060u128), balance(deps.as_ref(), "addr0001"));
+        let err = execute(deps.as_mut(), env.clone(), operator.clone(), defund(700)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+        execute(deps.as_mut(), env.clone(), operator, defund(600)).unwrap();
+
+        let value = bank(deps.as_ref());
+        assert_eq!(Uint128::from(1000u128), value.balance);
+        assert_eq!(reserves(0, 940), value.reserves);
+        assert_eq!(Uint128::from(1000u128), balance(deps.as_ref(), "addr0000"));
+    }
+
+    #[test]
+    fn bank_lifecycle() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let bank_id = || "bank0000".to_string();
+        let bank = |deps: Deps| -> BankInfoResponse {
+            let res = query(deps, mock_env(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+            from_binary(&res).unwrap()
+        };
+
+        let msg = ExecuteMsg::UpdateBank { bank_id: bank_id(), name: Some("Renamed".to_string()), operator: Some("addr0001".to_string()) };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let value = bank(deps.as_ref());
+        assert_eq!("Renamed", value.name);
+        assert_eq!(Some(Addr::unchecked("addr0001")), value.operator);
+        assert_eq!(BankStatus::Active, value.status);
+
+        // a suspended bank takes no new transactions but settles the pending ones
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::SuspendBank { bank_id: bank_id() }).unwrap();
+        assert_eq!(BankStatus::Suspended, bank(deps.as_ref()).status);
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap_err();
+        assert!(matches!(err, ContractError::BankSuspended { .. }));
+
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::CloseBank { bank_id: bank_id() }).unwrap_err();
+        assert!(matches!(err, ContractError::BankNotSettled { .. }));
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::CloseBank { bank_id: bank_id() }).unwrap_err();
+        assert!(matches!(err, ContractError::BankNotSettled { .. }));
+
+        // the operator withdraws the reserves first, the RUB go to an account of their own
+        let defund = |denom: &str, amount: u128, receiver: Option<&str>| ExecuteMsg::DefundBank {
+            bank_id: bank_id(),
+            denom: denom.to_string(),
+            amount: Uint128::from(amount),
+            receiver: receiver.map(str::to_string),
+        };
+        let operator = mock_info("addr0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), operator.clone(), defund("RUB", 100, None)).unwrap_err();
+        assert!(matches!(err, ContractError::TokenAlreadyAssigned { .. }));
+        execute(deps.as_mut(), env.clone(), operator.clone(), defund("RUB", 100, Some("addr0002"))).unwrap();
+        execute(deps.as_mut(), env.clone(), operator, defund("USD", 980, None)).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::CloseBank { bank_id: bank_id() }).unwrap();
+
+        assert_eq!(BankStatus::Closed, bank(deps.as_ref()).status);
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::ResumeBank { bank_id: bank_id() }).unwrap_err();
+        assert!(matches!(err, ContractError::BankClosed { .. }));
+        let err = execute(deps.as_mut(), env, admin, send_to_bank_msg(100)).unwrap_err();
+        assert!(matches!(err, ContractError::BankClosed { .. }));
+    }
+
+    #[test]
+    fn closed_bank_rejects_every_path() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let operator = mock_info("addr0001", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let msg = ExecuteMsg::CreateBank(BankInfo { id: "bank0001".to_string(), name: "Bank".to_string(), balance: Uint128::from(1000u128) });
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0001".to_string(), operator: Some("addr0001".to_string()) };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::CloseBank { bank_id: "bank0001".to_string() }).unwrap();
+
+        let msg = ExecuteMsg::FundBank { bank_id: "bank0001".to_string(), amount: Uint128::from(100u128) };
+        let err = execute(deps.as_mut(), env.clone(), operator.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::BankClosed { .. }));
+        let msg = ExecuteMsg::UpdateBank { bank_id: "bank0001".to_string(), name: Some("Renamed".to_string()), operator: None };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::BankClosed { .. }));
+        let err = execute(deps.as_mut(), env.clone(), admin, ExecuteMsg::SuspendBank { bank_id: "bank0001".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::BankClosed { .. }));
+        let msg = ExecuteMsg::SendToBankBatch(BatchTransactionMsg {
+            bank_id: "bank0001".to_string(),
+            from: Addr::unchecked("addr0000"),
+            payments: vec![PaymentMsg { to: Addr::unchecked("addr0001"), amount: Uint128::from(100u128), idempotency_key: None }],
+            mode: BatchMode::AllOrNothing,
+        });
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::BankClosed { .. }));
+        let res = query(deps.as_ref(), env, QueryMsg::Balance { address: "addr0000".to_string() }).unwrap();
+        assert_eq!(Uint128::from(1000u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+    }
+
+    #[test]
+    fn collateral_caps_exposure() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let operator = mock_info("addr0001", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let config = CollateralConfig { denom: "USD".to_string(), amount: Uint128::from(50u128), exposure_multiple: 2, keeper_bounty_bps: 0 };
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::SetCollateralConfig(config)).unwrap();
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0000".to_string(), operator: Some("addr0001".to_string()) };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+        // the creator locks the configured collateral
+        let create = |id: &str| ExecuteMsg::CreateBank(BankInfo { id: id.to_string(), name: "Bank".to_string(), balance: Uint128::zero() });
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), create("bank0001")).unwrap_err();
+        assert!(matches!(err, ContractError::AccountDoesNotExist { .. }));
+        execute(deps.as_mut(), env.clone(), operator.clone(), create("bank0001")).unwrap();
+
+        // a bank without collateral takes no transfers
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(500)).unwrap_err();
+        assert!(matches!(err, ContractError::ExposureCapExceeded { .. }));
+        let msg = ExecuteMsg::PostCollateral { bank_id: "bank0000".to_string(), amount: Uint128::from(60u128) };
+        execute(deps.as_mut(), env.clone(), operator.clone(), msg).unwrap();
+
+        // exposure is valued in USD at the 0.2 rate, the cap is 120 USD
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(500)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(150)).unwrap_err();
+        assert!(matches!(err, ContractError::ExposureCapExceeded { .. }));
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Solvency { start_after: None, limit: None }).unwrap();
+        let value: SolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Some("USD".to_string()), value.denom);
+        assert_eq!(vec![
+            BankSolvency { bank_id: "bank0000".to_string(), collateral: Uint128::from(60u128), exposure: Uint128::from(120u128), cap: Uint128::from(120u128) },
+            BankSolvency { bank_id: "bank0001".to_string(), collateral: Uint128::from(50u128), exposure: Uint128::zero(), cap: Uint128::from(100u128) },
+        ], value.banks);
+
+        // collateral is released while the exposure stays under the cap and above the minimum
+        let withdraw = |amount: u128| ExecuteMsg::WithdrawCollateral { bank_id: "bank0000".to_string(), amount: Uint128::from(amount) };
+        let err = execute(deps.as_mut(), env.clone(), operator.clone(), withdraw(5)).unwrap_err();
+        assert!(matches!(err, ContractError::ExposureCapExceeded { .. }));
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        execute(deps.as_mut(), env.clone(), admin, msg).unwrap();
+        execute(deps.as_mut(), env.clone(), operator.clone(), withdraw(10)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), operator, withdraw(1)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+
+        let res = query(deps.as_ref(), env, QueryMsg::Balance { address: "addr0001".to_string() }).unwrap();
+        assert_eq!(Uint128::from(1000u128), from_binary::<BalanceResponse>(&res).unwrap().amount);
+    }
+
+    #[test]
+    fn claim_default() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let operator = mock_info("addr0001", &[]);
+        let keeper = mock_info("keeper", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let config = CollateralConfig { denom: "USD".to_string(), amount: Uint128::zero(), exposure_multiple: 10, keeper_bounty_bps: 1000 };
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::SetCollateralConfig(config)).unwrap();
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0000".to_string(), operator: Some("addr0001".to_string()) };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::PostCollateral { bank_id: "bank0000".to_string(), amount: Uint128::from(105u128) };
+        execute(deps.as_mut(), env.clone(), operator.clone(), msg).unwrap();
+        let claim = |id: &str, receiver: Option<&str>| ExecuteMsg::ClaimDefault { transaction_id: id.to_string(), receiver: receiver.map(String::from) };
+        let balance = |deps: Deps, address: &str| -> Uint128 {
+            let res = query(deps, mock_env(), QueryMsg::Balance { address: address.to_string() }).unwrap();
+            from_binary::<BalanceResponse>(&res).unwrap().amount
+        };
+        let supply = |deps: Deps, denom: &str| -> Uint128 {
+            let res = query(deps, mock_env(), QueryMsg::TokenInfo { denom: denom.to_string() }).unwrap();
+            from_binary::<TokenInfoResponse>(&res).unwrap().total_supply
+        };
+        let bank_balance = |deps: Deps| -> Uint128 {
+            let res = query(deps, mock_env(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+            from_binary::<BankInfoResponse>(&res).unwrap().balance
+        };
+        // the sender holds RUB and takes the USD collateral on another account
+        BALANCES.save(deps.as_mut().storage, &Addr::unchecked("addr0002"), &BalanceInfo { amount: Uint128::zero(), denom: "USD".to_string() }).unwrap();
+
+        // transactions sent before the bank commits to an SLA have no deadline
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        let msg = ExecuteMsg::SetSettlementSla { bank_id: "bank0000".to_string(), seconds: Some(3600) };
+        execute(deps.as_mut(), env.clone(), operator, msg).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(500)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), keeper.clone(), claim("2", None)).unwrap_err();
+        assert!(matches!(err, ContractError::SettlementNotOverdue { .. }));
+
+        env.block.time = env.block.time.plus_seconds(3601);
+        let err = execute(deps.as_mut(), env.clone(), keeper.clone(), claim("1", None)).unwrap_err();
+        assert!(matches!(err, ContractError::SettlementNotOverdue { .. }));
+        // only the sender sends the compensation elsewhere, the RUB account cannot take USD
+        let err = execute(deps.as_mut(), env.clone(), keeper.clone(), claim("2", Some("keeper"))).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(deps.as_mut(), env.clone(), keeper.clone(), claim("2", None)).unwrap_err();
+        assert!(matches!(err, ContractError::TokenAlreadyAssigned { .. }));
+        let (rub, usd) = (supply(deps.as_ref(), "RUB"), supply(deps.as_ref(), "USD"));
+        execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), claim("2", Some("addr0002"))).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), keeper, claim("2", None)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransactionStatus { .. }));
+
+        // the 500 RUB come back out of what the bank received, they are worth 100 USD of collateral
+        // and the 10 USD bounty is capped at the 5 left, the sender claimed it so the receiver takes it
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::BankDefaults { bank_id: "bank0000".to_string(), start_after: None, limit: None }).unwrap();
+        let value: BankDefaultsResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.defaults.len());
+        assert_eq!((Uint128::from(500u128), Uint128::from(100u128)), (value.defaults[0].refunded, value.defaults[0].compensation));
+        assert_eq!((Uint128::from(105u128), Uint128::from(5u128)), (value.defaults[0].slashed, value.defaults[0].bounty));
+        assert_eq!(Uint128::from(900u128), balance(deps.as_ref(), "addr0000"));
+        assert_eq!(Uint128::from(105u128), balance(deps.as_ref(), "addr0002"));
+        // nothing is minted or burnt
+        assert_eq!((rub, usd), (supply(deps.as_ref(), "RUB"), supply(deps.as_ref(), "USD")));
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::TransactionInfo { id: "2".to_string() }).unwrap();
+        assert_eq!(TransactionStatus::Defaulted, from_binary::<TransactionInfoResponse>(&res).unwrap().status);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Solvency { start_after: None, limit: None }).unwrap();
+        let value: SolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::zero(), value.banks[0].collateral);
+        assert_eq!(Uint128::from(20u128), value.banks[0].exposure);
+
+        // 10 USD of collateral cover 50 of 200 RUB, the RUB come back in full but never out of the 1000 the bank was created with
+        let msg = ExecuteMsg::PostCollateral { bank_id: "bank0000".to_string(), amount: Uint128::from(10u128) };
+        execute(deps.as_mut(), env.clone(), mock_info("addr0001", &[]), msg).unwrap();
+        execute(deps.as_mut(), env.clone(), admin, send_to_bank_msg(200)).unwrap();
+        assert_eq!(Uint128::from(1300u128), bank_balance(deps.as_ref()));
+        env.block.time = env.block.time.plus_seconds(3601);
+        execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), claim("3", Some("addr0002"))).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::BankDefaults { bank_id: "bank0000".to_string(), start_after: None, limit: None }).unwrap();
+        let value: BankDefaultsResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.defaults.len());
+        assert_eq!((Uint128::from(200u128), Uint128::from(10u128)), (value.defaults[1].refunded, value.defaults[1].compensation));
+        assert_eq!(Uint128::from(900u128), balance(deps.as_ref(), "addr0000"));
+        assert_eq!(Uint128::from(115u128), balance(deps.as_ref(), "addr0002"));
+        assert_eq!(Uint128::from(1100u128), bank_balance(deps.as_ref()));
+        assert_eq!((rub, usd), (supply(deps.as_ref(), "RUB"), supply(deps.as_ref(), "USD")));
+        let res = query(deps.as_ref(), env, QueryMsg::TransactionInfo { id: "3".to_string() }).unwrap();
+        let value: TransactionInfoResponse = from_binary(&res).unwrap();
+        assert_eq!(TransactionStatus::Defaulted, value.status);
+        assert_eq!((Uint128::from(200u128), Uint128::zero()), (value.compensated, value.outstanding));
+    }
+
+    #[test]
+    fn bank_stats_and_ranking() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let admin = mock_info("creator", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let msg = ExecuteMsg::CreateBank(BankInfo { id: "bank0001".to_string(), name: "Bank".to_string(), balance: Uint128::from(1000u128) });
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+        for _ in 0..3 {
+            execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        }
+        let msg = ExecuteMsg::SendToBank(TransactionMsg {
+            bank_id: Some("bank0001".to_string()),
+            from: Addr::unchecked("addr0000"),
+            to: Addr::unchecked("addr0001"),
+            amount: Uint128::from(50u128),
+            idempotency_key: None,
+        });
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+
+        env.block.time = env.block.time.plus_seconds(60);
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::FlagTransaction { transaction_id: "2".to_string(), note: "check".to_string() };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::RejectTransaction { transaction_id: "2".to_string(), note: "rejected".to_string() };
+        execute(deps.as_mut(), env.clone(), admin, msg).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::BankStats { bank_id: "bank0000".to_string() }).unwrap();
+        let value: BankStatsResponse = from_binary(&res).unwrap();
+        assert_eq!((3, 1, 1, 0), (value.accepted, value.settled, value.rejected, value.defaulted));
+        assert_eq!(Some(60), value.average_settlement_time);
+        assert_eq!(Some(3333), value.settlement_rate_bps);
+        assert_eq!(vec![BalanceResponse { amount: Uint128::from(300u128), denom: "RUB".to_string() }], value.volumes);
+
+        let ranking = |deps: Deps, metric: RankingMetric| -> Vec<String> {
+            let res = query(deps, mock_env(), QueryMsg::BankRanking { metric, limit: None }).unwrap();
+            from_binary::<BankRankingResponse>(&res).unwrap().banks.into_iter().map(|bank| bank.bank_id).collect()
+        };
+        assert_eq!(vec!["bank0000", "bank0001"], ranking(deps.as_ref(), RankingMetric::SettlementRate));
+        assert_eq!(vec!["bank0000", "bank0001"], ranking(deps.as_ref(), RankingMetric::AverageSettlementTime));
+        assert_eq!(vec!["bank0000", "bank0001"], ranking(deps.as_ref(), RankingMetric::DefaultRate));
+
+        // unknown banks have no stats, and a rejected transaction is not counted as accepted
+        let err = query(deps.as_ref(), env.clone(), QueryMsg::BankStats { bank_id: "bank9999".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::BankNotRegistered { .. }));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), send_to_bank_msg(5000)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+        let res = query(deps.as_ref(), env, QueryMsg::BankStats { bank_id: "bank0000".to_string() }).unwrap();
+        assert_eq!(3, from_binary::<BankStatsResponse>(&res).unwrap().accepted);
+    }
+
+    #[test]
+    fn route_to_best_bank() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = mock_info("creator", &[]);
+        let operator = mock_info("addr0001", &[]);
+        setup_trading(deps.as_mut(), env.clone());
+        let msg = ExecuteMsg::CreateBank(BankInfo { id: "bank0001".to_string(), name: "Bank".to_string(), balance: Uint128::from(1000u128) });
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        for bank_id in ["bank0000", "bank0001"] {
+            let msg = ExecuteMsg::SetBankOperator { bank_id: bank_id.to_string(), operator: Some("addr0001".to_string()) };
+            execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+            let msg = ExecuteMsg::FundBank { bank_id: bank_id.to_string(), amount: Uint128::from(300u128) };
+            execute(deps.as_mut(), env.clone(), operator.clone(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::SetBankFee { bank_id: "bank0000".to_string(), fee_bps: 10_001 };
+        let err = execute(deps.as_mut(), env.clone(), operator.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFee { .. }));
+        let msg = ExecuteMsg::SetBankFee { bank_id: "bank0000".to_string(), fee_bps: 100 };
+        execute(deps.as_mut(), env.clone(), operator, msg).unwrap();
+
+        let preview = |deps: Deps, amount: u128| {
+            let msg = QueryMsg::RoutePreview { from: "addr0000".to_string(), to: "addr0001".to_string(), amount: Uint128::from(amount) };
+            query(deps, mock_env(), msg).map(|res| from_binary::<RouteDecision>(&res).unwrap())
+        };
+        let quote = |bank_id: &str, fee_bps: u64, received: u128, reserves: u128| RouteQuote {
+            bank_id: bank_id.to_string(),
+            fee_bps,
+            received: Uint128::from(received),
+            reserves: Uint128::from(reserves),
+        };
+        let expected = RouteDecision { chosen: quote("bank0001", 0, 100, 300), alternatives: vec![quote("bank0000", 100, 99, 1300)] };
+        assert_eq!(expected, preview(deps.as_ref(), 500).unwrap());
+        let err = preview(deps.as_ref(), 7000).unwrap_err();
+        assert!(matches!(err, ContractError::NoRouteAvailable { .. }));
+
+        // the decision is recorded on the transaction
+        let msg = ExecuteMsg::SendToBank(TransactionMsg {
+            bank_id: None,
+            from: Addr::unchecked("addr0000"),
+            to: Addr::unchecked("addr0001"),
+            amount: Uint128::from(500u128),
+            idempotency_key: None,
+        });
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::TransactionInfo { id: "1".to_string() }).unwrap();
+        let value: TransactionInfoResponse = from_binary(&res).unwrap();
+        assert_eq!("bank0001", value.bank_id);
+        assert_eq!(Some(expected), value.route);
+
+        // suspended banks are left out, and the fee is taken on settlement
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::SuspendBank { bank_id: "bank0001".to_string() }).unwrap();
+        assert_eq!("bank0000", preview(deps.as_ref(), 100).unwrap().chosen.bank_id);
+
+        // as are banks that could not settle while a token is paused
+        let scope = PauseScope::Denom { denom: "USD".to_string() };
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::Pause { scope: scope.clone() }).unwrap();
+        let err = preview(deps.as_ref(), 100).unwrap_err();
+        assert!(matches!(err, ContractError::NoRouteAvailable { .. }));
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::Unpause { scope }).unwrap();
+        execute(deps.as_mut(), env.clone(), admin.clone(), send_to_bank_msg(100)).unwrap();
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "2".to_string(), amount: None };
+        execute(deps.as_mut(), env.clone(), admin, msg).unwrap();
+        let res = query(deps.as_ref(), env, QueryMsg::TransactionInfo { id: "2".to_string() }).unwrap();
+        assert_eq!(Uint128::from(19u128), from_binary::<TransactionInfoResponse>(&res).unwrap().tranches[0].received);
+    }
 }
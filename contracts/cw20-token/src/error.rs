@@ -28,4 +28,122 @@ pub enum ContractError {
     TransactionDoesNotExist { id: String },
     #[error("Exchange rate does not exist")]
     ExchangeRateDoesNotExist { id: String },
+    #[error("Invalid exchange rate")]
+    InvalidExchangeRate { reason: String },
+    #[error("Unsupported IBC channel version")]
+    InvalidIbcVersion { version: String },
+    #[error("Only supports unordered channel")]
+    OnlyUnorderedChannel {},
+    #[error("Channel does not exist")]
+    ChannelDoesNotExist { id: String },
+    #[error("Cannot close the channel")]
+    CannotCloseChannel {},
+    #[error("Only accepts tokens that originate on this chain")]
+    NoForeignTokens { denom: String },
+    #[error("Not enough funds escrowed on the channel")]
+    InsufficientChannelFunds { channel: String, denom: String },
+    #[error("Unsupported packet version")]
+    UnsupportedPacketVersion { version: String },
+    #[error("Bank does not settle packets from the channel")]
+    UntrustedChannel { channel: String, port: String },
+    #[error("Oracle is not configured for the exchange rate")]
+    OracleDoesNotExist { id: String },
+    #[error("Exchange rate is managed by oracle feeders")]
+    ExchangeRateManagedByOracle { id: String },
+    #[error("Invalid oracle configuration")]
+    InvalidOracleConfig { reason: String },
+    #[error("Price submission is stale")]
+    StalePrice { id: String },
+    #[error("Price deviates too much from the other feeders")]
+    OutlierPrice { rate: u64, median: u64 },
+    #[error("Circuit breaker is not configured for the exchange rate")]
+    CircuitBreakerDoesNotExist { id: String },
+    #[error("Invalid circuit breaker configuration")]
+    InvalidCircuitBreaker { reason: String },
+    #[error("Settlements are paused until the new exchange rate is confirmed")]
+    CircuitBreakerTripped { id: String },
+    #[error("Contract is paused")]
+    ContractPaused {},
+    #[error("Token {denom} is paused")]
+    DenomPaused { denom: String },
+    #[error("Bank {id} is suspended")]
+    BankSuspended { id: String },
+    #[error("Bank {id} is closed")]
+    BankClosed { id: String },
+    #[error("Bank {id} still has pending transactions or reserves")]
+    BankNotSettled { id: String },
+    #[error("Bank collateral is not configured")]
+    CollateralNotConfigured {},
+    #[error("Transaction {id} is not past its settlement deadline")]
+    SettlementNotOverdue { id: String },
+    #[error("Exposure of {exposure} would exceed the collateral cap of {cap}")]
+    ExposureCapExceeded { exposure: Uint128, cap: Uint128 },
+    #[error("Bank {id} is paused")]
+    BankPaused { id: String },
+    #[error("Exchange rate {id} is paused")]
+    ExchangeRatePaused { id: String },
+    #[error("Account {account} is frozen")]
+    AccountFrozen { account: String },
+    #[error("Account {account} is not frozen")]
+    AccountNotFrozen { account: String },
+    #[error("Transfer exceeds the {limit} limit, {remaining} remaining")]
+    TransferLimitExceeded { limit: String, remaining: Uint128 },
+    #[error("Account {account} has no KYC tier required for {denom}")]
+    NotAllowlisted { account: String, denom: String },
+    #[error("Transaction rejected by compliance checker {checker}: {reason}")]
+    ComplianceRejected { checker: String, reason: String },
+    #[error("Transaction requires manual review by compliance checker {checker}: {reason}")]
+    ManualReviewRequired { checker: String, reason: String },
+    #[error("Transaction {id} cannot be processed in its current status")]
+    InvalidTransactionStatus { id: String },
+    #[error("Transaction {id} was acknowledged by the bank and cannot be cancelled")]
+    CancellationNotAllowed { id: String },
+    #[error("Settlement of {amount} exceeds the outstanding {outstanding}")]
+    SettlementExceedsOutstanding { amount: Uint128, outstanding: Uint128 },
+    #[error("Batch must not be empty")]
+    EmptyBatch {},
+    #[error("Withdrawal would leave the bank below its outstanding obligations of {outstanding}")]
+    BelowOutstandingObligations { outstanding: Uint128 },
+    #[error("Fee of {fee_bps} bps is over 100%")]
+    InvalidFee { fee_bps: u64 },
+    #[error("No active bank can settle {amount} {denom}")]
+    NoRouteAvailable { denom: String, amount: Uint128 },
+    #[error("Invalid order: {reason}")]
+    InvalidOrder { reason: String },
+    #[error("Order {id} does not exist")]
+    OrderDoesNotExist { id: u64 },
+    #[error("Pool {id} already exists")]
+    PoolAlreadyExists { id: String },
+    #[error("Pool {id} does not exist")]
+    PoolDoesNotExist { id: String },
+    #[error("Pool {id} does not have enough liquidity")]
+    InsufficientLiquidity { id: String },
+    #[error("Received {received} is below the minimum of {min}")]
+    SlippageExceeded { received: Uint128, min: Uint128 },
+    #[error("Invalid swap: {reason}")]
+    InvalidSwap { reason: String },
+    #[error("Swap {id} does not exist")]
+    SwapDoesNotExist { id: u64 },
+    #[error("Swap {id} is no longer open")]
+    SwapNotOpen { id: u64 },
+    #[error("Swap {id} has expired")]
+    SwapExpired { id: u64 },
+    #[error("Invalid hash lock: {reason}")]
+    InvalidHashLock { reason: String },
+    #[error("Hash lock {id} does not exist")]
+    HashLockDoesNotExist { id: u64 },
+    #[error("Hash lock {id} is already claimed or refunded")]
+    HashLockNotLocked { id: u64 },
+    #[error("Hash lock {id} has expired")]
+    HashLockExpired { id: u64 },
+    #[error("Hash lock {id} cannot be refunded before its timelock")]
+    HashLockNotExpired { id: u64 },
+    #[error("Invalid standing order: {reason}")]
+    InvalidStandingOrder { reason: String },
+    #[error("Standing order {id} does not exist")]
+    StandingOrderDoesNotExist { id: u64 },
+    #[error("Standing order {id} is no longer active")]
+    StandingOrderNotActive { id: u64 },
+    #[error("Amount must not be zero")]
+    ZeroAmount {},
 }
@@ -1,7 +1,13 @@
-use cosmwasm_std::{DepsMut, Uint128};
+use cosmwasm_std::{Addr, Deps, DepsMut, Order, StdResult, Storage, Timestamp, Uint128};
 use cw20::Cw20Coin;
 
-use crate::{ContractError, state::{BalanceInfo, BALANCES}};
+use crate::{ContractError, pools::routed_pool, msg::{AllowanceResponse, ComplianceDecision, ComplianceQueryMsg, ComplianceStage, TransactionMsg}, state::{Aggregation, BalanceInfo, BankStats, BankStatus, RouteDecision, RouteQuote, ClearingCycle, ExchangeRateInfo, NetPosition, NetTransfer, PauseScope, Position, Role, VolumeEntry, BALANCES, BANKS, BANK_CHECKERS, BANK_COLLATERAL, BANK_FEES, BANK_RESERVES, BANK_OPERATORS, BANK_OUTSTANDING, BANK_STATS, BANK_STATUSES, BANK_VOLUMES, CLEARING_CYCLE, COLLATERAL_CONFIG, EXCHANGE_RATES, POSITIONS, CIRCUIT_BREAKERS, CONFIG, FROZEN_ACCOUNTS, KYC_REQUIRED, KYC_TIERS, PAUSES, ROLES, TIER_LIMITS, TRANSACTION_COUNT, TRANSFER_VOLUMES}};
+
+pub const DAY: u64 = 86_400;
+pub const MONTH: u64 = 30 * DAY;
+// settings for pagination
+pub const MAX_LIMIT: u32 = 30;
+pub const DEFAULT_LIMIT: u32 = 10;
 
 pub fn create_accounts(
     deps: &mut DepsMut,
@@ -12,6 +18,7 @@ pub fn create_accounts(
     let mut total_supply = Uint128::zero();
     for account in accounts {
         let address = deps.api.addr_validate(&account.address)?;
+        assert_allowlisted(deps.storage, &address, &denom)?;
         let balance = BalanceInfo {
             amount: account.amount,
             denom: denom.clone(),
@@ -21,4 +28,423 @@ pub fn create_accounts(
     }
 
     Ok(total_supply)
-}
\ No newline at end of file
+}
+
+// Calculate amount in denom_to due to exchange rate
+pub fn exchange(amount: Uint128, exchange_rate: &ExchangeRateInfo) -> Uint128 {
+    Uint128::from(amount.u128() * exchange_rate.rate as u128 / 10_u128.pow(exchange_rate.precision))
+}
+
+pub fn assert_admin(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if *sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+pub fn assert_admin_or_guardian(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if *sender != config.admin && Some(sender) != config.guardian.as_ref() {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+// The admin holds every role
+pub fn assert_role(deps: Deps, sender: &Addr, role: Role) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if *sender != config.admin && !ROLES.has(deps.storage, (role.key(), sender)) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+pub fn assert_not_frozen(storage: &dyn Storage, address: &Addr, denom: &str) -> Result<(), ContractError> {
+    if let Some(frozen) = FROZEN_ACCOUNTS.may_load(storage, address)? {
+        if frozen.is_frozen(denom) {
+            return Err(ContractError::AccountFrozen { account: address.to_string() });
+        }
+    }
+    Ok(())
+}
+
+pub fn update_bank_stats(storage: &mut dyn Storage, bank_id: &str, action: impl FnOnce(&mut BankStats)) -> StdResult<()> {
+    let mut stats = BANK_STATS.may_load(storage, bank_id.to_string())?.unwrap_or_default();
+    action(&mut stats);
+    BANK_STATS.save(storage, bank_id.to_string(), &stats)
+}
+
+// Counts a transaction the bank took on and its volume
+pub fn record_accepted(storage: &mut dyn Storage, bank_id: &str, denom: &str, amount: Uint128) -> StdResult<()> {
+    update_bank_stats(storage, bank_id, |stats| stats.accepted += 1)?;
+    BANK_VOLUMES.update(storage, (bank_id.to_string(), denom.to_string()), |volume| -> StdResult<_> {
+        Ok(volume.unwrap_or_default() + amount)
+    })?;
+    Ok(())
+}
+
+// Settlement amount left after the bank fee
+pub fn after_fee(storage: &dyn Storage, bank_id: &str, amount: Uint128) -> StdResult<Uint128> {
+    let fee_bps = BANK_FEES.may_load(storage, bank_id.to_string())?.unwrap_or_default();
+    Ok(amount.multiply_ratio(10_000 - fee_bps, 10_000u128))
+}
+
+// Quotes every active bank able to settle the way SendToRecipient would, out of its denom_to
+// reserves or its pool, and picks the one paying the most, ties go to the lower bank id
+pub fn route_transaction(storage: &dyn Storage, from: &Addr, to: &Addr, amount: Uint128) -> Result<RouteDecision, ContractError> {
+    let denom_from = match BALANCES.may_load(storage, from)? {
+        Some(balance) => balance.denom,
+        None => return Err(ContractError::AccountDoesNotExist { account: from.to_string() }),
+    };
+    let denom_to = match BALANCES.may_load(storage, to)? {
+        Some(balance) => balance.denom,
+        None => return Err(ContractError::AccountDoesNotExist { account: to.to_string() }),
+    };
+    let id = denom_from.clone() + denom_to.as_str();
+    let rate = match EXCHANGE_RATES.may_load(storage, id.clone())? {
+        Some(rate) => rate,
+        None => return Err(ContractError::ExchangeRateDoesNotExist { id }),
+    };
+    assert_rate_confirmed(storage, &denom_from, &denom_to)?;
+
+    let bank_ids = BANKS.keys(storage, None, None, Order::Ascending).collect::<StdResult<Vec<_>>>()?;
+    let mut quotes = vec![];
+    for bank_id in bank_ids {
+        let eligible = assert_bank_active(storage, &bank_id).is_ok()
+            && assert_not_paused(storage, &settlement_scopes(&bank_id, &denom_from, &denom_to)).is_ok()
+            && assert_within_exposure(storage, &bank_id, &denom_from, amount).is_ok();
+        if !eligible {
+            continue;
+        }
+        let expected = exchange(amount, &rate);
+        let (received, reserves) = match routed_pool(storage, &bank_id, &denom_from, &denom_to)? {
+            Some((pool, max_slippage_bps)) => {
+                let quote = pool.swap_out(&denom_from, amount);
+                if quote < expected - expected.multiply_ratio(max_slippage_bps, 10_000u128) {
+                    continue;
+                }
+                (after_fee(storage, &bank_id, quote)?, pool.reserve(&denom_to))
+            },
+            None => {
+                let received = after_fee(storage, &bank_id, expected)?;
+                let reserves = BANK_RESERVES.may_load(storage, (bank_id.clone(), denom_to.clone()))?.unwrap_or_default();
+                if reserves < received {
+                    continue;
+                }
+                (received, reserves)
+            },
+        };
+        let fee_bps = BANK_FEES.may_load(storage, bank_id.clone())?.unwrap_or_default();
+        quotes.push(RouteQuote { bank_id, fee_bps, received, reserves });
+    }
+    // stable sort keeps the bank id order among equal quotes
+    quotes.sort_by_key(|quote| std::cmp::Reverse(quote.received));
+    let mut quotes = quotes.into_iter();
+    match quotes.next() {
+        Some(chosen) => Ok(RouteDecision { chosen, alternatives: quotes.collect() }),
+        None => Err(ContractError::NoRouteAvailable { denom: denom_to, amount }),
+    }
+}
+
+// Limits of the account tier minus the volume sent in the rolling windows
+pub fn remaining_allowance(storage: &dyn Storage, address: &Addr, denom: &str, now: Timestamp) -> StdResult<AllowanceResponse> {
+    let tier = KYC_TIERS.may_load(storage, address)?.unwrap_or_default();
+    let limits = TIER_LIMITS.may_load(storage, (tier, denom.to_string()))?;
+    let volumes = TRANSFER_VOLUMES.may_load(storage, (address, denom.to_string()))?.unwrap_or_default();
+    let sent = |window: u64| -> Uint128 {
+        volumes.iter()
+            .filter(|entry| entry.timestamp.plus_seconds(window) > now)
+            .map(|entry| entry.amount)
+            .sum()
+    };
+    let remaining = |limit: Option<Uint128>, window: u64| limit.map(|limit| limit.saturating_sub(sent(window)));
+
+    Ok(AllowanceResponse {
+        address: address.clone(),
+        denom: denom.to_string(),
+        tier,
+        allowed: assert_allowlisted(storage, address, denom).is_ok(),
+        max_transfer: limits.as_ref().and_then(|limits| limits.max_transfer),
+        daily_remaining: remaining(limits.as_ref().and_then(|limits| limits.daily_volume), DAY),
+        monthly_remaining: remaining(limits.as_ref().and_then(|limits| limits.monthly_volume), MONTH),
+    })
+}
+
+// Tokens requiring KYC can only be held by accounts an attestor assigned a tier
+pub fn assert_allowlisted(storage: &dyn Storage, address: &Addr, denom: &str) -> Result<(), ContractError> {
+    if KYC_REQUIRED.has(storage, denom.to_string()) && !KYC_TIERS.has(storage, address) {
+        return Err(ContractError::NotAllowlisted { account: address.to_string(), denom: denom.to_string() });
+    }
+    Ok(())
+}
+
+// Checks the allowlist and the tier limits and records the transfer in the rolling windows,
+// every path moving tokens out of an account goes through here
+pub fn consume_allowance(storage: &mut dyn Storage, address: &Addr, denom: &str, amount: Uint128, now: Timestamp) -> Result<(), ContractError> {
+    assert_allowlisted(storage, address, denom)?;
+    let allowance = remaining_allowance(storage, address, denom, now)?;
+    let limits = [
+        ("single transfer", allowance.max_transfer),
+        ("daily", allowance.daily_remaining),
+        ("monthly", allowance.monthly_remaining),
+    ];
+    for (limit, remaining) in limits {
+        if let Some(remaining) = remaining {
+            if amount > remaining {
+                return Err(ContractError::TransferLimitExceeded { limit: limit.to_string(), remaining });
+            }
+        }
+    }
+
+    let key = (address, denom.to_string());
+    let mut volumes = TRANSFER_VOLUMES.may_load(storage, key.clone())?.unwrap_or_default();
+    volumes.retain(|entry| entry.timestamp.plus_seconds(MONTH) > now);
+    volumes.push(VolumeEntry { amount, timestamp: now });
+    TRANSFER_VOLUMES.save(storage, key, &volumes)?;
+    Ok(())
+}
+
+// The admin operates every bank
+pub fn assert_bank_operator(deps: Deps, sender: &Addr, bank_id: &str) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if *sender != config.admin && BANK_OPERATORS.may_load(deps.storage, bank_id.to_string())?.as_ref() != Some(sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+// New transactions need an active bank
+pub fn assert_bank_active(storage: &dyn Storage, bank_id: &str) -> Result<(), ContractError> {
+    match BANK_STATUSES.may_load(storage, bank_id.to_string())?.unwrap_or_default() {
+        BankStatus::Active => Ok(()),
+        BankStatus::Suspended => Err(ContractError::BankSuspended { id: bank_id.to_string() }),
+        BankStatus::Closed => Err(ContractError::BankClosed { id: bank_id.to_string() }),
+    }
+}
+
+// Pending transactions keep settling while the bank is suspended
+pub fn assert_bank_open(storage: &dyn Storage, bank_id: &str) -> Result<(), ContractError> {
+    if BANK_STATUSES.may_load(storage, bank_id.to_string())? == Some(BankStatus::Closed) {
+        return Err(ContractError::BankClosed { id: bank_id.to_string() });
+    }
+    Ok(())
+}
+
+// Outstanding obligations of the bank valued in the collateral denom at the current rates
+pub fn bank_exposure(storage: &dyn Storage, bank_id: &str, denom: &str) -> Result<Uint128, ContractError> {
+    let outstanding = BANK_OUTSTANDING
+        .prefix(bank_id.to_string())
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    outstanding.into_iter().try_fold(Uint128::zero(), |exposure, (denom_from, amount)| {
+        Ok(exposure + to_collateral(storage, &denom_from, denom, amount)?)
+    })
+}
+
+pub fn to_collateral(storage: &dyn Storage, denom_from: &str, denom: &str, amount: Uint128) -> Result<Uint128, ContractError> {
+    if denom_from == denom || amount.is_zero() {
+        return Ok(amount);
+    }
+    let id = denom_from.to_owned() + denom;
+    match EXCHANGE_RATES.may_load(storage, id.clone())? {
+        Some(rate) => Ok(exchange(amount, &rate)),
+        None => Err(ContractError::ExchangeRateDoesNotExist { id }),
+    }
+}
+
+// Rejects new obligations that take the bank over its collateral cap
+pub fn assert_within_exposure(storage: &dyn Storage, bank_id: &str, denom_from: &str, amount: Uint128) -> Result<(), ContractError> {
+    let config = match COLLATERAL_CONFIG.may_load(storage)? {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+    let collateral = BANK_COLLATERAL.may_load(storage, bank_id.to_string())?.unwrap_or_default();
+    let cap = collateral * Uint128::from(config.exposure_multiple);
+    let exposure = bank_exposure(storage, bank_id, &config.denom)? + to_collateral(storage, denom_from, &config.denom, amount)?;
+    if exposure > cap {
+        return Err(ContractError::ExposureCapExceeded { exposure, cap });
+    }
+    Ok(())
+}
+
+// Asks every checker of the bank, rejections fail and the first review request
+// is returned as (checker, reason)
+pub fn check_compliance(deps: Deps, transaction_id: &str, transaction: &TransactionMsg, stage: ComplianceStage) -> Result<Option<(String, String)>, ContractError> {
+    let mut review = None;
+    let checkers = BANK_CHECKERS.may_load(deps.storage, transaction.bank_id.clone().unwrap_or_default())?.unwrap_or_default();
+    for checker in checkers {
+        let msg = ComplianceQueryMsg::CheckTransaction {
+            transaction_id: transaction_id.to_string(),
+            transaction: transaction.clone(),
+            stage: stage.clone(),
+        };
+        match deps.querier.query_wasm_smart(&checker, &msg)? {
+            ComplianceDecision::Approve => {},
+            ComplianceDecision::Reject { reason } => {
+                return Err(ContractError::ComplianceRejected { checker: checker.to_string(), reason });
+            },
+            ComplianceDecision::Review { reason } => {
+                review = review.or(Some((checker.to_string(), reason)));
+            },
+        }
+    }
+    Ok(review)
+}
+
+// Sequential ids, the counter is saved together with the transaction
+pub fn next_transaction_id(storage: &dyn Storage) -> StdResult<(u64, String)> {
+    let index = TRANSACTION_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    Ok((index, index.to_string()))
+}
+
+pub fn current_cycle(storage: &dyn Storage, now: Timestamp) -> StdResult<ClearingCycle> {
+    Ok(CLEARING_CYCLE.may_load(storage)?.unwrap_or(ClearingCycle { id: 1, opened_at: now }))
+}
+
+// The bank received amount_from from the sender and paid amount_to to the recipient
+pub fn record_obligation(
+    storage: &mut dyn Storage,
+    now: Timestamp,
+    bank_id: &str,
+    denom_from: &str,
+    amount_from: Uint128,
+    denom_to: &str,
+    amount_to: Uint128,
+) -> StdResult<()> {
+    let cycle = current_cycle(storage, now)?;
+    CLEARING_CYCLE.save(storage, &cycle)?;
+    POSITIONS.update(storage, (cycle.id, bank_id.to_string(), denom_from.to_string()), |position| -> StdResult<_> {
+        let position = position.unwrap_or_default();
+        Ok(Position { credit: position.credit + amount_from, ..position })
+    })?;
+    POSITIONS.update(storage, (cycle.id, bank_id.to_string(), denom_to.to_string()), |position| -> StdResult<_> {
+        let position = position.unwrap_or_default();
+        Ok(Position { debit: position.debit + amount_to, ..position })
+    })?;
+    Ok(())
+}
+
+pub fn net_positions(storage: &dyn Storage, cycle: u64) -> StdResult<Vec<NetPosition>> {
+    POSITIONS
+        .sub_prefix(cycle)
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|((bank_id, denom), position)| NetPosition {
+            bank_id,
+            denom,
+            credit: position.credit,
+            debit: position.debit,
+        }))
+        .collect()
+}
+
+// Matches the banks with a surplus to the banks with a shortfall, denom by denom
+pub fn net_transfers(positions: &[NetPosition]) -> Vec<NetTransfer> {
+    let mut denoms: Vec<&String> = positions.iter().map(|position| &position.denom).collect();
+    denoms.sort();
+    denoms.dedup();
+    let mut transfers = vec![];
+    for denom in denoms {
+        let in_denom = positions.iter().filter(|position| &position.denom == denom);
+        let mut payers: Vec<(&str, Uint128)> = in_denom.clone()
+            .filter(|position| !position.payable().is_zero())
+            .map(|position| (position.bank_id.as_str(), position.payable()))
+            .collect();
+        let mut receivers: Vec<(&str, Uint128)> = in_denom
+            .filter(|position| !position.receivable().is_zero())
+            .map(|position| (position.bank_id.as_str(), position.receivable()))
+            .collect();
+        let (mut payer, mut receiver) = (0, 0);
+        while payer < payers.len() && receiver < receivers.len() {
+            let amount = payers[payer].1.min(receivers[receiver].1);
+            transfers.push(NetTransfer {
+                denom: denom.clone(),
+                from_bank: payers[payer].0.to_string(),
+                to_bank: receivers[receiver].0.to_string(),
+                amount,
+            });
+            payers[payer].1 -= amount;
+            receivers[receiver].1 -= amount;
+            if payers[payer].1.is_zero() {
+                payer += 1;
+            }
+            if receivers[receiver].1.is_zero() {
+                receiver += 1;
+            }
+        }
+    }
+    transfers
+}
+
+// Fails with the first paused scope, the global pause always applies
+pub fn assert_not_paused(storage: &dyn Storage, scopes: &[PauseScope]) -> Result<(), ContractError> {
+    for scope in [PauseScope::Global].iter().chain(scopes) {
+        if !PAUSES.has(storage, scope.key()) {
+            continue;
+        }
+        return Err(match scope.clone() {
+            PauseScope::Global => ContractError::ContractPaused {},
+            PauseScope::Denom { denom } => ContractError::DenomPaused { denom },
+            PauseScope::Bank { id } => ContractError::BankPaused { id },
+            PauseScope::ExchangeRate { id } => ContractError::ExchangeRatePaused { id },
+        });
+    }
+    Ok(())
+}
+
+// Everything a settlement touches: the bank, both tokens and the pair
+pub fn settlement_scopes(bank_id: &str, denom_from: &str, denom_to: &str) -> Vec<PauseScope> {
+    let mut scopes = vec![
+        PauseScope::Bank { id: bank_id.to_string() },
+        PauseScope::Denom { denom: denom_from.to_string() },
+        PauseScope::Denom { denom: denom_to.to_string() },
+    ];
+    scopes.extend(PauseScope::exchange_rates(denom_from, denom_to));
+    scopes
+}
+
+pub fn median(rates: &[u64]) -> u64 {
+    let mut sorted = rates.to_vec();
+    sorted.sort_unstable();
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        ((sorted[middle - 1] as u128 + sorted[middle] as u128) / 2) as u64
+    } else {
+        sorted[middle]
+    }
+}
+
+// Aggregate the feeders' prices, rates must not be empty
+pub fn aggregate(rates: &[u64], aggregation: &Aggregation) -> u64 {
+    match aggregation {
+        Aggregation::Median => median(rates),
+        Aggregation::TrimmedMean { trim } => {
+            let trim = *trim as usize;
+            // Not enough prices to trim, fall back to median
+            if rates.len() <= trim * 2 {
+                return median(rates);
+            }
+            let mut sorted = rates.to_vec();
+            sorted.sort_unstable();
+            let kept = &sorted[trim..sorted.len() - trim];
+            (kept.iter().map(|rate| *rate as u128).sum::<u128>() / kept.len() as u128) as u64
+        }
+    }
+}
+
+// Distance between two rates in basis points of the reference rate
+pub fn deviation_bps(reference: u128, rate: u128) -> u128 {
+    rate.abs_diff(reference) * 10_000 / reference
+}
+
+// Fails while a circuit breaker of the pair, in any direction, is tripped
+pub fn assert_rate_confirmed(storage: &dyn Storage, denom_from: &str, denom_to: &str) -> Result<(), ContractError> {
+    for id in [denom_from.to_owned() + denom_to, denom_to.to_owned() + denom_from] {
+        if let Some(breaker) = CIRCUIT_BREAKERS.may_load(storage, id.clone())? {
+            if breaker.breach.is_some() {
+                return Err(ContractError::CircuitBreakerTripped { id });
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,318 @@
+use cosmwasm_std::{
+    to_binary, Binary, Deps, DepsMut, Env, Event, MessageInfo, Order as StorageOrder, Response, StdResult, Storage,
+    Timestamp, Uint128,
+};
+use cw_storage_plus::Bound;
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+use crate::helpers::{assert_not_frozen, assert_not_paused, consume_allowance, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::ibc::credit_account;
+use crate::msg::HashLocksResponse;
+use crate::state::{
+    BalanceInfo, HashLock, HashLockStatus, PauseScope, BALANCES, HASH_LOCKS, HASH_LOCKS_BY_HASH, HASH_LOCK_COUNT,
+    USER_HASH_LOCKS,
+};
+
+// Lowercase hex of a 32 bytes sha256 hash
+fn parse_hashlock(hashlock: &str) -> Result<String, ContractError> {
+    match hex::decode(hashlock) {
+        Ok(hash) if hash.len() == 32 => Ok(hex::encode(hash)),
+        _ => Err(ContractError::InvalidHashLock { reason: "hashlock must be a hex encoded sha256 hash".to_string() }),
+    }
+}
+
+fn load_lock(storage: &dyn Storage, id: u64) -> Result<HashLock, ContractError> {
+    match HASH_LOCKS.may_load(storage, id)? {
+        Some(lock) => Ok(lock),
+        None => Err(ContractError::HashLockDoesNotExist { id }),
+    }
+}
+
+fn lock_event(ty: &str, lock: &HashLock) -> Event {
+    Event::new(ty)
+        .add_attribute("lock_id", lock.id.to_string())
+        .add_attribute("hashlock", lock.hashlock.clone())
+        .add_attribute("sender", lock.sender.to_string())
+        .add_attribute("recipient", lock.recipient.to_string())
+        .add_attribute("amount", format!("{}{}", lock.amount, lock.denom))
+        .add_attribute("timelock", lock.timelock.seconds().to_string())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_lock_with_hash(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+    denom: String,
+    hashlock: String,
+    timelock: Timestamp,
+) -> Result<Response, ContractError> {
+    let recipient = deps.api.addr_validate(&recipient)?;
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    let hashlock = parse_hashlock(&hashlock)?;
+    if timelock <= env.block.time {
+        return Err(ContractError::InvalidHashLock { reason: "timelock has already passed".to_string() });
+    }
+    let balance = match BALANCES.may_load(deps.storage, &info.sender)? {
+        Some(balance) => balance,
+        None => return Err(ContractError::AccountDoesNotExist { account: info.sender.to_string() }),
+    };
+    if balance.denom != denom {
+        return Err(ContractError::TokenAlreadyAssigned { denom: balance.denom });
+    }
+    if balance.amount < amount {
+        return Err(ContractError::NotEnoughBalance { required: amount, available: balance.amount });
+    }
+    assert_not_paused(deps.storage, &[PauseScope::Denom { denom: denom.clone() }])?;
+    assert_not_frozen(deps.storage, &info.sender, &denom)?;
+    assert_not_frozen(deps.storage, &recipient, &denom)?;
+    // fail now rather than on claim if the recipient holds another token
+    credit_account(deps.branch(), &recipient, &denom, Uint128::zero())?;
+    consume_allowance(deps.storage, &info.sender, &denom, amount, env.block.time)?;
+
+    BALANCES.save(deps.storage, &info.sender, &BalanceInfo { amount: balance.amount - amount, denom: denom.clone() })?;
+    let id = HASH_LOCK_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    HASH_LOCK_COUNT.save(deps.storage, &id)?;
+    let lock = HashLock {
+        id,
+        sender: info.sender,
+        recipient,
+        amount,
+        denom,
+        hashlock,
+        timelock,
+        status: HashLockStatus::Locked,
+        preimage: None,
+        created_at: env.block.time,
+    };
+    HASH_LOCKS.save(deps.storage, id, &lock)?;
+    HASH_LOCKS_BY_HASH.save(deps.storage, (&lock.hashlock, id), &())?;
+    USER_HASH_LOCKS.save(deps.storage, (&lock.sender, id), &())?;
+    USER_HASH_LOCKS.save(deps.storage, (&lock.recipient, id), &())?;
+
+    Ok(Response::new()
+        .add_event(lock_event("htlc_lock", &lock))
+        .add_attribute("action", "lock_with_hash")
+        .add_attribute("lock_id", id.to_string()))
+}
+
+pub fn execute_claim(mut deps: DepsMut, env: Env, id: u64, preimage: String) -> Result<Response, ContractError> {
+    let secret = match hex::decode(&preimage) {
+        Ok(secret) => secret,
+        Err(_) => return Err(ContractError::InvalidHashLock { reason: "preimage must be hex encoded".to_string() }),
+    };
+    let mut lock = load_lock(deps.storage, id)?;
+    if hex::encode(Sha256::digest(&secret)) != lock.hashlock {
+        return Err(ContractError::InvalidHashLock { reason: "preimage does not match the hashlock".to_string() });
+    }
+    if lock.status != HashLockStatus::Locked {
+        return Err(ContractError::HashLockNotLocked { id });
+    }
+    if env.block.time >= lock.timelock {
+        return Err(ContractError::HashLockExpired { id });
+    }
+    assert_not_paused(deps.storage, &[PauseScope::Denom { denom: lock.denom.clone() }])?;
+    assert_not_frozen(deps.storage, &lock.recipient, &lock.denom)?;
+
+    credit_account(deps.branch(), &lock.recipient, &lock.denom, lock.amount)?;
+    lock.status = HashLockStatus::Claimed;
+    lock.preimage = Some(hex::encode(secret));
+    HASH_LOCKS.save(deps.storage, id, &lock)?;
+
+    // the counterparty watcher reads the preimage to claim on the other chain
+    Ok(Response::new()
+        .add_event(lock_event("htlc_claim", &lock).add_attribute("preimage", lock.preimage.clone().unwrap_or_default()))
+        .add_attribute("action", "claim")
+        .add_attribute("lock_id", id.to_string()))
+}
+
+pub fn execute_refund(mut deps: DepsMut, env: Env, id: u64) -> Result<Response, ContractError> {
+    let mut lock = load_lock(deps.storage, id)?;
+    if lock.status != HashLockStatus::Locked {
+        return Err(ContractError::HashLockNotLocked { id });
+    }
+    if env.block.time < lock.timelock {
+        return Err(ContractError::HashLockNotExpired { id });
+    }
+    assert_not_paused(deps.storage, &[PauseScope::Denom { denom: lock.denom.clone() }])?;
+    assert_not_frozen(deps.storage, &lock.sender, &lock.denom)?;
+
+    credit_account(deps.branch(), &lock.sender, &lock.denom, lock.amount)?;
+    lock.status = HashLockStatus::Refunded;
+    HASH_LOCKS.save(deps.storage, id, &lock)?;
+
+    Ok(Response::new()
+        .add_event(lock_event("htlc_refund", &lock))
+        .add_attribute("action", "refund")
+        .add_attribute("lock_id", id.to_string()))
+}
+
+pub fn query_hash_lock(deps: Deps, id: u64) -> Result<Binary, ContractError> {
+    Ok(to_binary(&load_lock(deps.storage, id)?)?)
+}
+
+pub fn query_hash_locks_by_hash(deps: Deps, hashlock: String, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let hashlock = parse_hashlock(&hashlock)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let locks = HASH_LOCKS_BY_HASH
+        .prefix(&hashlock)
+        .keys(deps.storage, start, None, StorageOrder::Ascending)
+        .take(limit)
+        .map(|id| HASH_LOCKS.load(deps.storage, id?))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&HashLocksResponse { locks })?)
+}
+
+pub fn query_hash_locks(deps: Deps, address: String, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let locks = USER_HASH_LOCKS
+        .prefix(&address)
+        .keys(deps.storage, start, None, StorageOrder::Ascending)
+        .take(limit)
+        .map(|id| HASH_LOCKS.load(deps.storage, id?))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&HashLocksResponse { locks })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, query};
+    use crate::msg::{ExecuteMsg, QueryMsg};
+    use crate::testing::{balance, setup, MockDeps};
+
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::from_binary;
+
+    fn hashlock(preimage: &[u8]) -> String {
+        hex::encode(Sha256::digest(preimage))
+    }
+
+    fn lock(deps: &mut MockDeps, recipient: &str, denom: &str, hashlock: String, timelock: Timestamp) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::LockWithHash {
+            recipient: recipient.to_string(),
+            amount: Uint128::from(300u128),
+            denom: denom.to_string(),
+            hashlock,
+            timelock,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg)
+    }
+
+    fn claim(deps: &mut MockDeps, env: Env, id: u64, preimage: &[u8]) -> Result<Response, ContractError> {
+        execute(deps.as_mut(), env, mock_info("anyone", &[]), ExecuteMsg::Claim { id, preimage: hex::encode(preimage) })
+    }
+
+    #[test]
+    fn lock_and_claim() {
+        let mut deps = setup();
+        let timelock = mock_env().block.time.plus_seconds(3600);
+
+        let err = lock(&mut deps, "addr0002", "RUB", "zz".to_string(), timelock).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidHashLock { .. }));
+        let err = lock(&mut deps, "addr0002", "RUB", hashlock(b"secret"), mock_env().block.time).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidHashLock { .. }));
+        let err = lock(&mut deps, "addr0002", "USD", hashlock(b"secret"), timelock).unwrap_err();
+        assert!(matches!(err, ContractError::TokenAlreadyAssigned { .. }));
+        let err = lock(&mut deps, "addr0001", "RUB", hashlock(b"secret"), timelock).unwrap_err();
+        assert!(matches!(err, ContractError::TokenAlreadyAssigned { .. }));
+        let res = lock(&mut deps, "addr0002", "RUB", hashlock(b"secret").to_uppercase(), timelock).unwrap();
+        assert_eq!("htlc_lock", res.events[0].ty);
+        assert_eq!(Uint128::from(700u128), balance(&deps, "addr0000"));
+        // a hash seen on another lock does not block a new one, claims name the lock
+        lock(&mut deps, "addr0002", "RUB", hashlock(b"secret"), timelock).unwrap();
+
+        let err = claim(&mut deps, mock_env(), 1, b"guess").unwrap_err();
+        assert!(matches!(err, ContractError::InvalidHashLock { .. }));
+        let err = claim(&mut deps, mock_env(), 3, b"secret").unwrap_err();
+        assert!(matches!(err, ContractError::HashLockDoesNotExist { id: 3 }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), ExecuteMsg::Refund { id: 1 }).unwrap_err();
+        assert!(matches!(err, ContractError::HashLockNotExpired { id: 1 }));
+
+        // anyone can reveal the preimage, the watcher reads it from the event
+        let res = claim(&mut deps, mock_env(), 1, b"secret").unwrap();
+        assert!(res.events[0].attributes.iter().any(|attr| attr.key == "preimage" && attr.value == hex::encode(b"secret")));
+        assert_eq!(Uint128::from(300u128), balance(&deps, "addr0002"));
+        let msg = QueryMsg::HashLocksByHash { hashlock: hashlock(b"secret"), start_after: None, limit: None };
+        let locks = from_binary::<HashLocksResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().locks;
+        assert_eq!(
+            vec![(1, HashLockStatus::Claimed, Some(hex::encode(b"secret"))), (2, HashLockStatus::Locked, None)],
+            locks.into_iter().map(|lock| (lock.id, lock.status, lock.preimage)).collect::<Vec<_>>()
+        );
+        let err = claim(&mut deps, mock_env(), 1, b"secret").unwrap_err();
+        assert!(matches!(err, ContractError::HashLockNotLocked { id: 1 }));
+        claim(&mut deps, mock_env(), 2, b"secret").unwrap();
+        assert_eq!(Uint128::from(600u128), balance(&deps, "addr0002"));
+    }
+
+    #[test]
+    fn refund_after_timelock() {
+        let mut deps = setup();
+        let timelock = mock_env().block.time.plus_seconds(3600);
+        lock(&mut deps, "addr0002", "RUB", hashlock(b"secret"), timelock).unwrap();
+
+        let mut env = mock_env();
+        env.block.time = timelock;
+        let err = claim(&mut deps, env.clone(), 1, b"secret").unwrap_err();
+        assert!(matches!(err, ContractError::HashLockExpired { id: 1 }));
+
+        // refunds wait for the token to be unpaused, as claims do
+        let scope = PauseScope::Denom { denom: "RUB".to_string() };
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::Pause { scope: scope.clone() }).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), ExecuteMsg::Refund { id: 1 }).unwrap_err();
+        assert!(matches!(err, ContractError::DenomPaused { .. }));
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::Unpause { scope }).unwrap();
+        let res = execute(deps.as_mut(), env, mock_info("anyone", &[]), ExecuteMsg::Refund { id: 1 }).unwrap();
+        assert_eq!("htlc_refund", res.events[0].ty);
+        assert_eq!(Uint128::from(1000u128), balance(&deps, "addr0000"));
+
+        let msg = QueryMsg::HashLocks { address: "addr0002".to_string(), start_after: None, limit: None };
+        let locks = from_binary::<HashLocksResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().locks;
+        assert_eq!(vec![(1, HashLockStatus::Refunded)], locks.into_iter().map(|lock| (lock.id, lock.status)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rejected_claims_and_refunds_keep_the_lock() {
+        let mut deps = setup();
+        let timelock = mock_env().block.time.plus_seconds(3600);
+        lock(&mut deps, "addr0002", "RUB", hashlock(b"first"), timelock).unwrap();
+        lock(&mut deps, "addr0002", "RUB", hashlock(b"second"), timelock).unwrap();
+        let status = |deps: &MockDeps, id: u64| {
+            let msg = QueryMsg::HashLocks { address: "addr0000".to_string(), start_after: Some(id - 1), limit: Some(1) };
+            from_binary::<HashLocksResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().locks[0].status.clone()
+        };
+
+        // the preimage of another lock does not open this one
+        let err = claim(&mut deps, mock_env(), 1, b"second").unwrap_err();
+        assert!(matches!(err, ContractError::InvalidHashLock { .. }));
+        assert_eq!(HashLockStatus::Locked, status(&deps, 1));
+
+        // a paused token holds both the claim and, past the timelock, the refund
+        let scope = PauseScope::Denom { denom: "RUB".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), ExecuteMsg::Pause { scope: scope.clone() }).unwrap();
+        let err = claim(&mut deps, mock_env(), 1, b"first").unwrap_err();
+        assert!(matches!(err, ContractError::DenomPaused { .. }));
+        let mut env = mock_env();
+        env.block.time = timelock;
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), ExecuteMsg::Refund { id: 1 }).unwrap_err();
+        assert!(matches!(err, ContractError::DenomPaused { .. }));
+        assert_eq!(HashLockStatus::Locked, status(&deps, 1));
+        assert_eq!(Uint128::from(400u128), balance(&deps, "addr0000"));
+
+        // once unpaused the preimage comes too late and the sender gets the lock back
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::Unpause { scope }).unwrap();
+        let err = claim(&mut deps, env.clone(), 1, b"first").unwrap_err();
+        assert!(matches!(err, ContractError::HashLockExpired { id: 1 }));
+        execute(deps.as_mut(), env, mock_info("addr0000", &[]), ExecuteMsg::Refund { id: 1 }).unwrap();
+        assert_eq!(Uint128::from(700u128), balance(&deps, "addr0000"));
+        assert_eq!((HashLockStatus::Refunded, HashLockStatus::Locked), (status(&deps, 1), status(&deps, 2)));
+    }
+}
@@ -0,0 +1,582 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    from_binary, to_binary, Addr, Binary, Deps, DepsMut, Env, Ibc3ChannelOpenResponse,
+    IbcBasicResponse, IbcChannel, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg,
+    IbcChannelOpenResponse, IbcOrder, IbcPacket, IbcPacketAckMsg, IbcPacketReceiveMsg,
+    IbcPacketTimeoutMsg, IbcReceiveResponse, StdResult, Uint128,
+};
+use cosmwasm_schema::cw_serde;
+
+use crate::error::ContractError;
+use crate::helpers::{assert_allowlisted, assert_not_frozen, assert_not_paused};
+use crate::ibc_settlement::{self, TRADING_VERSION, TRADING_VERSIONS};
+use crate::state::{
+    BalanceInfo, ChannelInfo, ChannelState, PauseScope, BALANCES, CHANNEL_INFO, CHANNEL_STATE, TOKENS,
+};
+
+pub const ICS20_VERSION: &str = "ics20-1";
+pub const ICS20_ORDERING: IbcOrder = IbcOrder::Unordered;
+
+// ICS-20 transfer packet, the receiver is an address on the destination chain
+#[cw_serde]
+pub struct Ics20Packet {
+    pub amount: Uint128,
+    pub denom: String,
+    pub receiver: String,
+    pub sender: String,
+}
+
+impl Ics20Packet {
+    pub fn new(amount: Uint128, denom: &str, sender: &str, receiver: &str) -> Self {
+        Ics20Packet {
+            denom: denom.to_string(),
+            amount,
+            sender: sender.to_string(),
+            receiver: receiver.to_string(),
+        }
+    }
+}
+
+// ICS acknowledgement, JSON compatible with the one of the transfer module
+#[cw_serde]
+pub enum Ics20Ack {
+    Result(Binary),
+    Error(String),
+}
+
+// create a serialized success message
+fn ack_success() -> Binary {
+    let res = Ics20Ack::Result(b"1".into());
+    to_binary(&res).unwrap()
+}
+
+// create a serialized error message
+fn ack_fail(err: String) -> Binary {
+    let res = Ics20Ack::Error(err);
+    to_binary(&res).unwrap()
+}
+
+// Enforces ordering and negotiates the channel version
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_open(
+    _deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelOpenMsg,
+) -> Result<IbcChannelOpenResponse, ContractError> {
+    let version = negotiate_version(msg.channel(), msg.counterparty_version())?;
+    Ok(Some(Ibc3ChannelOpenResponse { version }))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_connect(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelConnectMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    // we need to check the counter party version in try and ack (sometimes here)
+    let channel = msg.channel();
+    if channel.order != ICS20_ORDERING {
+        return Err(ContractError::OnlyUnorderedChannel {});
+    }
+    if !is_supported_version(&channel.version) {
+        return Err(ContractError::InvalidIbcVersion { version: channel.version.clone() });
+    }
+    if let Some(version) = msg.counterparty_version() {
+        if version != channel.version {
+            return Err(ContractError::InvalidIbcVersion { version: version.to_string() });
+        }
+    }
+
+    let channel: IbcChannel = msg.into();
+    let info = ChannelInfo {
+        id: channel.endpoint.channel_id,
+        counterparty_endpoint: channel.counterparty_endpoint,
+        connection_id: channel.connection_id,
+        version: channel.version,
+    };
+    CHANNEL_INFO.save(deps.storage, info.id.clone(), &info)?;
+
+    Ok(IbcBasicResponse::default())
+}
+
+fn is_supported_version(version: &str) -> bool {
+    version == ICS20_VERSION || TRADING_VERSIONS.contains(&version)
+}
+
+// The initiator may leave the version empty, in which case we propose the latest
+// settlement protocol. On try we accept whatever supported version the counterparty proposed.
+fn negotiate_version(
+    channel: &IbcChannel,
+    counterparty_version: Option<&str>,
+) -> Result<String, ContractError> {
+    if channel.order != ICS20_ORDERING {
+        return Err(ContractError::OnlyUnorderedChannel {});
+    }
+    let version = match counterparty_version {
+        Some(version) => version,
+        None if channel.version.is_empty() => TRADING_VERSION,
+        None => channel.version.as_str(),
+    };
+    if !is_supported_version(version) {
+        return Err(ContractError::InvalidIbcVersion { version: version.to_string() });
+    }
+    Ok(version.to_string())
+}
+
+fn channel_version(deps: Deps, channel_id: &str) -> Result<String, ContractError> {
+    match CHANNEL_INFO.load(deps.storage, channel_id.to_string()) {
+        Ok(info) => Ok(info.version),
+        Err(_) => Err(ContractError::ChannelDoesNotExist { id: channel_id.to_string() }),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_close(
+    _deps: DepsMut,
+    _env: Env,
+    _channel: IbcChannelCloseMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    // Escrowed tokens would be stuck forever on a closed channel
+    Err(ContractError::CannotCloseChannel {})
+}
+
+// Errors are returned as failed acknowledgements so the sender gets refunded
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_receive(
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketReceiveMsg,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let packet = msg.packet;
+
+    if channel_version(deps.as_ref(), &packet.dest.channel_id)? != ICS20_VERSION {
+        return ibc_settlement::packet_receive(deps, env, &packet);
+    }
+
+    do_ibc_packet_receive(deps, &packet).or_else(|err| {
+        Ok(IbcReceiveResponse::new()
+            .set_ack(ack_fail(err.to_string()))
+            .add_attribute("action", "receive")
+            .add_attribute("success", "false")
+            .add_attribute("error", err.to_string()))
+    })
+}
+
+// Returns local denom if the denom is an encoded voucher from the expected endpoint
+// Otherwise, error
+fn parse_voucher_denom<'a>(
+    voucher_denom: &'a str,
+    packet: &IbcPacket,
+) -> Result<&'a str, ContractError> {
+    // the remote chain prefixes our tokens with its own port and channel
+    let prefix = format!("{}/{}/", packet.src.port_id, packet.src.channel_id);
+    match voucher_denom.strip_prefix(&prefix) {
+        Some(denom) if !denom.contains('/') => Ok(denom),
+        _ => Err(ContractError::NoForeignTokens { denom: voucher_denom.to_string() }),
+    }
+}
+
+// this does the work of ibc_packet_receive, we wrap it to turn errors into acknowledgements
+fn do_ibc_packet_receive(mut deps: DepsMut, packet: &IbcPacket) -> Result<IbcReceiveResponse, ContractError> {
+    let msg: Ics20Packet = from_binary(&packet.data)?;
+    let channel = packet.dest.channel_id.clone();
+
+    // If the token originated on the remote chain, it looks like "ucosm".
+    // If it originated on our chain, it looks like "remote_port/remote_channel/ucosm".
+    let denom = parse_voucher_denom(&msg.denom, packet)?;
+    let receiver = deps.api.addr_validate(&msg.receiver)?;
+    assert_not_paused(deps.storage, &[PauseScope::Denom { denom: denom.to_string() }])?;
+    assert_not_frozen(deps.storage, &receiver, denom)?;
+
+    // check everything credit_account does before touching the escrow, an error ack keeps the state changes
+    if !TOKENS.has(deps.storage, denom.to_string()) {
+        return Err(ContractError::TokenNotRegistered { denom: denom.to_string() });
+    }
+    assert_allowlisted(deps.storage, &receiver, denom)?;
+    if let Some(balance) = BALANCES.may_load(deps.storage, &receiver)? {
+        if balance.denom != denom {
+            return Err(ContractError::TokenAlreadyAssigned { denom: balance.denom });
+        }
+    }
+
+    reduce_channel_balance(deps.branch(), &channel, denom, msg.amount)?;
+    credit_account(deps, &receiver, denom, msg.amount)?;
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_success())
+        .add_attribute("action", "receive")
+        .add_attribute("sender", msg.sender)
+        .add_attribute("receiver", receiver)
+        .add_attribute("denom", denom)
+        .add_attribute("amount", msg.amount)
+        .add_attribute("success", "true");
+
+    Ok(res)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_ack(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcPacketAckMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    if channel_version(deps.as_ref(), &msg.original_packet.src.channel_id)? != ICS20_VERSION {
+        return ibc_settlement::packet_ack(deps, msg.original_packet, msg.acknowledgement);
+    }
+
+    let ics20msg: Ics20Ack = from_binary(&msg.acknowledgement.data)?;
+    match ics20msg {
+        Ics20Ack::Result(_) => on_packet_success(msg.original_packet),
+        Ics20Ack::Error(err) => on_packet_failure(deps, msg.original_packet, err),
+    }
+}
+
+// Same as a failed acknowledgement, the sender gets the escrow back
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_timeout(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcPacketTimeoutMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let packet = msg.packet;
+    if channel_version(deps.as_ref(), &packet.src.channel_id)? != ICS20_VERSION {
+        return ibc_settlement::packet_timeout(deps, packet);
+    }
+    on_packet_failure(deps, packet, "timeout".to_string())
+}
+
+// update the balance stored on this (channel, denom) index
+fn on_packet_success(packet: IbcPacket) -> Result<IbcBasicResponse, ContractError> {
+    let msg: Ics20Packet = from_binary(&packet.data)?;
+
+    Ok(IbcBasicResponse::new()
+        .add_attribute("action", "acknowledge")
+        .add_attribute("sender", msg.sender)
+        .add_attribute("receiver", msg.receiver)
+        .add_attribute("denom", msg.denom)
+        .add_attribute("amount", msg.amount)
+        .add_attribute("success", "true"))
+}
+
+// return the tokens to sender
+fn on_packet_failure(
+    mut deps: DepsMut,
+    packet: IbcPacket,
+    err: String,
+) -> Result<IbcBasicResponse, ContractError> {
+    let msg: Ics20Packet = from_binary(&packet.data)?;
+    let sender = deps.api.addr_validate(&msg.sender)?;
+
+    reduce_channel_balance(deps.branch(), &packet.src.channel_id, &msg.denom, msg.amount)?;
+    credit_account(deps, &sender, &msg.denom, msg.amount)?;
+
+    Ok(IbcBasicResponse::new()
+        .add_attribute("action", "acknowledge")
+        .add_attribute("sender", sender)
+        .add_attribute("receiver", msg.receiver)
+        .add_attribute("denom", msg.denom)
+        .add_attribute("amount", msg.amount)
+        .add_attribute("success", "false")
+        .add_attribute("error", err))
+}
+
+pub fn increase_channel_balance(
+    deps: DepsMut,
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    CHANNEL_STATE.update(deps.storage, (channel.to_string(), denom.to_string()), |orig| -> StdResult<_> {
+        let mut state = orig.unwrap_or_default();
+        state.outstanding += amount;
+        state.total_sent += amount;
+        Ok(state)
+    })?;
+    Ok(())
+}
+
+pub fn reduce_channel_balance(
+    deps: DepsMut,
+    channel: &str,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let key = (channel.to_string(), denom.to_string());
+    let mut state: ChannelState = CHANNEL_STATE.may_load(deps.storage, key.clone())?.unwrap_or_default();
+    if state.outstanding < amount {
+        return Err(ContractError::InsufficientChannelFunds {
+            channel: channel.to_string(),
+            denom: denom.to_string(),
+        });
+    }
+    state.outstanding -= amount;
+    CHANNEL_STATE.save(deps.storage, key, &state)?;
+    Ok(())
+}
+
+// Credit an account, creating it if the address holds no balance yet
+// accounts hold a single token, a payout in another one goes to a receiver account of that token
+pub fn credit_account(
+    deps: DepsMut,
+    address: &Addr,
+    denom: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if !TOKENS.has(deps.storage, denom.to_string()) {
+        return Err(ContractError::TokenNotRegistered { denom: denom.to_string() });
+    }
+    assert_allowlisted(deps.storage, address, denom)?;
+    let balance = match BALANCES.may_load(deps.storage, address)? {
+        Some(balance) if balance.denom != denom => {
+            return Err(ContractError::TokenAlreadyAssigned { denom: balance.denom });
+        }
+        Some(balance) => BalanceInfo { amount: balance.amount + amount, denom: balance.denom },
+        None => BalanceInfo { amount, denom: denom.to_string() },
+    };
+    BALANCES.save(deps.storage, address, &balance)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, instantiate, query, DEFAULT_TIMEOUT};
+    use crate::msg::{
+        BalanceResponse, ChannelInfoResponse, ExecuteMsg, InstantiateMsg, QueryMsg, TokenInfoMsg,
+        TransferMsg,
+    };
+
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_ibc_channel_connect_ack, mock_ibc_channel_open_init,
+        mock_ibc_channel_open_try, mock_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        CosmosMsg, IbcAcknowledgement, IbcEndpoint, IbcMsg, IbcTimeout, OwnedDeps, Timestamp,
+    };
+    use cw20::Cw20Coin;
+
+    const CONTRACT_PORT: &str = "wasm.cosmos2contract";
+    const REMOTE_PORT: &str = "transfer";
+    const CHANNEL: &str = "channel-0";
+    const REMOTE_CHANNEL: &str = "channel-7";
+    const CONNECTION: &str = "connection-2";
+
+    fn local_endpoint() -> IbcEndpoint {
+        IbcEndpoint { port_id: CONTRACT_PORT.to_string(), channel_id: CHANNEL.to_string() }
+    }
+
+    fn remote_endpoint() -> IbcEndpoint {
+        IbcEndpoint { port_id: REMOTE_PORT.to_string(), channel_id: REMOTE_CHANNEL.to_string() }
+    }
+
+    fn mock_channel(order: IbcOrder, version: &str) -> IbcChannel {
+        IbcChannel::new(local_endpoint(), remote_endpoint(), order, version, CONNECTION)
+    }
+
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg {}).unwrap();
+
+        let msg = ExecuteMsg::CreateToken(TokenInfoMsg {
+            name: "RUB".to_string(),
+            denom: "RUB".to_string(),
+            initial_balances: vec![Cw20Coin {
+                address: "addr0000".to_string(),
+                amount: Uint128::from(1000u128),
+            }],
+        });
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let channel = mock_channel(ICS20_ORDERING, ICS20_VERSION);
+        let open_msg = IbcChannelOpenMsg::new_init(channel.clone());
+        ibc_channel_open(deps.as_mut(), mock_env(), open_msg).unwrap();
+        let connect_msg = IbcChannelConnectMsg::new_ack(channel, ICS20_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), connect_msg).unwrap();
+        deps
+    }
+
+    fn sent_packet(data: &Ics20Packet) -> IbcPacket {
+        IbcPacket::new(
+            to_binary(data).unwrap(),
+            local_endpoint(),
+            remote_endpoint(),
+            3,
+            IbcTimeout::with_timestamp(Timestamp::from_seconds(1665321069)),
+        )
+    }
+
+    fn received_packet(data: &Ics20Packet) -> IbcPacket {
+        IbcPacket::new(
+            to_binary(data).unwrap(),
+            remote_endpoint(),
+            local_endpoint(),
+            4,
+            IbcTimeout::with_timestamp(Timestamp::from_seconds(1665321069)),
+        )
+    }
+
+    fn receive(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, data: &Ics20Packet) -> Ics20Ack {
+        let msg = IbcPacketReceiveMsg::new(received_packet(data), Addr::unchecked("relayer"));
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        from_binary(&res.acknowledgement).unwrap()
+    }
+
+    fn query_balance(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, address: &str) -> Uint128 {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Balance { address: address.to_string() }).unwrap();
+        let value: BalanceResponse = from_binary(&res).unwrap();
+        value.amount
+    }
+
+    fn query_outstanding(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>) -> Uint128 {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ChannelInfo { id: CHANNEL.to_string() }).unwrap();
+        let value: ChannelInfoResponse = from_binary(&res).unwrap();
+        value.balances.iter().find(|b| b.denom == "RUB").map(|b| b.amount).unwrap_or_default()
+    }
+
+    fn transfer_msg(channel: &str, amount: u128) -> ExecuteMsg {
+        ExecuteMsg::Transfer(TransferMsg {
+            channel: channel.to_string(),
+            remote_address: "cheqd1remote".to_string(),
+            amount: Uint128::from(amount),
+            timeout: None,
+        })
+    }
+
+    fn transfer(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, amount: u128) -> Ics20Packet {
+        let msg = transfer_msg(CHANNEL, amount);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { channel_id, data, timeout }) => {
+                assert_eq!(CHANNEL, channel_id);
+                assert_eq!(Some(mock_env().block.time.plus_seconds(DEFAULT_TIMEOUT)), timeout.timestamp());
+                from_binary(data).unwrap()
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn enforce_version_in_handshake() {
+        let mut deps = mock_dependencies();
+
+        let open_msg = IbcChannelOpenMsg::new_init(mock_channel(IbcOrder::Ordered, ICS20_VERSION));
+        let err = ibc_channel_open(deps.as_mut(), mock_env(), open_msg).unwrap_err();
+        assert!(matches!(err, ContractError::OnlyUnorderedChannel {}));
+
+        let open_msg = mock_ibc_channel_open_init("channel-1", ICS20_ORDERING, "ics20-2");
+        let err = ibc_channel_open(deps.as_mut(), mock_env(), open_msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIbcVersion { .. }));
+
+        let open_msg = mock_ibc_channel_open_try("channel-1", ICS20_ORDERING, "ics20-2");
+        let err = ibc_channel_open(deps.as_mut(), mock_env(), open_msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIbcVersion { .. }));
+
+        let open_msg = mock_ibc_channel_open_try("channel-1", ICS20_ORDERING, ICS20_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), open_msg).unwrap();
+
+        let connect_msg = mock_ibc_channel_connect_ack("channel-1", ICS20_ORDERING, ICS20_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), connect_msg).unwrap();
+        assert!(CHANNEL_INFO.has(&deps.storage, "channel-1".to_string()));
+    }
+
+    #[test]
+    fn transfer_escrows_tokens() {
+        let mut deps = setup();
+
+        let packet = transfer(&mut deps, 400);
+        assert_eq!(Ics20Packet::new(Uint128::from(400u128), "RUB", "addr0000", "cheqd1remote"), packet);
+        assert_eq!(Uint128::from(600u128), query_balance(&deps, "addr0000"));
+        assert_eq!(Uint128::from(400u128), query_outstanding(&deps));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), transfer_msg("channel-9", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::ChannelDoesNotExist { .. }));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), transfer_msg(CHANNEL, 601)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), transfer_msg(CHANNEL, 0)).unwrap_err();
+        assert!(matches!(err, ContractError::ZeroAmount {}));
+    }
+
+    #[test]
+    fn success_ack_keeps_escrow() {
+        let mut deps = setup();
+        let data = transfer(&mut deps, 300);
+
+        let ack = IbcAcknowledgement::new(ack_success());
+        let msg = IbcPacketAckMsg::new(ack, sent_packet(&data), Addr::unchecked("relayer"));
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "success" && a.value == "true"));
+
+        assert_eq!(Uint128::from(700u128), query_balance(&deps, "addr0000"));
+        assert_eq!(Uint128::from(300u128), query_outstanding(&deps));
+    }
+
+    #[test]
+    fn error_ack_and_timeout_refund_sender() {
+        let mut deps = setup();
+        let first = transfer(&mut deps, 300);
+        let second = transfer(&mut deps, 200);
+        assert_eq!(Uint128::from(500u128), query_balance(&deps, "addr0000"));
+
+        let ack = IbcAcknowledgement::new(ack_fail("denom not allowed".to_string()));
+        let msg = IbcPacketAckMsg::new(ack, sent_packet(&first), Addr::unchecked("relayer"));
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "success" && a.value == "false"));
+        assert_eq!(Uint128::from(800u128), query_balance(&deps, "addr0000"));
+        assert_eq!(Uint128::from(200u128), query_outstanding(&deps));
+
+        let msg = IbcPacketTimeoutMsg::new(sent_packet(&second), Addr::unchecked("relayer"));
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(Uint128::from(1000u128), query_balance(&deps, "addr0000"));
+        assert_eq!(Uint128::zero(), query_outstanding(&deps));
+    }
+
+    #[test]
+    fn receive_returning_tokens() {
+        let mut deps = setup();
+        transfer(&mut deps, 500);
+        let voucher = format!("{}/{}/RUB", REMOTE_PORT, REMOTE_CHANNEL);
+
+        let data = Ics20Packet::new(Uint128::from(200u128), &voucher, "cheqd1remote", "addr0001");
+        assert_eq!(Ics20Ack::Result(b"1".into()), receive(&mut deps, &data));
+        assert_eq!(Uint128::from(200u128), query_balance(&deps, "addr0001"));
+        assert_eq!(Uint128::from(300u128), query_outstanding(&deps));
+
+        // cannot redeem more than escrowed
+        let data = Ics20Packet::new(Uint128::from(301u128), &voucher, "cheqd1remote", "addr0001");
+        assert!(matches!(receive(&mut deps, &data), Ics20Ack::Error(_)));
+        assert_eq!(Uint128::from(200u128), query_balance(&deps, "addr0001"));
+
+        // foreign tokens are rejected
+        let data = Ics20Packet::new(Uint128::from(1u128), "ncheq", "cheqd1remote", "addr0001");
+        assert!(matches!(receive(&mut deps, &data), Ics20Ack::Error(_)));
+        assert_eq!(Uint128::from(300u128), query_outstanding(&deps));
+    }
+
+    #[test]
+    fn receive_to_account_not_on_allowlist() {
+        let mut deps = setup();
+        transfer(&mut deps, 500);
+        let msg = ExecuteMsg::SetKycRequired { denom: "RUB".to_string(), required: true };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the error ack refunds the remote sender, so the escrow has to stay as it was
+        let voucher = format!("{}/{}/RUB", REMOTE_PORT, REMOTE_CHANNEL);
+        let data = Ics20Packet::new(Uint128::from(200u128), &voucher, "cheqd1remote", "addr0001");
+        assert!(matches!(receive(&mut deps, &data), Ics20Ack::Error(_)));
+        assert_eq!(Uint128::from(500u128), query_outstanding(&deps));
+        assert!(!BALANCES.has(&deps.storage, &Addr::unchecked("addr0001")));
+    }
+
+    #[test]
+    fn cannot_close_channel() {
+        let mut deps = setup();
+        let msg = IbcChannelCloseMsg::new_init(mock_channel(ICS20_ORDERING, ICS20_VERSION));
+        let err = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::CannotCloseChannel {}));
+    }
+}
@@ -0,0 +1,635 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    from_binary, to_binary, Addr, Binary, DepsMut, Env, IbcAcknowledgement, IbcBasicResponse,
+    IbcPacket, IbcReceiveResponse, MessageInfo, Response, StdResult, Uint128,
+};
+
+use crate::error::ContractError;
+use crate::helpers::{after_fee, record_accepted, update_bank_stats, assert_bank_active, assert_bank_operator, assert_allowlisted, assert_not_frozen, assert_not_paused, assert_rate_confirmed, check_compliance, exchange, record_obligation, settlement_scopes};
+use crate::ibc::{credit_account, reduce_channel_balance, ICS20_VERSION};
+use crate::msg::{ComplianceStage, TransactionMsg};
+use crate::state::{
+    BalanceInfo, TransactionInfo, TransactionStatus, Tranche, BALANCES, BANKS, BANK_CHANNELS, BANK_RESERVES, CHANNEL_INFO, EXCHANGE_RATES, TOKENS, TRANSACTIONS,
+};
+
+// Latest version of the settlement protocol, used both for the channel and the packets
+pub const TRADING_VERSION: &str = "cw20-trading-v1";
+// All the versions this contract is able to speak
+pub const TRADING_VERSIONS: &[&str] = &[TRADING_VERSION];
+
+// Envelope of every packet sent over a cw20-trading channel
+#[cw_serde]
+pub struct TradingPacket {
+    // schema version of the packet
+    pub version: String,
+    pub msg: TradingPacketMsg,
+}
+
+impl TradingPacket {
+    pub fn settle(instruction: SettlementInstruction) -> Self {
+        TradingPacket {
+            version: TRADING_VERSION.to_string(),
+            msg: TradingPacketMsg::Settle(instruction),
+        }
+    }
+}
+
+#[cw_serde]
+pub enum TradingPacketMsg {
+    // Ask a bank on the counterparty chain to pay the recipient
+    Settle(SettlementInstruction),
+}
+
+// Transfer instructions for the bank on the counterparty chain
+#[cw_serde]
+pub struct SettlementInstruction {
+    // Transaction id on the sending chain
+    pub transaction_id: String,
+    pub bank_id: String,
+    pub sender: String,
+    pub recipient: String,
+    // Amount in denom_from, escrowed on the sending chain
+    pub amount: Uint128,
+    pub denom_from: String,
+    pub denom_to: String,
+}
+
+#[cw_serde]
+pub enum TradingAck {
+    // The recipient was credited with amount of denom_to
+    Settled { amount: Uint128 },
+    Error(String),
+}
+
+fn ack_settled(amount: Uint128) -> Binary {
+    to_binary(&TradingAck::Settled { amount }).unwrap()
+}
+
+fn ack_fail(err: String) -> Binary {
+    to_binary(&TradingAck::Error(err)).unwrap()
+}
+
+pub fn execute_set_bank_channel(
+    deps: DepsMut,
+    info: MessageInfo,
+    bank_id: String,
+    channel: String,
+    counterparty_port: Option<String>,
+) -> Result<Response, ContractError> {
+    if !BANKS.has(deps.storage, bank_id.clone()) {
+        return Err(ContractError::BankNotRegistered { id: bank_id });
+    }
+    assert_bank_operator(deps.as_ref(), &info.sender, &bank_id)?;
+
+    match &counterparty_port {
+        Some(port) => {
+            let info = match CHANNEL_INFO.may_load(deps.storage, channel.clone())? {
+                Some(info) => info,
+                None => return Err(ContractError::ChannelDoesNotExist { id: channel }),
+            };
+            if info.version == ICS20_VERSION {
+                return Err(ContractError::InvalidIbcVersion { version: info.version });
+            }
+            if &info.counterparty_endpoint.port_id != port {
+                return Err(ContractError::UntrustedChannel { channel, port: port.clone() });
+            }
+            BANK_CHANNELS.save(deps.storage, (bank_id.clone(), channel.clone()), port)?;
+        }
+        None => BANK_CHANNELS.remove(deps.storage, (bank_id.clone(), channel.clone())),
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_bank_channel")
+        .add_attribute("bank_id", bank_id)
+        .add_attribute("channel", channel)
+        .add_attribute("counterparty_port", counterparty_port.unwrap_or_default()))
+}
+
+// Settle a transfer on behalf of a local bank, errors are turned into acknowledgements
+pub fn packet_receive(deps: DepsMut, env: Env, packet: &IbcPacket) -> Result<IbcReceiveResponse, ContractError> {
+    do_packet_receive(deps, env, packet).or_else(|err| {
+        Ok(IbcReceiveResponse::new()
+            .set_ack(ack_fail(err.to_string()))
+            .add_attribute("action", "settle")
+            .add_attribute("success", "false")
+            .add_attribute("error", err.to_string()))
+    })
+}
+
+fn do_packet_receive(deps: DepsMut, env: Env, packet: &IbcPacket) -> Result<IbcReceiveResponse, ContractError> {
+    let packet_msg: TradingPacket = from_binary(&packet.data)?;
+    if !TRADING_VERSIONS.contains(&packet_msg.version.as_str()) {
+        return Err(ContractError::UnsupportedPacketVersion { version: packet_msg.version });
+    }
+
+    match packet_msg.msg {
+        TradingPacketMsg::Settle(instruction) => settle(deps, env, packet, instruction),
+    }
+}
+
+fn settle(deps: DepsMut, env: Env, packet: &IbcPacket, instruction: SettlementInstruction) -> Result<IbcReceiveResponse, ContractError> {
+    // Remote transactions are namespaced by the channel they came from
+    let channel = packet.dest.channel_id.as_str();
+    let transaction_id = format!("{}/{}", channel, instruction.transaction_id);
+    if TRANSACTIONS.has(deps.storage, transaction_id.clone()) {
+        return Err(ContractError::TransactionAlreadyExists { id: transaction_id });
+    }
+
+    let bank_id = instruction.bank_id.clone();
+    if !BANKS.has(deps.storage, bank_id.clone()) {
+        return Err(ContractError::BankNotRegistered { id: bank_id });
+    }
+    assert_bank_active(deps.storage, &bank_id)?;
+    // anyone can open a channel, only the contract the bank trusts escrows the amount it pays against
+    if BANK_CHANNELS.may_load(deps.storage, (bank_id.clone(), channel.to_string()))?.as_ref() != Some(&packet.src.port_id) {
+        return Err(ContractError::UntrustedChannel { channel: channel.to_string(), port: packet.src.port_id.clone() });
+    }
+
+    let recipient = deps.api.addr_validate(&instruction.recipient)?;
+    let balance_to = match BALANCES.may_load(deps.storage, &recipient)? {
+        Some(balance) => balance,
+        None => return Err(ContractError::AccountDoesNotExist { account: recipient.to_string() }),
+    };
+    if balance_to.denom != instruction.denom_to {
+        return Err(ContractError::TokenAlreadyAssigned { denom: balance_to.denom });
+    }
+    // the bank takes the sent amount as reserves the operator can defund
+    let mut token_from = match TOKENS.may_load(deps.storage, instruction.denom_from.clone())? {
+        Some(token) => token,
+        None => return Err(ContractError::TokenNotRegistered { denom: instruction.denom_from }),
+    };
+
+    let exchange_rate_id = instruction.denom_from.to_owned() + instruction.denom_to.as_str();
+    let exchange_rate = match EXCHANGE_RATES.load(deps.storage, exchange_rate_id.clone()) {
+        Ok(exchange_rate) => exchange_rate,
+        Err(_) => return Err(ContractError::ExchangeRateDoesNotExist { id: exchange_rate_id }),
+    };
+    assert_rate_confirmed(deps.storage, &instruction.denom_from, &instruction.denom_to)?;
+    assert_not_paused(deps.storage, &settlement_scopes(&bank_id, &instruction.denom_from, &instruction.denom_to))?;
+    assert_not_frozen(deps.storage, &recipient, &instruction.denom_to)?;
+    assert_allowlisted(deps.storage, &recipient, &instruction.denom_to)?;
+    let review = check_compliance(deps.as_ref(), &transaction_id, &TransactionMsg {
+        bank_id: Some(bank_id.clone()),
+        from: Addr::unchecked(&instruction.sender),
+        to: recipient.clone(),
+        amount: instruction.amount,
+        idempotency_key: None,
+    }, ComplianceStage::Settlement)?;
+    // a packet cannot wait for a reviewer, the sender gets refunded instead
+    if let Some((checker, reason)) = review {
+        return Err(ContractError::ManualReviewRequired { checker, reason });
+    }
+
+    // the bank pays the recipient out of its reserves in denom_to
+    let amount = after_fee(deps.storage, &bank_id, exchange(instruction.amount, &exchange_rate))?;
+    let reserves = BANK_RESERVES.may_load(deps.storage, (bank_id.clone(), instruction.denom_to.clone()))?.unwrap_or_default();
+    if reserves < amount {
+        return Err(ContractError::NotEnoughBalance { available: reserves, required: amount });
+    }
+
+    BANK_RESERVES.save(deps.storage, (bank_id.clone(), instruction.denom_to.clone()), &(reserves - amount))?;
+    // and takes the escrowed tokens, minted here as the sending chain burns them on the ack
+    BANK_RESERVES.update(deps.storage, (bank_id.clone(), instruction.denom_from.clone()), |reserve| -> StdResult<_> {
+        Ok(reserve.unwrap_or_default() + instruction.amount)
+    })?;
+    token_from.total_supply += instruction.amount;
+    TOKENS.save(deps.storage, instruction.denom_from.clone(), &token_from)?;
+    record_obligation(deps.storage, env.block.time, &bank_id, &instruction.denom_from, instruction.amount, &instruction.denom_to, amount)?;
+    BALANCES.save(deps.storage, &recipient, &BalanceInfo {
+        amount: balance_to.amount + amount,
+        denom: balance_to.denom,
+    })?;
+
+    let transaction = TransactionInfo {
+        id: transaction_id.clone(),
+        bank_id,
+        from: Addr::unchecked(instruction.sender),
+        to: recipient,
+        amount: instruction.amount,
+        denom_from: instruction.denom_from,
+        denom_to: instruction.denom_to,
+        status: TransactionStatus::SentToRecipient,
+        channel_id: Some(channel.to_string()),
+        reviews: vec![],
+        acknowledged_at: None,
+        settled: instruction.amount,
+        compensated: Uint128::zero(),
+        deadline: None,
+        created_at: Some(env.block.time),
+        route: None,
+        tranches: vec![Tranche {
+            amount: instruction.amount,
+            received: amount,
+            rate: exchange_rate.rate,
+            precision: exchange_rate.precision,
+            timestamp: env.block.time,
+        }],
+    };
+    TRANSACTIONS.save(deps.storage, transaction_id.clone(), &transaction)?;
+    // settled on arrival
+    record_accepted(deps.storage, &transaction.bank_id, &transaction.denom_from, transaction.amount)?;
+    update_bank_stats(deps.storage, &transaction.bank_id, |stats| stats.settled += 1)?;
+
+    Ok(IbcReceiveResponse::new()
+        .set_ack(ack_settled(amount))
+        .add_attribute("action", "settle")
+        .add_attribute("transaction_id", transaction_id)
+        .add_attribute("amount", amount)
+        .add_attribute("success", "true"))
+}
+
+// Mark the transaction as settled, or refund the sender on error
+pub fn packet_ack(
+    mut deps: DepsMut,
+    packet: IbcPacket,
+    acknowledgement: IbcAcknowledgement,
+) -> Result<IbcBasicResponse, ContractError> {
+    let ack: TradingAck = from_binary(&acknowledgement.data)?;
+    let TradingPacketMsg::Settle(instruction) = from_binary::<TradingPacket>(&packet.data)?.msg;
+    let mut transaction = match TRANSACTIONS.may_load(deps.storage, instruction.transaction_id.clone())? {
+        Some(transaction) => transaction,
+        None => return Err(ContractError::TransactionDoesNotExist { id: instruction.transaction_id }),
+    };
+
+    match ack {
+        TradingAck::Settled { amount } => {
+            // the remote bank was paid the escrow in its own copy of the token, burn ours
+            reduce_channel_balance(deps.branch(), &packet.src.channel_id, &instruction.denom_from, instruction.amount)?;
+            let mut token = TOKENS.load(deps.storage, instruction.denom_from.clone())?;
+            token.total_supply -= instruction.amount;
+            TOKENS.save(deps.storage, instruction.denom_from.clone(), &token)?;
+            transaction.settled = transaction.amount;
+            TRANSACTIONS.save(deps.storage, instruction.transaction_id.clone(), &transaction.update_status(TransactionStatus::SentToRecipient))?;
+            Ok(IbcBasicResponse::new()
+                .add_attribute("action", "acknowledge_settlement")
+                .add_attribute("transaction_id", instruction.transaction_id)
+                .add_attribute("amount", amount)
+                .add_attribute("success", "true"))
+        }
+        TradingAck::Error(err) => refund(deps, &packet.src.channel_id, instruction, err),
+    }
+}
+
+// Refund the sender, the remote bank never saw the transfer
+pub fn packet_timeout(deps: DepsMut, packet: IbcPacket) -> Result<IbcBasicResponse, ContractError> {
+    let TradingPacketMsg::Settle(instruction) = from_binary::<TradingPacket>(&packet.data)?.msg;
+    refund(deps, &packet.src.channel_id, instruction, "timeout".to_string())
+}
+
+fn refund(
+    mut deps: DepsMut,
+    channel: &str,
+    instruction: SettlementInstruction,
+    err: String,
+) -> Result<IbcBasicResponse, ContractError> {
+    let mut transaction = match TRANSACTIONS.may_load(deps.storage, instruction.transaction_id.clone())? {
+        Some(transaction) => transaction,
+        None => return Err(ContractError::TransactionDoesNotExist { id: instruction.transaction_id }),
+    };
+    let sender = deps.api.addr_validate(&instruction.sender)?;
+
+    reduce_channel_balance(deps.branch(), channel, &instruction.denom_from, instruction.amount)?;
+    credit_account(deps.branch(), &sender, &instruction.denom_from, instruction.amount)?;
+    TRANSACTIONS.save(deps.storage, instruction.transaction_id.clone(), &transaction.update_status(TransactionStatus::RejectedByBank))?;
+
+    Ok(IbcBasicResponse::new()
+        .add_attribute("action", "acknowledge_settlement")
+        .add_attribute("transaction_id", instruction.transaction_id)
+        .add_attribute("success", "false")
+        .add_attribute("error", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, instantiate, query};
+    use crate::ibc::{ibc_channel_connect, ibc_channel_open, ibc_packet_ack, ibc_packet_receive, ibc_packet_timeout, ICS20_ORDERING};
+    use crate::msg::{
+        BalanceResponse, BankInfoResponse, ChannelInfoResponse, ExchangeRateMsg, ExecuteMsg, InstantiateMsg, QueryMsg,
+        RemoteTransactionMsg, TokenInfoMsg, TokenInfoResponse, TransactionInfoResponse,
+    };
+    use crate::state::BankInfo;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{
+        CosmosMsg, IbcChannel, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcEndpoint, IbcMsg,
+        IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcTimeout, OwnedDeps,
+    };
+    use cw20::Cw20Coin;
+
+    type Deps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+    const OSMOSIS_CHANNEL: &str = "channel-3";
+    const CHEQD_CHANNEL: &str = "channel-5";
+
+    fn osmosis_endpoint() -> IbcEndpoint {
+        IbcEndpoint { port_id: "wasm.osmo1contract".to_string(), channel_id: OSMOSIS_CHANNEL.to_string() }
+    }
+
+    fn cheqd_endpoint() -> IbcEndpoint {
+        IbcEndpoint { port_id: "wasm.cheqd1contract".to_string(), channel_id: CHEQD_CHANNEL.to_string() }
+    }
+
+    fn create_token(deps: &mut Deps, denom: &str, balances: &[(&str, u128)]) {
+        let msg = ExecuteMsg::CreateToken(TokenInfoMsg {
+            name: denom.to_string(),
+            denom: denom.to_string(),
+            initial_balances: balances.iter()
+                .map(|(address, amount)| Cw20Coin { address: address.to_string(), amount: Uint128::from(*amount) })
+                .collect(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    // Runs the whole handshake, osmosis is the initiator
+    fn setup() -> (Deps, Deps) {
+        let mut osmosis = mock_dependencies();
+        let mut cheqd = mock_dependencies();
+        instantiate(osmosis.as_mut(), mock_env(), mock_info("creator", &[]), InstantiateMsg {}).unwrap();
+        instantiate(cheqd.as_mut(), mock_env(), mock_info("creator", &[]), InstantiateMsg {}).unwrap();
+
+        // the relayer leaves the version empty and the contract proposes its latest one
+        let channel = IbcChannel::new(osmosis_endpoint(), cheqd_endpoint(), ICS20_ORDERING, "", "connection-0");
+        let res = ibc_channel_open(osmosis.as_mut(), mock_env(), IbcChannelOpenMsg::new_init(channel)).unwrap();
+        let version = res.unwrap().version;
+        assert_eq!(TRADING_VERSION, version);
+
+        let channel = IbcChannel::new(cheqd_endpoint(), osmosis_endpoint(), ICS20_ORDERING, version.clone(), "connection-1");
+        let res = ibc_channel_open(cheqd.as_mut(), mock_env(), IbcChannelOpenMsg::new_try(channel.clone(), version.clone())).unwrap();
+        assert_eq!(TRADING_VERSION, res.unwrap().version);
+        ibc_channel_connect(cheqd.as_mut(), mock_env(), IbcChannelConnectMsg::new_confirm(channel)).unwrap();
+
+        let channel = IbcChannel::new(osmosis_endpoint(), cheqd_endpoint(), ICS20_ORDERING, version.clone(), "connection-0");
+        ibc_channel_connect(osmosis.as_mut(), mock_env(), IbcChannelConnectMsg::new_ack(channel, version)).unwrap();
+
+        create_token(&mut osmosis, "RUB", &[("addr0000", 1000)]);
+
+        create_token(&mut cheqd, "RUB", &[]);
+        create_token(&mut cheqd, "USD", &[("addr0001", 0), ("treasury", 1000)]);
+        let msg = ExecuteMsg::CreateBank(BankInfo {
+            id: "bank0000".to_string(),
+            name: "Bank".to_string(),
+            balance: Uint128::from(1000u128),
+        });
+        execute(cheqd.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0000".to_string(), operator: Some("treasury".to_string()) };
+        execute(cheqd.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::FundBank { bank_id: "bank0000".to_string(), amount: Uint128::from(1000u128) };
+        execute(cheqd.as_mut(), mock_env(), mock_info("treasury", &[]), msg).unwrap();
+        trust_channel(&mut cheqd, Some("wasm.osmo1contract"));
+        let msg = ExecuteMsg::SetExchangeRate(ExchangeRateMsg {
+            denom_from: "RUB".to_string(),
+            denom_to: "USD".to_string(),
+            precision: 3,
+            rate: 200,
+        });
+        execute(cheqd.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        (osmosis, cheqd)
+    }
+
+    fn trust_channel(deps: &mut Deps, counterparty_port: Option<&str>) {
+        let msg = ExecuteMsg::SetBankChannel {
+            bank_id: "bank0000".to_string(),
+            channel: CHEQD_CHANNEL.to_string(),
+            counterparty_port: counterparty_port.map(str::to_string),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("treasury", &[]), msg).unwrap();
+    }
+
+    fn send_to_remote_bank(deps: &mut Deps, bank_id: &str, amount: u128) -> IbcPacket {
+        let msg = ExecuteMsg::SendToRemoteBank(RemoteTransactionMsg {
+            channel: OSMOSIS_CHANNEL.to_string(),
+            bank_id: bank_id.to_string(),
+            to: "addr0001".to_string(),
+            denom_to: "USD".to_string(),
+            amount: Uint128::from(amount),
+            timeout: None,
+            idempotency_key: None,
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { channel_id, data, timeout }) => {
+                assert_eq!(OSMOSIS_CHANNEL, channel_id);
+                IbcPacket::new(data.clone(), osmosis_endpoint(), cheqd_endpoint(), 1, timeout.clone())
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+    }
+
+    fn relay(osmosis: &mut Deps, cheqd: &mut Deps, packet: IbcPacket) -> TradingAck {
+        let msg = IbcPacketReceiveMsg::new(packet.clone(), Addr::unchecked("relayer"));
+        let res = ibc_packet_receive(cheqd.as_mut(), mock_env(), msg).unwrap();
+        let ack = IbcAcknowledgement::new(res.acknowledgement.clone());
+        let msg = IbcPacketAckMsg::new(ack, packet, Addr::unchecked("relayer"));
+        ibc_packet_ack(osmosis.as_mut(), mock_env(), msg).unwrap();
+        from_binary(&res.acknowledgement).unwrap()
+    }
+
+    fn query_balance(deps: &Deps, address: &str) -> Uint128 {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Balance { address: address.to_string() }).unwrap();
+        from_binary::<BalanceResponse>(&res).unwrap().amount
+    }
+
+    fn query_supply(deps: &Deps, denom: &str) -> Uint128 {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo { denom: denom.to_string() }).unwrap();
+        from_binary::<TokenInfoResponse>(&res).unwrap().total_supply
+    }
+
+    fn query_escrow(deps: &Deps) -> Vec<BalanceResponse> {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ChannelInfo { id: OSMOSIS_CHANNEL.to_string() }).unwrap();
+        from_binary::<ChannelInfoResponse>(&res).unwrap().balances
+    }
+
+    fn query_transaction(deps: &Deps, id: &str) -> TransactionInfoResponse {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::TransactionInfo { id: id.to_string() }).unwrap();
+        from_binary(&res).unwrap()
+    }
+
+    #[test]
+    fn negotiate_version() {
+        let mut deps = mock_dependencies();
+
+        let channel = IbcChannel::new(osmosis_endpoint(), cheqd_endpoint(), ICS20_ORDERING, "cw20-trading-v2", "connection-0");
+        let err = ibc_channel_open(deps.as_mut(), mock_env(), IbcChannelOpenMsg::new_init(channel)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIbcVersion { .. }));
+
+        let channel = IbcChannel::new(cheqd_endpoint(), osmosis_endpoint(), ICS20_ORDERING, "cw20-trading-v2", "connection-1");
+        let err = ibc_channel_open(deps.as_mut(), mock_env(), IbcChannelOpenMsg::new_try(channel, "cw20-trading-v2")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIbcVersion { .. }));
+
+        // counterparty agreed on a different version than the one we proposed
+        let channel = IbcChannel::new(osmosis_endpoint(), cheqd_endpoint(), ICS20_ORDERING, TRADING_VERSION, "connection-0");
+        let err = ibc_channel_connect(deps.as_mut(), mock_env(), IbcChannelConnectMsg::new_ack(channel, "ics20-1")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIbcVersion { .. }));
+    }
+
+    #[test]
+    fn settle_on_remote_bank() {
+        let (mut osmosis, mut cheqd) = setup();
+
+        let packet = send_to_remote_bank(&mut osmosis, "bank0000", 400);
+        assert_eq!(Uint128::from(600u128), query_balance(&osmosis, "addr0000"));
+        let tinfo = query_transaction(&osmosis, "1");
+        assert_eq!(TransactionStatus::SentToBank, tinfo.status);
+        assert_eq!(Some(OSMOSIS_CHANNEL.to_string()), tinfo.channel_id);
+
+        let ack = relay(&mut osmosis, &mut cheqd, packet);
+        assert_eq!(TradingAck::Settled { amount: Uint128::from(80u128) }, ack);
+
+        // recipient paid out of the bank reserves on cheqd, the bank gets the escrow minted on cheqd
+        assert_eq!(Uint128::from(80u128), query_balance(&cheqd, "addr0001"));
+        let res = query(cheqd.as_ref(), mock_env(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+        let bank: BankInfoResponse = from_binary(&res).unwrap();
+        let expected = vec![
+            BalanceResponse { amount: Uint128::from(400u128), denom: "RUB".to_string() },
+            BalanceResponse { amount: Uint128::from(920u128), denom: "USD".to_string() },
+        ];
+        assert_eq!(expected, bank.reserves);
+        let remote = query_transaction(&cheqd, &format!("{}/1", CHEQD_CHANNEL));
+        assert_eq!(TransactionStatus::SentToRecipient, remote.status);
+
+        let tinfo = query_transaction(&osmosis, "1");
+        assert_eq!(TransactionStatus::SentToRecipient, tinfo.status);
+        assert_eq!(Uint128::from(600u128), query_balance(&osmosis, "addr0000"));
+        assert_eq!(vec![BalanceResponse { amount: Uint128::zero(), denom: "RUB".to_string() }], query_escrow(&osmosis));
+        // the escrow moved from one chain to the other
+        assert_eq!(Uint128::from(600u128), query_supply(&osmosis, "RUB"));
+        assert_eq!(Uint128::from(400u128), query_supply(&cheqd, "RUB"));
+    }
+
+    #[test]
+    fn defund_settled_reserves_and_close() {
+        let (mut osmosis, mut cheqd) = setup();
+        let packet = send_to_remote_bank(&mut osmosis, "bank0000", 400);
+        relay(&mut osmosis, &mut cheqd, packet);
+
+        let treasury = mock_info("treasury", &[]);
+        let defund = |denom: &str, amount: u128, receiver: Option<&str>| ExecuteMsg::DefundBank {
+            bank_id: "bank0000".to_string(),
+            denom: denom.to_string(),
+            amount: Uint128::from(amount),
+            receiver: receiver.map(str::to_string),
+        };
+        execute(cheqd.as_mut(), mock_env(), treasury.clone(), defund("RUB", 400, Some("addr0002"))).unwrap();
+        execute(cheqd.as_mut(), mock_env(), treasury, defund("USD", 920, None)).unwrap();
+        assert_eq!(Uint128::from(400u128), query_balance(&cheqd, "addr0002"));
+        assert_eq!(Uint128::from(920u128), query_balance(&cheqd, "treasury"));
+
+        let msg = ExecuteMsg::CloseBank { bank_id: "bank0000".to_string() };
+        execute(cheqd.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn reject_unregistered_token() {
+        let (mut osmosis, mut cheqd) = setup();
+        let packet = send_to_remote_bank(&mut osmosis, "bank0000", 400);
+        let mut packet_msg: TradingPacket = from_binary(&packet.data).unwrap();
+        let TradingPacketMsg::Settle(instruction) = &mut packet_msg.msg;
+        instruction.denom_from = "EUR".to_string();
+        let packet = IbcPacket::new(to_binary(&packet_msg).unwrap(), packet.src, packet.dest, packet.sequence, packet.timeout);
+
+        let msg = IbcPacketReceiveMsg::new(packet, Addr::unchecked("relayer"));
+        let res = ibc_packet_receive(cheqd.as_mut(), mock_env(), msg).unwrap();
+        assert!(matches!(from_binary(&res.acknowledgement).unwrap(), TradingAck::Error(_)));
+        assert_eq!(Uint128::zero(), query_balance(&cheqd, "addr0001"));
+    }
+
+    #[test]
+    fn reject_untrusted_channel() {
+        let (mut osmosis, mut cheqd) = setup();
+
+        // only the admin or the operator picks the channels
+        let msg = ExecuteMsg::SetBankChannel {
+            bank_id: "bank0000".to_string(),
+            channel: CHEQD_CHANNEL.to_string(),
+            counterparty_port: None,
+        };
+        let err = execute(cheqd.as_mut(), mock_env(), mock_info("addr0001", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let msg = ExecuteMsg::SetBankChannel {
+            bank_id: "bank0000".to_string(),
+            channel: CHEQD_CHANNEL.to_string(),
+            counterparty_port: Some("wasm.forged".to_string()),
+        };
+        let err = execute(cheqd.as_mut(), mock_env(), mock_info("treasury", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::UntrustedChannel { .. }));
+
+        trust_channel(&mut cheqd, None);
+        let res = query(cheqd.as_ref(), mock_env(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+        assert!(from_binary::<BankInfoResponse>(&res).unwrap().channels.is_empty());
+
+        let packet = send_to_remote_bank(&mut osmosis, "bank0000", 400);
+        let ack = relay(&mut osmosis, &mut cheqd, packet);
+        assert!(matches!(ack, TradingAck::Error(_)));
+        assert_eq!(Uint128::zero(), query_balance(&cheqd, "addr0001"));
+        assert_eq!(Uint128::from(1000u128), query_balance(&osmosis, "addr0000"));
+    }
+
+    #[test]
+    fn ack_unknown_transaction() {
+        let (mut osmosis, _) = setup();
+
+        let mut packet = send_to_remote_bank(&mut osmosis, "bank0000", 400);
+        let mut packet_msg: TradingPacket = from_binary(&packet.data).unwrap();
+        let TradingPacketMsg::Settle(instruction) = &mut packet_msg.msg;
+        instruction.transaction_id = "9999".to_string();
+        packet.data = to_binary(&packet_msg).unwrap();
+
+        let ack = IbcAcknowledgement::new(ack_settled(Uint128::from(80u128)));
+        let err = ibc_packet_ack(osmosis.as_mut(), mock_env(), IbcPacketAckMsg::new(ack, packet.clone(), Addr::unchecked("relayer"))).unwrap_err();
+        assert!(matches!(err, ContractError::TransactionDoesNotExist { .. }));
+        let err = ibc_packet_timeout(osmosis.as_mut(), mock_env(), IbcPacketTimeoutMsg::new(packet, Addr::unchecked("relayer"))).unwrap_err();
+        assert!(matches!(err, ContractError::TransactionDoesNotExist { .. }));
+    }
+
+    #[test]
+    fn refund_on_error_ack() {
+        let (mut osmosis, mut cheqd) = setup();
+
+        let packet = send_to_remote_bank(&mut osmosis, "bank9999", 400);
+        let ack = relay(&mut osmosis, &mut cheqd, packet);
+        assert!(matches!(ack, TradingAck::Error(_)));
+
+        assert_eq!(Uint128::from(1000u128), query_balance(&osmosis, "addr0000"));
+        assert_eq!(Uint128::zero(), query_balance(&cheqd, "addr0001"));
+        let tinfo = query_transaction(&osmosis, "1");
+        assert_eq!(TransactionStatus::RejectedByBank, tinfo.status);
+    }
+
+    #[test]
+    fn refund_on_timeout() {
+        let (mut osmosis, _) = setup();
+
+        let packet = send_to_remote_bank(&mut osmosis, "bank0000", 400);
+        let msg = IbcPacketTimeoutMsg::new(packet, Addr::unchecked("relayer"));
+        ibc_packet_timeout(osmosis.as_mut(), mock_env(), msg).unwrap();
+
+        assert_eq!(Uint128::from(1000u128), query_balance(&osmosis, "addr0000"));
+        let tinfo = query_transaction(&osmosis, "1");
+        assert_eq!(TransactionStatus::RejectedByBank, tinfo.status);
+    }
+
+    #[test]
+    fn reject_unknown_packet_version() {
+        let (_, mut cheqd) = setup();
+
+        let mut packet_msg = TradingPacket::settle(SettlementInstruction {
+            transaction_id: "transaction0000".to_string(),
+            bank_id: "bank0000".to_string(),
+            sender: "addr0000".to_string(),
+            recipient: "addr0001".to_string(),
+            amount: Uint128::from(400u128),
+            denom_from: "RUB".to_string(),
+            denom_to: "USD".to_string(),
+        });
+        packet_msg.version = "cw20-trading-v2".to_string();
+        let timeout = IbcTimeout::with_timestamp(mock_env().block.time.plus_seconds(60));
+        let packet = IbcPacket::new(to_binary(&packet_msg).unwrap(), osmosis_endpoint(), cheqd_endpoint(), 1, timeout);
+
+        let res = ibc_packet_receive(cheqd.as_mut(), mock_env(), IbcPacketReceiveMsg::new(packet, Addr::unchecked("relayer"))).unwrap();
+        let ack: TradingAck = from_binary(&res.acknowledgement).unwrap();
+        assert!(matches!(ack, TradingAck::Error(_)));
+        assert_eq!(Uint128::zero(), query_balance(&cheqd, "addr0001"));
+    }
+}
@@ -0,0 +1,160 @@
+#[cfg(test)]
+mod tests {
+    use crate::msg::{
+        BankInfoResponse, ComplianceDecision, ComplianceQueryMsg, ComplianceStage, ExchangeRateMsg, ExecuteMsg,
+        InstantiateMsg, QueryMsg, TokenInfoMsg, TransactionInfoResponse, TransactionMsg,
+    };
+    use crate::state::{BankInfo, TransactionStatus};
+    use crate::ContractError;
+    use cosmwasm_std::{
+        to_binary, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult, Uint128,
+    };
+    use cw20::Cw20Coin;
+    use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+
+    const ADMIN: &str = "creator";
+
+    pub fn contract_token() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            crate::contract::instantiate,
+            crate::contract::query,
+        );
+        Box::new(contract)
+    }
+
+    // Rejects anything from 500 on and sends settlements from 300 on to manual review
+    pub fn contract_checker() -> Box<dyn Contract<Empty>> {
+        fn instantiate(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+            Ok(Response::default())
+        }
+        fn execute(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+            Ok(Response::default())
+        }
+        fn query(_deps: Deps, _env: Env, msg: ComplianceQueryMsg) -> StdResult<Binary> {
+            let ComplianceQueryMsg::CheckTransaction { transaction, stage, .. } = msg;
+            let decision = if transaction.amount >= Uint128::from(500u128) {
+                ComplianceDecision::Reject { reason: "amount above threshold".to_string() }
+            } else if stage == ComplianceStage::Settlement && transaction.amount >= Uint128::from(300u128) {
+                ComplianceDecision::Review { reason: "large settlement".to_string() }
+            } else {
+                ComplianceDecision::Approve
+            };
+            to_binary(&decision)
+        }
+        Box::new(ContractWrapper::new(execute, instantiate, query))
+    }
+
+    // RUB holder addr0000, USD holder addr0001, bank0000 with USD reserves and a RUBUSD rate of 0.2
+    fn setup() -> (App, Addr, Addr) {
+        let mut app = App::default();
+        let admin = Addr::unchecked(ADMIN);
+        let token_id = app.store_code(contract_token());
+        let checker_id = app.store_code(contract_checker());
+
+        let token = app
+            .instantiate_contract(token_id, admin.clone(), &InstantiateMsg {}, &[], "token", None)
+            .unwrap();
+        let checker = app
+            .instantiate_contract(checker_id, admin.clone(), &Empty {}, &[], "checker", None)
+            .unwrap();
+
+        for (denom, addresses) in [("RUB", vec!["addr0000"]), ("USD", vec!["addr0001", "treasury"])] {
+            let msg = ExecuteMsg::CreateToken(TokenInfoMsg {
+                name: denom.to_string(),
+                denom: denom.to_string(),
+                initial_balances: addresses.into_iter()
+                    .map(|address| Cw20Coin { address: address.to_string(), amount: Uint128::from(1000u128) })
+                    .collect(),
+            });
+            app.execute_contract(admin.clone(), token.clone(), &msg, &[]).unwrap();
+        }
+        let msg = ExecuteMsg::CreateBank(BankInfo {
+            id: "bank0000".to_string(),
+            name: "Bank".to_string(),
+            balance: Uint128::from(1000u128),
+        });
+        app.execute_contract(admin.clone(), token.clone(), &msg, &[]).unwrap();
+        let msg = ExecuteMsg::SetBankOperator { bank_id: "bank0000".to_string(), operator: Some("treasury".to_string()) };
+        app.execute_contract(admin.clone(), token.clone(), &msg, &[]).unwrap();
+        let msg = ExecuteMsg::FundBank { bank_id: "bank0000".to_string(), amount: Uint128::from(1000u128) };
+        app.execute_contract(Addr::unchecked("treasury"), token.clone(), &msg, &[]).unwrap();
+        let msg = ExecuteMsg::SetExchangeRate(ExchangeRateMsg {
+            denom_from: "RUB".to_string(),
+            denom_to: "USD".to_string(),
+            precision: 3,
+            rate: 200,
+        });
+        app.execute_contract(admin, token.clone(), &msg, &[]).unwrap();
+
+        (app, token, checker)
+    }
+
+    fn send_to_bank_msg(amount: u128) -> ExecuteMsg {
+        ExecuteMsg::SendToBank(TransactionMsg {
+            bank_id: Some("bank0000".to_string()),
+            from: Addr::unchecked("addr0000"),
+            to: Addr::unchecked("addr0001"),
+            amount: Uint128::from(amount),
+            idempotency_key: None,
+        })
+    }
+
+    mod compliance {
+        use super::*;
+
+        #[test]
+        fn checkers_approve_reject_and_review() {
+            let (mut app, token, checker) = setup();
+            let admin = Addr::unchecked(ADMIN);
+
+            let msg = ExecuteMsg::SetComplianceCheckers {
+                bank_id: "bank0000".to_string(),
+                checkers: vec![checker.to_string()],
+            };
+            let err = app.execute_contract(Addr::unchecked("addr0000"), token.clone(), &msg, &[]).unwrap_err();
+            assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+            app.execute_contract(admin.clone(), token.clone(), &msg, &[]).unwrap();
+
+            let bank: BankInfoResponse = app
+                .wrap()
+                .query_wasm_smart(&token, &QueryMsg::BankInfo { id: "bank0000".to_string() })
+                .unwrap();
+            assert_eq!(vec![checker.clone()], bank.compliance_checkers);
+
+            let err = app.execute_contract(admin.clone(), token.clone(), &send_to_bank_msg(500), &[]).unwrap_err();
+            assert_eq!(
+                format!("Transaction rejected by compliance checker {}: amount above threshold", checker),
+                err.downcast::<ContractError>().unwrap().to_string()
+            );
+
+            // approved on both stages
+            app.execute_contract(admin.clone(), token.clone(), &send_to_bank_msg(100), &[]).unwrap();
+            let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+            app.execute_contract(admin.clone(), token.clone(), &msg, &[]).unwrap();
+
+            // accepted by the bank but held at settlement
+            app.execute_contract(admin.clone(), token.clone(), &send_to_bank_msg(300), &[]).unwrap();
+            let msg = ExecuteMsg::SendToRecipient { transaction_id: "2".to_string(), amount: None };
+            app.execute_contract(admin.clone(), token.clone(), &msg, &[]).unwrap();
+            let transaction: TransactionInfoResponse = app
+                .wrap()
+                .query_wasm_smart(&token, &QueryMsg::TransactionInfo { id: "2".to_string() })
+                .unwrap();
+            assert_eq!(TransactionStatus::UnderReview, transaction.status);
+            assert_eq!(format!("compliance checker {}: large settlement", checker), transaction.reviews[0].note);
+            let err = app.execute_contract(admin.clone(), token.clone(), &msg, &[]).unwrap_err();
+            assert!(matches!(err.downcast().unwrap(), ContractError::InvalidTransactionStatus { .. }));
+
+            // once approved the checker is not asked for a review again
+            let approve = ExecuteMsg::ApproveTransaction { transaction_id: "2".to_string(), note: "known customer".to_string() };
+            app.execute_contract(admin.clone(), token.clone(), &approve, &[]).unwrap();
+            app.execute_contract(admin, token.clone(), &msg, &[]).unwrap();
+            let transaction: TransactionInfoResponse = app
+                .wrap()
+                .query_wasm_smart(&token, &QueryMsg::TransactionInfo { id: "2".to_string() })
+                .unwrap();
+            assert_eq!(TransactionStatus::SentToRecipient, transaction.status);
+        }
+    }
+}
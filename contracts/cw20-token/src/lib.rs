@@ -1,7 +1,17 @@
+pub mod atomic_swaps;
 pub mod contract;
 mod error;
 pub mod helpers;
+pub mod htlc;
+pub mod ibc;
+pub mod ibc_settlement;
+pub mod integration_tests;
 pub mod msg;
+pub mod order_book;
+pub mod pools;
+pub mod standing_orders;
 pub mod state;
+#[cfg(test)]
+mod testing;
 
 pub use crate::error::ContractError;
@@ -1,8 +1,8 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Coin, Decimal, IbcEndpoint, Timestamp, Uint128};
 use cw20::Cw20Coin;
 
-use crate::state::{ BankInfo, TransactionStatus};
+use crate::state::{Aggregation, BankInfo, BankStatus, CancellationPolicy, CollateralConfig, DefaultRecord, HashLock, Order, Pool, PoolSeed, RouteDecision, StandingOrder, StandingOrderExecution, SwapOffer, ClearingCycleInfo, NetPosition, NetTransfer, FreezeRecord, FrozenAccount, PauseInfo, PauseScope, PriceSubmission, RateBreach, RatePoint, ReviewQueueEntry, ReviewRecord, Role, TierLimits, Tranche, TransactionStatus};
 
 #[cw_serde]
 #[cfg_attr(test, derive(Default))]
@@ -16,10 +16,127 @@ pub enum ExecuteMsg {
     CreateBank(BankInfo),
     // Send Transaction to Bank
     SendToBank(TransactionMsg),
-    // Send Transaction to Recipient
-    SendToRecipient { transaction_id: String},
+    // Send Transaction to Recipient, the whole outstanding amount or a tranche of it
+    SendToRecipient { transaction_id: String, amount: Option<Uint128> },
+    // Send to many recipients through one bank, from the sender or by the admin
+    SendToBankBatch(BatchTransactionMsg),
+    // Settle the outstanding amount of many transactions
+    SettleBatch { transaction_ids: Vec<String>, mode: BatchMode },
     // Set exchange rate
     SetExchangeRate(ExchangeRateMsg),
+    // Send tokens to another chain over ICS-20
+    Transfer(TransferMsg),
+    // Send Transaction to a bank on another chain
+    SendToRemoteBank(RemoteTransactionMsg),
+    // Settle packets for the bank from the channel only if they come from the counterparty port, admin or bank operator
+    SetBankChannel { bank_id: String, channel: String, counterparty_port: Option<String> },
+    // Register oracle feeders for an exchange rate, admin only
+    SetOracleConfig(OracleConfigMsg),
+    // Submit a price as a registered feeder
+    SubmitPrice(PriceSubmissionMsg),
+    // Configure max rate moves of an exchange rate, admin only
+    SetCircuitBreaker(CircuitBreakerMsg),
+    // Resume settlements after a tripped circuit breaker, admin only
+    ConfirmExchangeRate { id: String },
+    // Set or remove the guardian, admin only
+    SetGuardian { guardian: Option<String> },
+    // Stop operations of a scope, admin or guardian
+    Pause { scope: PauseScope },
+    // Resume operations of a scope, admin only
+    Unpause { scope: PauseScope },
+    // Grant a role to an address, admin only
+    GrantRole { role: Role, address: String },
+    // Revoke a role from an address, admin only
+    RevokeRole { role: Role, address: String },
+    // Freeze an account for every denom or a single one, compliance only
+    Freeze { address: String, denom: Option<String>, reason: String },
+    // Unfreeze an account, compliance only
+    Unfreeze { address: String, denom: Option<String>, reason: String },
+    // Assign the KYC tier of an account, attestor only
+    SetKycTier { address: String, tier: u32 },
+    // Set the transfer limits of a tier for a denom, admin only
+    SetTierLimits { tier: u32, denom: String, limits: TierLimits },
+    // Let only accounts with a KYC tier send or receive the denom, can be set before the token is created, admin only
+    SetKycRequired { denom: String, required: bool },
+    // Replace the compliance checker contracts of a bank, admin only
+    SetComplianceCheckers { bank_id: String, checkers: Vec<String> },
+    // Set the operator of a bank, admin only
+    SetBankOperator { bank_id: String, operator: Option<String> },
+    // Rename the bank or hand it to another operator, admin only
+    UpdateBank { bank_id: String, name: Option<String>, operator: Option<String> },
+    // Stop new transactions of the bank, pending ones can still settle, admin only
+    SuspendBank { bank_id: String },
+    ResumeBank { bank_id: String },
+    // Retire a bank without pending transactions or reserves, admin only
+    CloseBank { bank_id: String },
+    // Send transactions of at least threshold to review, bank operator only
+    SetReviewThreshold { bank_id: String, denom: String, threshold: Option<Uint128> },
+    // Hold a transaction sent to the bank for review, bank operator only
+    FlagTransaction { transaction_id: String, note: String },
+    // Release a transaction under review for settlement, reviewer only
+    ApproveTransaction { transaction_id: String, note: String },
+    // Refund the sender of a transaction under review, reviewer only
+    RejectTransaction { transaction_id: String, note: String },
+    // Set when senders may cancel their transactions, bank operator only
+    SetCancellationPolicy { bank_id: String, policy: CancellationPolicy },
+    // Acknowledge a transaction sent to the bank, bank operator only
+    AcknowledgeTransaction { transaction_id: String },
+    // Withdraw a transaction not settled yet, sender only
+    CancelTransaction { transaction_id: String },
+    // Net the obligations of the current clearing cycle and open the next one, admin only
+    CloseClearingCycle {},
+    // Move tokens from the operator balance into the bank reserves, bank operator only
+    FundBank { bank_id: String, amount: Uint128 },
+    // Move tokens from the bank reserves back to the operator or the receiver, bank operator only
+    DefundBank { bank_id: String, denom: String, amount: Uint128, receiver: Option<String> },
+    // Require collateral from new banks, admin only
+    SetCollateralConfig(CollateralConfig),
+    // Lock more collateral from the sender balance, admin or bank operator
+    PostCollateral { bank_id: String, amount: Uint128 },
+    // Release collateral to the operator while the exposure stays under the cap, bank operator only
+    WithdrawCollateral { bank_id: String, amount: Uint128 },
+    // Commit to settle new transactions within the given seconds, admin or bank operator
+    SetSettlementSla { bank_id: String, seconds: Option<u64> },
+    // Charge a fee on settlements of the bank, admin or bank operator
+    SetBankFee { bank_id: String, fee_bps: u64 },
+    // Refund the sender of an overdue transaction and compensate it from the bank collateral, anyone
+    // the sender names a receiver holding the collateral token when its account holds another one
+    ClaimDefault { transaction_id: String, receiver: Option<String> },
+    // Sell amount of the sender token for buy_denom at price or better, the rest stays on the book
+    PlaceOrder { buy_denom: String, amount: Uint128, price: Decimal, receiver: Option<String> },
+    // Take a resting order off the book and refund what is left, order owner only
+    CancelOrder { order_id: u64 },
+    // Open a constant product pool for the pair, admin only
+    CreatePool { denom_a: String, denom_b: String, fee_bps: u64 },
+    // Deposit amount of the sender token on its side of the pool for LP shares. The first deposit of
+    // an empty pool waits for other_amount of the other token, which the next deposit has to match
+    AddLiquidity { pool_id: String, amount: Uint128, min_shares: Option<Uint128>, other_amount: Option<Uint128> },
+    // Take back the first deposit of a pool nobody matched yet, its provider only
+    CancelPoolSeed { pool_id: String },
+    // Burn LP shares for both tokens of the pool, the one the sender does not hold goes to receiver
+    RemoveLiquidity { pool_id: String, shares: Uint128, receiver: Option<String> },
+    // Sell amount of the sender token to the pool, the other token goes to receiver
+    Swap { pool_id: String, amount: Uint128, min_received: Option<Uint128>, receiver: Option<String> },
+    // Convert settlements of the bank through the pool of the pair when there is one, admin or bank operator
+    SetBankPoolRouting { bank_id: String, max_slippage_bps: Option<u64> },
+    // Offer give of the sender token for want from the counterparty until expires, give is escrowed
+    ProposeSwap { counterparty: String, give: Uint128, want: Coin, expires: Timestamp, receiver: Option<String> },
+    // Pay want and take the escrowed tokens of an open swap, counterparty only
+    AcceptSwap { id: u64, receiver: Option<String> },
+    // Withdraw an open swap and refund the escrow, proposer only
+    CancelSwap { id: u64 },
+    // Lock amount of the sender token for recipient until the sha256 preimage of hashlock is revealed
+    LockWithHash { recipient: String, amount: Uint128, denom: String, hashlock: String, timelock: Timestamp },
+    // Pay a lock to its recipient with the hex encoded preimage of its hashlock before the timelock, anyone
+    Claim { id: u64, preimage: String },
+    // Return a lock to its sender once the timelock has passed, anyone
+    Refund { id: u64 },
+    // Pay the recipient from the sender account every interval through SendToBank
+    CreateStandingOrder(StandingOrderMsg),
+    // Stop a standing order, owner only
+    CancelStandingOrder { id: u64 },
+    // Execute the due standing orders, soonest first, anyone
+    Crank { limit: Option<u32> },
 }
 
 #[cw_serde]
@@ -36,6 +153,110 @@ pub enum QueryMsg {
     BankInfo { id: String },
     #[returns(TransactionInfoResponse)]
     TransactionInfo { id: String },
+    /// Returns the escrowed balances of an ICS-20 channel
+    #[returns(ChannelInfoResponse)]
+    ChannelInfo { id: String },
+    #[returns(ConfigResponse)]
+    Config {},
+    #[returns(OracleConfigResponse)]
+    OracleConfig { id: String },
+    /// Returns the latest price of every feeder of the exchange rate
+    #[returns(PriceSubmissionsResponse)]
+    PriceSubmissions { id: String },
+    #[returns(CircuitBreakerResponse)]
+    CircuitBreaker { id: String },
+    /// Returns the history of breaches, oldest first
+    #[returns(RateBreachesResponse)]
+    RateBreaches { id: String, start_after: Option<u64>, limit: Option<u32> },
+    /// Returns every paused scope
+    #[returns(PausesResponse)]
+    Pauses {},
+    #[returns(RoleMembersResponse)]
+    RoleMembers { role: Role, start_after: Option<String>, limit: Option<u32> },
+    #[returns(FrozenAccountsResponse)]
+    FrozenAccounts { start_after: Option<String>, limit: Option<u32> },
+    /// Returns the freeze and unfreeze actions of an account, oldest first
+    #[returns(FreezeHistoryResponse)]
+    FreezeHistory { address: String, start_after: Option<u64>, limit: Option<u32> },
+    /// Returns what the account can still send of the denom under its tier limits
+    #[returns(AllowanceResponse)]
+    RemainingAllowance { address: String, denom: String },
+    /// Returns the transactions under review, oldest first
+    #[returns(ReviewQueueResponse)]
+    ReviewQueue { start_after: Option<String>, limit: Option<u32> },
+    /// Returns the net positions and transfers of the open clearing cycle
+    #[returns(NetObligationsResponse)]
+    NetObligations {},
+    /// Returns the closed clearing cycles, oldest first
+    #[returns(ClearingCyclesResponse)]
+    ClearingCycles { start_after: Option<u64>, limit: Option<u32> },
+    /// Returns the exposure of each bank against its collateral
+    #[returns(SolvencyResponse)]
+    Solvency { start_after: Option<String>, limit: Option<u32> },
+    /// Returns the recorded defaults of a bank
+    #[returns(BankDefaultsResponse)]
+    BankDefaults { bank_id: String, start_after: Option<String>, limit: Option<u32> },
+    /// Returns the performance counters of a bank
+    #[returns(BankStatsResponse)]
+    BankStats { bank_id: String },
+    /// Returns the banks that are not closed, best first by the metric
+    #[returns(BankRankingResponse)]
+    BankRanking { metric: RankingMetric, limit: Option<u32> },
+    /// Returns the bank a SendToBank without bank_id would be routed to
+    #[returns(RouteDecision)]
+    RoutePreview { from: String, to: String, amount: Uint128 },
+    /// Returns the resting orders selling sell_denom for buy_denom by price level, best first
+    #[returns(OrderBookResponse)]
+    OrderBook { sell_denom: String, buy_denom: String, limit: Option<u32> },
+    #[returns(OpenOrdersResponse)]
+    OpenOrders { owner: String, start_after: Option<u64>, limit: Option<u32> },
+    #[returns(Pool)]
+    Pool { pool_id: String },
+    /// Returns the first deposit of an empty pool waiting for the other token
+    #[returns(Option<PoolSeed>)]
+    PoolSeed { pool_id: String },
+    /// Returns the amount of the other token paid per unit of denom_from, before fees
+    #[returns(SpotPriceResponse)]
+    SpotPrice { pool_id: String, denom_from: String },
+    #[returns(LiquiditySharesResponse)]
+    LiquidityShares { pool_id: String, address: String },
+    /// Returns the spot prices of denom_a in denom_b after each change of the pool, oldest first
+    #[returns(PoolPricesResponse)]
+    PoolPrices { pool_id: String, start_after: Option<u64>, limit: Option<u32> },
+    #[returns(SwapOffer)]
+    SwapOffer { id: u64 },
+    /// Returns the swaps proposed by or to the address, oldest first
+    #[returns(SwapOffersResponse)]
+    SwapOffers { address: String, start_after: Option<u64>, limit: Option<u32> },
+    #[returns(HashLock)]
+    HashLock { id: u64 },
+    /// Returns the locks of the hashlock, oldest first
+    #[returns(HashLocksResponse)]
+    HashLocksByHash { hashlock: String, start_after: Option<u64>, limit: Option<u32> },
+    /// Returns the locks sent by or to the address, oldest first
+    #[returns(HashLocksResponse)]
+    HashLocks { address: String, start_after: Option<u64>, limit: Option<u32> },
+    #[returns(StandingOrder)]
+    StandingOrder { id: u64 },
+    #[returns(StandingOrdersResponse)]
+    StandingOrders { owner: String, start_after: Option<u64>, limit: Option<u32> },
+    /// Returns the next executions of the active standing orders, soonest first
+    #[returns(UpcomingExecutionsResponse)]
+    UpcomingExecutions { limit: Option<u32> },
+    /// Returns the executions and failures of a standing order, oldest first
+    #[returns(StandingOrderHistoryResponse)]
+    StandingOrderHistory { id: u64, start_after: Option<u64>, limit: Option<u32> },
+}
+
+#[cw_serde]
+pub enum RankingMetric {
+    // share of accepted transactions that were fully settled
+    SettlementRate,
+    // share of accepted transactions that defaulted, lowest first
+    DefaultRate,
+    // lowest first, banks without settlements last
+    AverageSettlementTime,
+    Settled,
 }
 
 #[cw_serde]
@@ -60,6 +281,40 @@ pub struct ExchangeRateMsg {
     pub rate: u64,
 }
 
+#[cw_serde]
+pub struct OracleConfigMsg {
+    pub denom_from: String,
+    pub denom_to: String,
+    pub feeders: Vec<String>,
+    pub quorum: u32,
+    pub precision: u32,
+    pub max_age: u64,
+    pub max_deviation_bps: u64,
+    pub aggregation: Aggregation,
+}
+
+#[cw_serde]
+pub struct PriceSubmissionMsg {
+    pub denom_from: String,
+    pub denom_to: String,
+    /// Rate with the precision of the oracle config
+    pub rate: u64,
+    /// When the price was observed
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub struct CircuitBreakerMsg {
+    pub denom_from: String,
+    pub denom_to: String,
+    /// Max move of a single update, in basis points
+    pub max_update_deviation_bps: u64,
+    /// Max move against any rate published within the window, in basis points
+    pub max_window_deviation_bps: u64,
+    /// Window length in seconds
+    pub window: u64,
+}
+
 #[cw_serde]
 pub struct TokenInfoResponse {
     pub name: String,
@@ -70,11 +325,63 @@ pub struct TokenInfoResponse {
 
 #[cw_serde]
 pub struct TransactionMsg {
-    pub id: String,
+    /// If not specified, the contract routes to the bank paying the recipient the most
+    pub bank_id: Option<String>,
+    pub from: Addr,
+    pub to: Addr,
+    pub amount: Uint128,
+    /// Retries with the same key from the same sender return the original transaction
+    pub idempotency_key: Option<String>,
+}
+
+#[cw_serde]
+pub struct BatchTransactionMsg {
     pub bank_id: String,
     pub from: Addr,
+    pub payments: Vec<PaymentMsg>,
+    pub mode: BatchMode,
+}
+
+#[cw_serde]
+pub struct PaymentMsg {
     pub to: Addr,
     pub amount: Uint128,
+    pub idempotency_key: Option<String>,
+}
+
+#[cw_serde]
+pub enum BatchMode {
+    // the first failed item fails the whole batch
+    AllOrNothing,
+    // failed items are skipped and reported
+    BestEffort,
+}
+
+#[cw_serde]
+pub struct TransferMsg {
+    /// The local channel to send the packets on
+    pub channel: String,
+    /// The remote address to send to
+    pub remote_address: String,
+    pub amount: Uint128,
+    /// How long the packet lives in seconds. If not specified, use DEFAULT_TIMEOUT
+    pub timeout: Option<u64>,
+}
+
+#[cw_serde]
+pub struct RemoteTransactionMsg {
+    /// The local cw20-trading channel connected to the bank's chain
+    pub channel: String,
+    /// The bank id on the counterparty chain
+    pub bank_id: String,
+    /// The recipient address on the counterparty chain
+    pub to: String,
+    pub denom_to: String,
+    pub amount: Uint128,
+    /// How long the packet lives in seconds. If not specified, use DEFAULT_TIMEOUT
+    pub timeout: Option<u64>,
+    /// Retries with the same key from the same sender return the original transaction
+    pub idempotency_key: Option<String>,
 }
 
 #[cw_serde]
@@ -87,6 +394,15 @@ pub struct TransactionInfoResponse {
     pub denom_from: String,
     pub denom_to: String,
     pub status: TransactionStatus,
+    pub channel_id: Option<String>,
+    pub reviews: Vec<ReviewRecord>,
+    pub acknowledged_at: Option<Timestamp>,
+    pub settled: Uint128,
+    pub compensated: Uint128,
+    pub outstanding: Uint128,
+    pub tranches: Vec<Tranche>,
+    pub deadline: Option<Timestamp>,
+    pub route: Option<RouteDecision>,
 }
 
 #[cw_serde]
@@ -103,4 +419,313 @@ pub struct BankInfoResponse {
     pub id: String,
     pub name: String,
     pub balance: Uint128,
+    pub operator: Option<Addr>,
+    pub compliance_checkers: Vec<Addr>,
+    pub cancellation_policy: CancellationPolicy,
+    pub status: BankStatus,
+    pub settlement_sla: Option<u64>,
+    pub fee_bps: u64,
+    pub reserves: Vec<BalanceResponse>,
+    pub outstanding: Vec<BalanceResponse>,
+    // slippage against the exchange rate the bank accepts on pool settlements
+    pub pool_routing: Option<u64>,
+    pub channels: Vec<BankChannel>,
+}
+
+#[cw_serde]
+pub struct BankChannel {
+    pub channel: String,
+    pub counterparty_port: String,
+}
+
+#[cw_serde]
+pub struct ChannelInfoResponse {
+    pub id: String,
+    pub counterparty_endpoint: IbcEndpoint,
+    pub connection_id: String,
+    /// Tokens currently escrowed on the channel
+    pub balances: Vec<BalanceResponse>,
+    /// Tokens ever sent over the channel
+    pub total_sent: Vec<BalanceResponse>,
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    pub admin: Addr,
+    pub guardian: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct PausesResponse {
+    pub pauses: Vec<PauseInfo>,
+}
+
+#[cw_serde]
+pub struct OracleConfigResponse {
+    pub id: String,
+    pub denom_from: String,
+    pub denom_to: String,
+    pub feeders: Vec<Addr>,
+    pub quorum: u32,
+    pub precision: u32,
+    pub max_age: u64,
+    pub max_deviation_bps: u64,
+    pub aggregation: Aggregation,
+}
+
+#[cw_serde]
+pub struct PriceSubmissionsResponse {
+    pub id: String,
+    pub submissions: Vec<PriceSubmission>,
+}
+
+#[cw_serde]
+pub struct CircuitBreakerResponse {
+    pub id: String,
+    pub denom_from: String,
+    pub denom_to: String,
+    pub max_update_deviation_bps: u64,
+    pub max_window_deviation_bps: u64,
+    pub window: u64,
+    pub history: Vec<RatePoint>,
+    /// Set while settlements of the pair are paused
+    pub breach: Option<RateBreach>,
+}
+
+#[cw_serde]
+pub struct RateBreachResponse {
+    pub index: u64,
+    pub breach: RateBreach,
+}
+
+#[cw_serde]
+pub struct RateBreachesResponse {
+    pub breaches: Vec<RateBreachResponse>,
+}
+
+#[cw_serde]
+pub struct RoleMembersResponse {
+    pub role: Role,
+    pub members: Vec<Addr>,
+}
+
+#[cw_serde]
+pub struct FrozenAccountsResponse {
+    pub accounts: Vec<FrozenAccount>,
+}
+
+#[cw_serde]
+pub struct FreezeRecordResponse {
+    pub index: u64,
+    pub record: FreezeRecord,
+}
+
+#[cw_serde]
+pub struct FreezeHistoryResponse {
+    pub records: Vec<FreezeRecordResponse>,
+}
+
+// None means unlimited
+#[cw_serde]
+pub struct AllowanceResponse {
+    pub address: Addr,
+    pub denom: String,
+    pub tier: u32,
+    // false while the denom requires a KYC tier the account does not have
+    pub allowed: bool,
+    pub max_transfer: Option<Uint128>,
+    pub daily_remaining: Option<Uint128>,
+    pub monthly_remaining: Option<Uint128>,
+}
+
+/// Data of SendToBank and SendToRemoteBank responses
+#[cw_serde]
+pub struct TransactionIdResponse {
+    pub transaction_id: String,
+}
+
+/// Data of SendToBankBatch and SettleBatch responses, one result per item in order
+#[cw_serde]
+pub struct BatchResponse {
+    pub results: Vec<BatchItemResult>,
+}
+
+#[cw_serde]
+pub struct BatchItemResult {
+    pub transaction_id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[cw_serde]
+pub struct ReviewQueueResponse {
+    pub entries: Vec<ReviewQueueEntry>,
+}
+
+#[cw_serde]
+pub struct NetObligationsResponse {
+    pub cycle: u64,
+    pub opened_at: Timestamp,
+    pub positions: Vec<NetPosition>,
+    pub transfers: Vec<NetTransfer>,
+}
+
+#[cw_serde]
+pub struct ClearingCyclesResponse {
+    pub cycles: Vec<ClearingCycleInfo>,
+}
+
+#[cw_serde]
+pub struct BankSolvency {
+    pub bank_id: String,
+    pub collateral: Uint128,
+    // outstanding obligations valued in the collateral denom
+    pub exposure: Uint128,
+    pub cap: Uint128,
+}
+
+#[cw_serde]
+pub struct PlaceOrderResponse {
+    pub order_id: u64,
+    // sold right away, in sell_denom
+    pub filled: Uint128,
+    // left on the book
+    pub remaining: Uint128,
+}
+
+#[cw_serde]
+pub struct DepthLevel {
+    pub price: Decimal,
+    // in sell_denom
+    pub amount: Uint128,
+    pub orders: u32,
+}
+
+#[cw_serde]
+pub struct OrderBookResponse {
+    pub levels: Vec<DepthLevel>,
+}
+
+#[cw_serde]
+pub struct OpenOrdersResponse {
+    pub orders: Vec<Order>,
+}
+
+#[cw_serde]
+pub struct SpotPriceResponse {
+    // none while a side of the pool is empty
+    pub price: Option<Decimal>,
+}
+
+#[cw_serde]
+pub struct LiquiditySharesResponse {
+    pub shares: Uint128,
+    pub total_shares: Uint128,
+}
+
+#[cw_serde]
+pub struct PoolPricesResponse {
+    pub prices: Vec<RatePoint>,
+}
+
+#[cw_serde]
+pub struct SwapResponse {
+    pub received: Uint128,
+}
+
+#[cw_serde]
+pub struct StandingOrderMsg {
+    pub to: String,
+    pub amount: Uint128,
+    // has to be the token of the sender account
+    pub denom: String,
+    // routed to the best bank when none
+    pub bank_id: Option<String>,
+    // seconds between executions
+    pub interval: u64,
+    // first execution
+    pub start: Timestamp,
+    // no execution after, runs until cancelled when none
+    pub end: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub struct StandingOrdersResponse {
+    pub orders: Vec<StandingOrder>,
+}
+
+#[cw_serde]
+pub struct UpcomingExecution {
+    pub order_id: u64,
+    pub scheduled_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct UpcomingExecutionsResponse {
+    pub executions: Vec<UpcomingExecution>,
+}
+
+#[cw_serde]
+pub struct StandingOrderHistoryResponse {
+    pub executions: Vec<StandingOrderExecution>,
+}
+
+#[cw_serde]
+pub struct HashLocksResponse {
+    pub locks: Vec<HashLock>,
+}
+
+#[cw_serde]
+pub struct SwapOffersResponse {
+    pub swaps: Vec<SwapOffer>,
+}
+
+#[cw_serde]
+pub struct BankStatsResponse {
+    pub bank_id: String,
+    pub accepted: u64,
+    pub settled: u64,
+    pub rejected: u64,
+    pub defaulted: u64,
+    pub average_settlement_time: Option<u64>,
+    pub settlement_rate_bps: Option<u64>,
+    pub default_rate_bps: Option<u64>,
+    // accepted amount by denom_from
+    pub volumes: Vec<BalanceResponse>,
+}
+
+#[cw_serde]
+pub struct BankRankingResponse {
+    pub banks: Vec<BankStatsResponse>,
+}
+
+#[cw_serde]
+pub struct BankDefaultsResponse {
+    pub defaults: Vec<DefaultRecord>,
+}
+
+#[cw_serde]
+pub struct SolvencyResponse {
+    pub denom: Option<String>,
+    pub banks: Vec<BankSolvency>,
+}
+
+/// Query interface every compliance checker contract implements
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum ComplianceQueryMsg {
+    #[returns(ComplianceDecision)]
+    CheckTransaction { transaction_id: String, transaction: TransactionMsg, stage: ComplianceStage },
+}
+
+#[cw_serde]
+pub enum ComplianceStage {
+    SendToBank,
+    Settlement,
+}
+
+#[cw_serde]
+pub enum ComplianceDecision {
+    Approve,
+    Reject { reason: String },
+    Review { reason: String },
 }
@@ -0,0 +1,313 @@
+use cosmwasm_std::{
+    to_binary, Binary, Decimal, Deps, DepsMut, Env, Event, MessageInfo, Order as StorageOrder, Response,
+    StdResult, Storage, Uint128, Uint256,
+};
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::helpers::{assert_not_frozen, assert_not_paused, consume_allowance, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::ibc::credit_account;
+use crate::msg::{DepthLevel, OpenOrdersResponse, OrderBookResponse, PlaceOrderResponse};
+use crate::state::{BalanceInfo, Order, PauseScope, BALANCES, ORDERS, ORDER_BOOK, ORDER_COUNT, OWNER_ORDERS, TOKENS};
+
+// fills per placement, whatever is left rests on the book
+const MAX_FILLS: usize = 30;
+
+fn pair(sell_denom: &str, buy_denom: &str) -> String {
+    format!("{}/{}", sell_denom, buy_denom)
+}
+
+pub fn execute_place_order(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    buy_denom: String,
+    amount: Uint128,
+    price: Decimal,
+    receiver: Option<String>,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    if price.is_zero() {
+        return Err(ContractError::InvalidOrder { reason: "price must not be zero".to_string() });
+    }
+    let balance = match BALANCES.may_load(deps.storage, &info.sender)? {
+        Some(balance) => balance,
+        None => return Err(ContractError::AccountDoesNotExist { account: info.sender.to_string() }),
+    };
+    let sell_denom = balance.denom.clone();
+    if sell_denom == buy_denom {
+        return Err(ContractError::InvalidOrder { reason: "cannot buy the token sold".to_string() });
+    }
+    if !TOKENS.has(deps.storage, buy_denom.clone()) {
+        return Err(ContractError::TokenNotRegistered { denom: buy_denom });
+    }
+    if balance.amount < amount {
+        return Err(ContractError::NotEnoughBalance { required: amount, available: balance.amount });
+    }
+    assert_not_paused(deps.storage, &[
+        PauseScope::Denom { denom: sell_denom.clone() },
+        PauseScope::Denom { denom: buy_denom.clone() },
+    ])?;
+    assert_not_frozen(deps.storage, &info.sender, &sell_denom)?;
+    let receiver = match receiver {
+        Some(receiver) => deps.api.addr_validate(&receiver)?,
+        None => info.sender.clone(),
+    };
+    assert_not_frozen(deps.storage, &receiver, &buy_denom)?;
+    credit_account(deps.branch(), &receiver, &buy_denom, Uint128::zero())?;
+    consume_allowance(deps.storage, &info.sender, &sell_denom, amount, env.block.time)?;
+
+    // escrow the whole amount, fills and cancellation pay out of it
+    BALANCES.save(deps.storage, &info.sender, &BalanceInfo { amount: balance.amount - amount, denom: sell_denom.clone() })?;
+    let id = ORDER_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    ORDER_COUNT.save(deps.storage, &id)?;
+    let mut order = Order {
+        id,
+        owner: info.sender,
+        receiver,
+        sell_denom,
+        buy_denom,
+        amount,
+        remaining: amount,
+        price,
+        created_at: env.block.time,
+    };
+
+    let trades = match_order(deps.branch(), &mut order)?;
+    if !order.remaining.is_zero() {
+        rest_order(deps.storage, &order)?;
+    }
+    let filled = order.amount - order.remaining;
+
+    Ok(Response::new()
+        .set_data(to_binary(&PlaceOrderResponse { order_id: id, filled, remaining: order.remaining })?)
+        .add_events(trades)
+        .add_attribute("action", "place_order")
+        .add_attribute("order_id", id.to_string())
+        .add_attribute("filled", filled)
+        .add_attribute("remaining", order.remaining))
+}
+
+// Fills the taker against the resting orders selling its buy_denom, lowest price first,
+// each fill at the resting order price
+fn match_order(mut deps: DepsMut, taker: &mut Order) -> Result<Vec<Event>, ContractError> {
+    let makers = ORDER_BOOK
+        .sub_prefix(pair(&taker.buy_denom, &taker.sell_denom))
+        .keys(deps.storage, None, None, StorageOrder::Ascending)
+        .take(MAX_FILLS)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut trades = vec![];
+    for (_, maker_id) in makers {
+        if taker.remaining.is_zero() {
+            break;
+        }
+        let mut maker = ORDERS.load(deps.storage, maker_id)?;
+        // the maker asks maker.price of taker.sell_denom per unit, the taker accepts up to 1 / taker.price
+        let crosses = maker.price.checked_mul(taker.price).is_ok_and(|product| product <= Decimal::one());
+        if !crosses {
+            break;
+        }
+        let affordable = taker.remaining.multiply_ratio(Decimal::one().atomics(), maker.price.atomics());
+        let bought = maker.remaining.min(affordable);
+        if bought.is_zero() {
+            break;
+        }
+        // rounded up in favour of the maker, never more than the taker has left
+        let paid = mul_ceil(bought, maker.price)?;
+        maker.remaining -= bought;
+        taker.remaining -= paid;
+
+        credit_account(deps.branch(), &maker.receiver, &maker.buy_denom, paid)?;
+        credit_account(deps.branch(), &taker.receiver, &taker.buy_denom, bought)?;
+        if maker.remaining.is_zero() {
+            remove_order(deps.storage, &maker);
+        } else {
+            ORDERS.save(deps.storage, maker.id, &maker)?;
+        }
+
+        trades.push(Event::new("trade")
+            .add_attribute("maker_order", maker.id.to_string())
+            .add_attribute("taker_order", taker.id.to_string())
+            .add_attribute("price", maker.price.to_string())
+            .add_attribute("maker_sold", format!("{}{}", bought, maker.sell_denom))
+            .add_attribute("taker_sold", format!("{}{}", paid, taker.sell_denom)));
+    }
+    Ok(trades)
+}
+
+fn mul_ceil(amount: Uint128, price: Decimal) -> StdResult<Uint128> {
+    let one = Uint256::from(Decimal::one().atomics());
+    let product = amount.full_mul(price.atomics());
+    Ok(((product + one - Uint256::one()) / one).try_into()?)
+}
+
+fn rest_order(storage: &mut dyn Storage, order: &Order) -> StdResult<()> {
+    ORDERS.save(storage, order.id, order)?;
+    ORDER_BOOK.save(storage, (pair(&order.sell_denom, &order.buy_denom), order.price.atomics().u128(), order.id), &())?;
+    OWNER_ORDERS.save(storage, (&order.owner, order.id), &())
+}
+
+fn remove_order(storage: &mut dyn Storage, order: &Order) {
+    ORDERS.remove(storage, order.id);
+    ORDER_BOOK.remove(storage, (pair(&order.sell_denom, &order.buy_denom), order.price.atomics().u128(), order.id));
+    OWNER_ORDERS.remove(storage, (&order.owner, order.id));
+}
+
+pub fn execute_cancel_order(mut deps: DepsMut, info: MessageInfo, order_id: u64) -> Result<Response, ContractError> {
+    let order = match ORDERS.may_load(deps.storage, order_id)? {
+        Some(order) => order,
+        None => return Err(ContractError::OrderDoesNotExist { id: order_id }),
+    };
+    if info.sender != order.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    remove_order(deps.storage, &order);
+    credit_account(deps.branch(), &order.owner, &order.sell_denom, order.remaining)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_order")
+        .add_attribute("order_id", order_id.to_string())
+        .add_attribute("refunded", order.remaining))
+}
+
+pub fn query_order_book(deps: Deps, sell_denom: String, buy_denom: String, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut levels: Vec<DepthLevel> = vec![];
+    for key in ORDER_BOOK.sub_prefix(pair(&sell_denom, &buy_denom)).keys(deps.storage, None, None, StorageOrder::Ascending) {
+        let (_, id) = key?;
+        let order = ORDERS.load(deps.storage, id)?;
+        if let Some(level) = levels.last_mut().filter(|level| level.price == order.price) {
+            level.amount += order.remaining;
+            level.orders += 1;
+            continue;
+        }
+        if levels.len() == limit {
+            break;
+        }
+        levels.push(DepthLevel { price: order.price, amount: order.remaining, orders: 1 });
+    }
+    Ok(to_binary(&OrderBookResponse { levels })?)
+}
+
+pub fn query_open_orders(deps: Deps, owner: String, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let orders = OWNER_ORDERS
+        .prefix(&owner)
+        .keys(deps.storage, start, None, StorageOrder::Ascending)
+        .take(limit)
+        .map(|id| ORDERS.load(deps.storage, id?))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&OpenOrdersResponse { orders })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, query};
+    use crate::msg::{ExecuteMsg, QueryMsg};
+    use crate::testing::{balance, setup, MockDeps};
+
+    use cosmwasm_std::from_binary;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use std::str::FromStr;
+
+    // RUB seller addr0000 is paid to addr0002, USD seller addr0001 to addr0003
+    fn place(deps: &mut MockDeps, owner: &str, buy_denom: &str, amount: u128, price: &str, receiver: &str) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::PlaceOrder {
+            buy_denom: buy_denom.to_string(),
+            amount: Uint128::from(amount),
+            price: Decimal::from_str(price).unwrap(),
+            receiver: Some(receiver.to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), msg)
+    }
+
+    fn depth(deps: &MockDeps, sell_denom: &str, buy_denom: &str) -> Vec<(String, u128, u32)> {
+        let msg = QueryMsg::OrderBook { sell_denom: sell_denom.to_string(), buy_denom: buy_denom.to_string(), limit: None };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        from_binary::<OrderBookResponse>(&res).unwrap().levels
+            .into_iter()
+            .map(|level| (level.price.to_string(), level.amount.u128(), level.orders))
+            .collect()
+    }
+
+    #[test]
+    fn orders_match_with_partial_fills() {
+        let mut deps = setup();
+        // the bought token needs an account of its own
+        let err = place(&mut deps, "addr0000", "USD", 500, "0.2", "addr0000").unwrap_err();
+        assert!(matches!(err, ContractError::TokenAlreadyAssigned { .. }));
+
+        place(&mut deps, "addr0000", "USD", 500, "0.2", "addr0002").unwrap();
+        place(&mut deps, "addr0000", "USD", 200, "0.25", "addr0002").unwrap();
+        place(&mut deps, "addr0000", "USD", 100, "0.25", "addr0002").unwrap();
+        assert_eq!(vec![("0.2".to_string(), 500, 1), ("0.25".to_string(), 300, 2)], depth(&deps, "RUB", "USD"));
+        assert_eq!(Uint128::from(200u128), balance(&deps, "addr0000"));
+
+        // 150 USD buy the 500 RUB at 0.2 and 200 RUB at 0.25
+        let res = place(&mut deps, "addr0001", "RUB", 150, "4", "addr0003").unwrap();
+        let value: PlaceOrderResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(PlaceOrderResponse { order_id: 4, filled: Uint128::from(150u128), remaining: Uint128::zero() }, value);
+        assert_eq!(2, res.events.iter().filter(|event| event.ty == "trade").count());
+        assert_eq!(Uint128::from(150u128), balance(&deps, "addr0002"));
+        assert_eq!(Uint128::from(700u128), balance(&deps, "addr0003"));
+        assert_eq!(vec![("0.25".to_string(), 100, 1)], depth(&deps, "RUB", "USD"));
+
+        // asks below the resting price do not cross and rest
+        let res = place(&mut deps, "addr0001", "RUB", 100, "5", "addr0003").unwrap();
+        let value: PlaceOrderResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::zero(), value.filled);
+        assert_eq!(vec![("5".to_string(), 100, 1)], depth(&deps, "USD", "RUB"));
+
+        let msg = QueryMsg::OpenOrders { owner: "addr0000".to_string(), start_after: None, limit: None };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let orders = from_binary::<OpenOrdersResponse>(&res).unwrap().orders;
+        assert_eq!(vec![(3, Uint128::from(100u128))], orders.iter().map(|order| (order.id, order.remaining)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cancel_order() {
+        let mut deps = setup();
+        place(&mut deps, "addr0000", "USD", 500, "0.2", "addr0002").unwrap();
+        place(&mut deps, "addr0001", "RUB", 40, "5", "addr0003").unwrap();
+        assert_eq!(vec![("0.2".to_string(), 300, 1)], depth(&deps, "RUB", "USD"));
+
+        let cancel = ExecuteMsg::CancelOrder { order_id: 1 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), cancel.clone()).unwrap();
+        assert_eq!(Uint128::from(800u128), balance(&deps, "addr0000"));
+        assert!(depth(&deps, "RUB", "USD").is_empty());
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), cancel).unwrap_err();
+        assert!(matches!(err, ContractError::OrderDoesNotExist { .. }));
+    }
+
+    #[test]
+    fn cancel_partially_filled_taker() {
+        let mut deps = setup();
+        place(&mut deps, "addr0000", "USD", 500, "0.2", "addr0002").unwrap();
+
+        // 150 USD want 750 RUB, 100 USD take the 500 on the book and 50 rest
+        let res = place(&mut deps, "addr0001", "RUB", 150, "5", "addr0003").unwrap();
+        let value: PlaceOrderResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(PlaceOrderResponse { order_id: 2, filled: Uint128::from(100u128), remaining: Uint128::from(50u128) }, value);
+        assert!(depth(&deps, "RUB", "USD").is_empty());
+        assert_eq!(vec![("5".to_string(), 50, 1)], depth(&deps, "USD", "RUB"));
+
+        // the filled maker order is gone, the taker gets back what did not fill
+        let cancel = |order_id: u64| ExecuteMsg::CancelOrder { order_id };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), cancel(1)).unwrap_err();
+        assert!(matches!(err, ContractError::OrderDoesNotExist { .. }));
+        execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), cancel(2)).unwrap();
+        assert_eq!(Uint128::from(900u128), balance(&deps, "addr0001"));
+        assert_eq!(Uint128::from(500u128), balance(&deps, "addr0003"));
+        assert_eq!(Uint128::from(100u128), balance(&deps, "addr0002"));
+        assert!(depth(&deps, "USD", "RUB").is_empty());
+    }
+}
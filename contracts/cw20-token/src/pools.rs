@@ -0,0 +1,696 @@
+use cosmwasm_std::{
+    to_binary, Binary, Deps, DepsMut, Env, Event, MessageInfo, Order as StorageOrder, Response, StdResult,
+    Storage, Timestamp, Uint128, Uint256,
+};
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::helpers::{
+    assert_admin, assert_bank_operator, assert_not_frozen, assert_not_paused, consume_allowance, DEFAULT_LIMIT, MAX_LIMIT,
+};
+use crate::ibc::credit_account;
+use crate::msg::{LiquiditySharesResponse, PoolPricesResponse, SpotPriceResponse, SwapResponse};
+use crate::state::{
+    BalanceInfo, PauseScope, Pool, PoolSeed, RatePoint, BALANCES, BANKS, BANK_POOL_ROUTING, LP_SHARES, POOLS,
+    POOL_PRICES, POOL_SEEDS, TOKENS,
+};
+
+// precision of the recorded pool prices
+pub const PRICE_PRECISION: u32 = 6;
+
+pub fn pool_id(denom_a: &str, denom_b: &str) -> String {
+    if denom_a < denom_b {
+        format!("{}/{}", denom_a, denom_b)
+    } else {
+        format!("{}/{}", denom_b, denom_a)
+    }
+}
+
+fn load_pool(storage: &dyn Storage, pool_id: &str) -> Result<Pool, ContractError> {
+    match POOLS.may_load(storage, pool_id.to_string())? {
+        Some(pool) => Ok(pool),
+        None => Err(ContractError::PoolDoesNotExist { id: pool_id.to_string() }),
+    }
+}
+
+// Saves the pool and records its new spot price
+fn save_pool(storage: &mut dyn Storage, pool: &Pool, now: Timestamp) -> StdResult<()> {
+    POOLS.save(storage, pool.id.clone(), pool)?;
+    if pool.reserve_a.is_zero() || pool.reserve_b.is_zero() {
+        return Ok(());
+    }
+    let rate = pool.reserve_b.multiply_ratio(10_u128.pow(PRICE_PRECISION), pool.reserve_a);
+    let point = RatePoint {
+        rate: u64::try_from(rate.u128()).unwrap_or(u64::MAX),
+        precision: PRICE_PRECISION,
+        timestamp: now,
+    };
+    POOL_PRICES.save(storage, (pool.id.clone(), now.seconds()), &point)
+}
+
+fn isqrt(n: Uint256) -> Uint256 {
+    if n.is_zero() {
+        return n;
+    }
+    let two = Uint256::from(2u8);
+    let mut x = n;
+    let mut y = (x + Uint256::one()) / two;
+    while y < x {
+        x = y;
+        y = (x + n / x) / two;
+    }
+    x
+}
+
+// The first shares are the square root of the reserves product
+fn seed_shares(amount_a: Uint128, amount_b: Uint128) -> StdResult<Uint128> {
+    Ok(isqrt(amount_a.full_mul(amount_b)).try_into()?)
+}
+
+// Shares grow with the square root of the reserves product, the other reserve does not change
+fn join_shares(total_shares: Uint128, reserve: Uint128, deposit: Uint128) -> StdResult<Uint128> {
+    let root: Uint128 = isqrt(reserve.full_mul(reserve + deposit)).try_into()?;
+    Ok((total_shares.full_mul(root - reserve) / Uint256::from(reserve)).try_into()?)
+}
+
+pub fn execute_create_pool(deps: DepsMut, info: MessageInfo, denom_a: String, denom_b: String, fee_bps: u64) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info.sender)?;
+    if fee_bps > 10_000 {
+        return Err(ContractError::InvalidFee { fee_bps });
+    }
+    for denom in [&denom_a, &denom_b] {
+        if !TOKENS.has(deps.storage, denom.clone()) {
+            return Err(ContractError::TokenNotRegistered { denom: denom.clone() });
+        }
+    }
+    if denom_a == denom_b {
+        return Err(ContractError::InvalidSwap { reason: "a pool needs two different tokens".to_string() });
+    }
+    let id = pool_id(&denom_a, &denom_b);
+    if POOLS.has(deps.storage, id.clone()) {
+        return Err(ContractError::PoolAlreadyExists { id });
+    }
+    let (denom_a, denom_b) = if denom_a < denom_b { (denom_a, denom_b) } else { (denom_b, denom_a) };
+    let pool = Pool {
+        id: id.clone(),
+        denom_a,
+        denom_b,
+        reserve_a: Uint128::zero(),
+        reserve_b: Uint128::zero(),
+        total_shares: Uint128::zero(),
+        fee_bps,
+    };
+    POOLS.save(deps.storage, id.clone(), &pool)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_pool")
+        .add_attribute("pool_id", id)
+        .add_attribute("fee_bps", fee_bps.to_string()))
+}
+
+// Checks the sender can pay amount of the pool token it holds, and returns it
+fn sender_balance(deps: Deps, info: &MessageInfo, pool: &Pool, amount: Uint128) -> Result<BalanceInfo, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    let balance = match BALANCES.may_load(deps.storage, &info.sender)? {
+        Some(balance) => balance,
+        None => return Err(ContractError::AccountDoesNotExist { account: info.sender.to_string() }),
+    };
+    if !pool.has(&balance.denom) {
+        return Err(ContractError::TokenAlreadyAssigned { denom: balance.denom });
+    }
+    if balance.amount < amount {
+        return Err(ContractError::NotEnoughBalance { required: amount, available: balance.amount });
+    }
+    assert_not_paused(deps.storage, &[
+        PauseScope::Denom { denom: pool.denom_a.clone() },
+        PauseScope::Denom { denom: pool.denom_b.clone() },
+    ])?;
+    assert_not_frozen(deps.storage, &info.sender, &balance.denom)?;
+    Ok(balance)
+}
+
+pub fn execute_add_liquidity(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    amount: Uint128,
+    min_shares: Option<Uint128>,
+    other_amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let mut pool = load_pool(deps.storage, &pool_id)?;
+    let balance = sender_balance(deps.as_ref(), &info, &pool, amount)?;
+    let denom = balance.denom.clone();
+    let reserve = pool.reserve(&denom);
+
+    // an empty pool takes both tokens at once, so no outside price values the first deposit
+    let (shares, seed) = if pool.total_shares.is_zero() {
+        match POOL_SEEDS.may_load(deps.storage, pool_id.clone())? {
+            None => return seed_pool(deps, env, info, pool_id, balance, amount, other_amount),
+            Some(seed) if seed.denom == denom || seed.other_amount != amount => {
+                let reason = format!("the first deposit waits for {}{}", seed.other_amount, pool.other(&seed.denom));
+                return Err(ContractError::InvalidSwap { reason });
+            },
+            Some(seed) => {
+                // both sides are worth the same at the seeded price, so they get half of the shares each
+                let total = seed_shares(seed.amount, amount)?;
+                let half = total.multiply_ratio(1u128, 2u128);
+                (total - half, Some((seed, half)))
+            },
+        }
+    } else {
+        // a single sided deposit swaps about half of it, which pays the fee
+        let deposit = amount - amount.multiply_ratio(pool.fee_bps, 20_000u128);
+        (join_shares(pool.total_shares, reserve, deposit)?, None)
+    };
+    if shares.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    if let Some(min) = min_shares.filter(|min| shares < *min) {
+        return Err(ContractError::SlippageExceeded { received: shares, min });
+    }
+    consume_allowance(deps.storage, &info.sender, &denom, amount, env.block.time)?;
+
+    BALANCES.save(deps.storage, &info.sender, &BalanceInfo { amount: balance.amount - amount, denom: denom.clone() })?;
+    pool.set_reserve(&denom, reserve + amount);
+    pool.total_shares += shares;
+    if let Some((seed, seed_shares)) = seed {
+        POOL_SEEDS.remove(deps.storage, pool_id.clone());
+        pool.set_reserve(&seed.denom, seed.amount);
+        pool.total_shares += seed_shares;
+        LP_SHARES.update(deps.storage, (pool_id.clone(), &seed.provider), |held| -> StdResult<_> {
+            Ok(held.unwrap_or_default() + seed_shares)
+        })?;
+    }
+    save_pool(deps.storage, &pool, env.block.time)?;
+    LP_SHARES.update(deps.storage, (pool_id.clone(), &info.sender), |held| -> StdResult<_> {
+        Ok(held.unwrap_or_default() + shares)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_liquidity")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("deposit", format!("{}{}", amount, denom))
+        .add_attribute("shares", shares))
+}
+
+// Escrows the first deposit of an empty pool until the other token matches it
+fn seed_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    balance: BalanceInfo,
+    amount: Uint128,
+    other_amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let other_amount = match other_amount.filter(|other_amount| !other_amount.is_zero()) {
+        Some(other_amount) => other_amount,
+        None => return Err(ContractError::InvalidSwap { reason: "the first deposit needs other_amount".to_string() }),
+    };
+    consume_allowance(deps.storage, &info.sender, &balance.denom, amount, env.block.time)?;
+
+    BALANCES.save(deps.storage, &info.sender, &BalanceInfo { amount: balance.amount - amount, denom: balance.denom.clone() })?;
+    POOL_SEEDS.save(deps.storage, pool_id.clone(), &PoolSeed {
+        provider: info.sender,
+        denom: balance.denom.clone(),
+        amount,
+        other_amount,
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "seed_pool")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("deposit", format!("{}{}", amount, balance.denom))
+        .add_attribute("other_amount", other_amount))
+}
+
+pub fn execute_cancel_pool_seed(deps: DepsMut, info: MessageInfo, pool_id: String) -> Result<Response, ContractError> {
+    let seed = match POOL_SEEDS.may_load(deps.storage, pool_id.clone())? {
+        Some(seed) => seed,
+        None => return Err(ContractError::InvalidSwap { reason: "the pool has no pending first deposit".to_string() }),
+    };
+    if seed.provider != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    POOL_SEEDS.remove(deps.storage, pool_id.clone());
+    credit_account(deps, &seed.provider, &seed.denom, seed.amount)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_pool_seed")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("refund", format!("{}{}", seed.amount, seed.denom)))
+}
+
+pub fn execute_remove_liquidity(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    shares: Uint128,
+    receiver: Option<String>,
+) -> Result<Response, ContractError> {
+    if shares.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    let mut pool = load_pool(deps.storage, &pool_id)?;
+    let held = LP_SHARES.may_load(deps.storage, (pool_id.clone(), &info.sender))?.unwrap_or_default();
+    if held < shares {
+        return Err(ContractError::NotEnoughBalance { required: shares, available: held });
+    }
+    // the sender takes its side of the pool, the other side goes to receiver
+    let denom = match BALANCES.may_load(deps.storage, &info.sender)? {
+        Some(balance) if pool.has(&balance.denom) => balance.denom,
+        Some(balance) => return Err(ContractError::TokenAlreadyAssigned { denom: balance.denom }),
+        None => return Err(ContractError::AccountDoesNotExist { account: info.sender.to_string() }),
+    };
+    let other = pool.other(&denom);
+    let receiver = match receiver {
+        Some(receiver) => deps.api.addr_validate(&receiver)?,
+        None => info.sender.clone(),
+    };
+    assert_not_frozen(deps.storage, &info.sender, &denom)?;
+    assert_not_frozen(deps.storage, &receiver, &other)?;
+    let out = pool.reserve(&denom).multiply_ratio(shares, pool.total_shares);
+    let out_other = pool.reserve(&other).multiply_ratio(shares, pool.total_shares);
+    // the receiver has to take the other token before anything is written
+    if !out_other.is_zero() {
+        credit_account(deps.branch(), &receiver, &other, Uint128::zero())?;
+    }
+
+    pool.set_reserve(&denom, pool.reserve(&denom) - out);
+    pool.set_reserve(&other, pool.reserve(&other) - out_other);
+    pool.total_shares -= shares;
+    save_pool(deps.storage, &pool, env.block.time)?;
+    if held == shares {
+        LP_SHARES.remove(deps.storage, (pool_id.clone(), &info.sender));
+    } else {
+        LP_SHARES.save(deps.storage, (pool_id.clone(), &info.sender), &(held - shares))?;
+    }
+
+    credit_account(deps.branch(), &info.sender, &denom, out)?;
+    if !out_other.is_zero() {
+        credit_account(deps.branch(), &receiver, &other, out_other)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_liquidity")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("shares", shares)
+        .add_attribute("withdrawn", format!("{}{},{}{}", out, denom, out_other, other)))
+}
+
+// Sells amount of denom_from to the pool and returns what it pays out of the other reserve
+pub fn swap(storage: &mut dyn Storage, now: Timestamp, pool: &mut Pool, denom_from: &str, amount: Uint128) -> Result<(Uint128, Event), ContractError> {
+    let denom_to = pool.other(denom_from);
+    let received = pool.swap_out(denom_from, amount);
+    if received.is_zero() {
+        return Err(ContractError::InsufficientLiquidity { id: pool.id.clone() });
+    }
+    pool.set_reserve(denom_from, pool.reserve(denom_from) + amount);
+    pool.set_reserve(&denom_to, pool.reserve(&denom_to) - received);
+    save_pool(storage, pool, now)?;
+
+    let event = Event::new("swap")
+        .add_attribute("pool_id", pool.id.clone())
+        .add_attribute("sold", format!("{}{}", amount, denom_from))
+        .add_attribute("bought", format!("{}{}", received, denom_to));
+    Ok((received, event))
+}
+
+pub fn execute_swap(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pool_id: String,
+    amount: Uint128,
+    min_received: Option<Uint128>,
+    receiver: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut pool = load_pool(deps.storage, &pool_id)?;
+    let balance = sender_balance(deps.as_ref(), &info, &pool, amount)?;
+    let denom = balance.denom.clone();
+    let other = pool.other(&denom);
+    let receiver = match receiver {
+        Some(receiver) => deps.api.addr_validate(&receiver)?,
+        None => info.sender.clone(),
+    };
+    assert_not_frozen(deps.storage, &receiver, &other)?;
+    let quote = pool.swap_out(&denom, amount);
+    if let Some(min) = min_received.filter(|min| quote < *min) {
+        return Err(ContractError::SlippageExceeded { received: quote, min });
+    }
+    credit_account(deps.branch(), &receiver, &other, Uint128::zero())?;
+    consume_allowance(deps.storage, &info.sender, &denom, amount, env.block.time)?;
+
+    BALANCES.save(deps.storage, &info.sender, &BalanceInfo { amount: balance.amount - amount, denom: denom.clone() })?;
+    let (received, event) = swap(deps.storage, env.block.time, &mut pool, &denom, amount)?;
+    credit_account(deps.branch(), &receiver, &other, received)?;
+
+    Ok(Response::new()
+        .set_data(to_binary(&SwapResponse { received })?)
+        .add_event(event)
+        .add_attribute("action", "swap")
+        .add_attribute("pool_id", pool_id)
+        .add_attribute("received", received))
+}
+
+pub fn execute_set_bank_pool_routing(deps: DepsMut, info: MessageInfo, bank_id: String, max_slippage_bps: Option<u64>) -> Result<Response, ContractError> {
+    if !BANKS.has(deps.storage, bank_id.clone()) {
+        return Err(ContractError::BankNotRegistered { id: bank_id });
+    }
+    assert_bank_operator(deps.as_ref(), &info.sender, &bank_id)?;
+    match max_slippage_bps {
+        Some(bps) if bps > 10_000 => return Err(ContractError::InvalidSwap { reason: "slippage above 10000 bps".to_string() }),
+        Some(bps) => BANK_POOL_ROUTING.save(deps.storage, bank_id.clone(), &bps)?,
+        None => BANK_POOL_ROUTING.remove(deps.storage, bank_id.clone()),
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_bank_pool_routing")
+        .add_attribute("bank_id", bank_id)
+        .add_attribute("max_slippage_bps", max_slippage_bps.map(|bps| bps.to_string()).unwrap_or_default()))
+}
+
+// Pool settlements of the bank are converted through, if it opted in and the pool is priced,
+// along with the slippage the bank accepts
+pub fn routed_pool(storage: &dyn Storage, bank_id: &str, denom_from: &str, denom_to: &str) -> StdResult<Option<(Pool, u64)>> {
+    let max_slippage_bps = match BANK_POOL_ROUTING.may_load(storage, bank_id.to_string())? {
+        Some(bps) => bps,
+        None => return Ok(None),
+    };
+    Ok(POOLS
+        .may_load(storage, pool_id(denom_from, denom_to))?
+        .filter(|pool| pool.spot_price(denom_from).is_some())
+        .map(|pool| (pool, max_slippage_bps)))
+}
+
+pub fn query_pool(deps: Deps, pool_id: String) -> Result<Binary, ContractError> {
+    Ok(to_binary(&load_pool(deps.storage, &pool_id)?)?)
+}
+
+pub fn query_pool_seed(deps: Deps, pool_id: String) -> Result<Binary, ContractError> {
+    load_pool(deps.storage, &pool_id)?;
+    Ok(to_binary(&POOL_SEEDS.may_load(deps.storage, pool_id)?)?)
+}
+
+pub fn query_spot_price(deps: Deps, pool_id: String, denom_from: String) -> Result<Binary, ContractError> {
+    let pool = load_pool(deps.storage, &pool_id)?;
+    if !pool.has(&denom_from) {
+        return Err(ContractError::TokenNotRegistered { denom: denom_from });
+    }
+    Ok(to_binary(&SpotPriceResponse { price: pool.spot_price(&denom_from) })?)
+}
+
+pub fn query_liquidity_shares(deps: Deps, pool_id: String, address: String) -> Result<Binary, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let pool = load_pool(deps.storage, &pool_id)?;
+    let shares = LP_SHARES.may_load(deps.storage, (pool_id, &address))?.unwrap_or_default();
+    Ok(to_binary(&LiquiditySharesResponse { shares, total_shares: pool.total_shares })?)
+}
+
+pub fn query_pool_prices(deps: Deps, pool_id: String, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let prices = POOL_PRICES
+        .prefix(pool_id)
+        .range(deps.storage, start, None, StorageOrder::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, point)| point))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&PoolPricesResponse { prices })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, query};
+    use crate::msg::{BankInfoResponse, ExchangeRateMsg, ExecuteMsg, QueryMsg, TransactionInfoResponse, TransactionMsg};
+    use crate::state::{BankInfo, RouteDecision};
+    use crate::testing::{self, balance, MockDeps};
+
+    use cosmwasm_std::from_binary;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{Addr, Decimal};
+
+    // a RUBUSD rate of 0.2 and a RUB/USD pool with a 1% fee
+    fn setup() -> MockDeps {
+        let mut deps = testing::setup();
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::SetExchangeRate(ExchangeRateMsg {
+            denom_from: "RUB".to_string(),
+            denom_to: "USD".to_string(),
+            precision: 3,
+            rate: 200,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::CreatePool { denom_a: "USD".to_string(), denom_b: "RUB".to_string(), fee_bps: 100 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::PoolAlreadyExists { .. }));
+        deps
+    }
+
+    fn add_liquidity(deps: &mut MockDeps, provider: &str, amount: u128, other_amount: Option<u128>) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::AddLiquidity {
+            pool_id: "RUB/USD".to_string(),
+            amount: Uint128::from(amount),
+            min_shares: None,
+            other_amount: other_amount.map(Uint128::from),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(provider, &[]), msg)
+    }
+
+    fn pool(deps: &MockDeps) -> Pool {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Pool { pool_id: "RUB/USD".to_string() }).unwrap();
+        from_binary(&res).unwrap()
+    }
+
+    fn shares(deps: &MockDeps, address: &str) -> Uint128 {
+        let msg = QueryMsg::LiquidityShares { pool_id: "RUB/USD".to_string(), address: address.to_string() };
+        from_binary::<LiquiditySharesResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().shares
+    }
+
+    fn spot_price(deps: &MockDeps) -> Option<Decimal> {
+        let msg = QueryMsg::SpotPrice { pool_id: "RUB/USD".to_string(), denom_from: "RUB".to_string() };
+        from_binary::<SpotPriceResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().price
+    }
+
+    #[test]
+    fn liquidity_and_swaps() {
+        let mut deps = setup();
+
+        // the first deposit names the other side and waits for it, sqrt(500 * 100) shares are split
+        add_liquidity(&mut deps, "addr0000", 500, Some(100)).unwrap();
+        assert_eq!(None, spot_price(&deps));
+        assert_eq!(Uint128::from(500u128), balance(&deps, "addr0000"));
+        add_liquidity(&mut deps, "addr0001", 100, None).unwrap();
+        assert_eq!(Uint128::from(111u128), shares(&deps, "addr0000"));
+        assert_eq!(Uint128::from(112u128), shares(&deps, "addr0001"));
+        assert_eq!(Some(Decimal::from_ratio(1u128, 5u128)), spot_price(&deps));
+
+        // 1 RUB of fee stays in the pool, 99 buy 100 * 99 / 599 USD
+        let msg = ExecuteMsg::Swap {
+            pool_id: "RUB/USD".to_string(),
+            amount: Uint128::from(100u128),
+            min_received: Some(Uint128::from(17u128)),
+            receiver: Some("addr0002".to_string()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::SlippageExceeded { .. }));
+        let msg = ExecuteMsg::Swap {
+            pool_id: "RUB/USD".to_string(),
+            amount: Uint128::from(100u128),
+            min_received: Some(Uint128::from(16u128)),
+            receiver: Some("addr0002".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+        assert_eq!(Uint128::from(16u128), from_binary::<SwapResponse>(&res.data.unwrap()).unwrap().received);
+        assert_eq!(Uint128::from(16u128), balance(&deps, "addr0002"));
+        assert_eq!(Uint128::from(400u128), balance(&deps, "addr0000"));
+        let value = pool(&deps);
+        assert_eq!((Uint128::from(600u128), Uint128::from(84u128)), (value.reserve_a, value.reserve_b));
+
+        // the latest price of the block is recorded
+        let msg = QueryMsg::PoolPrices { pool_id: "RUB/USD".to_string(), start_after: None, limit: None };
+        let prices = from_binary::<PoolPricesResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().prices;
+        assert_eq!(vec![(140_000, 6)], prices.iter().map(|point| (point.rate, point.precision)).collect::<Vec<_>>());
+
+        // single sided deposit once priced, 223 * (sqrt(600 * 700) - 600) / 600
+        add_liquidity(&mut deps, "addr0000", 100, None).unwrap();
+        assert_eq!(Uint128::from(128u128), shares(&deps, "addr0000"));
+        assert_eq!(Uint128::from(240u128), pool(&deps).total_shares);
+
+        // the RUB side goes to a RUB account
+        let remove = |receiver: Option<&str>, shares: u128| ExecuteMsg::RemoveLiquidity {
+            pool_id: "RUB/USD".to_string(),
+            shares: Uint128::from(shares),
+            receiver: receiver.map(str::to_string),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), remove(Some("addr0003"), 113)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), remove(None, 112)).unwrap_err();
+        assert!(matches!(err, ContractError::TokenAlreadyAssigned { .. }));
+        execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), remove(Some("addr0003"), 112)).unwrap();
+        assert_eq!(Uint128::from(939u128), balance(&deps, "addr0001"));
+        assert_eq!(Uint128::from(326u128), balance(&deps, "addr0003"));
+        assert_eq!(Uint128::zero(), shares(&deps, "addr0001"));
+        let value = pool(&deps);
+        assert_eq!((Uint128::from(374u128), Uint128::from(45u128)), (value.reserve_a, value.reserve_b));
+    }
+
+    #[test]
+    fn withdraw_last_shares() {
+        let mut deps = setup();
+        add_liquidity(&mut deps, "addr0000", 500, Some(100)).unwrap();
+        add_liquidity(&mut deps, "addr0001", 100, None).unwrap();
+        let remove = |receiver: &str, shares: u128| ExecuteMsg::RemoveLiquidity {
+            pool_id: "RUB/USD".to_string(),
+            shares: Uint128::from(shares),
+            receiver: Some(receiver.to_string()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), remove("addr0003", 0)).unwrap_err();
+        assert!(matches!(err, ContractError::ZeroAmount {}));
+        execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), remove("addr0003", 112)).unwrap();
+
+        // the last provider cannot take more than its shares, and its shares take the whole pool
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), remove("addr0002", 112)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+        execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), remove("addr0002", 111)).unwrap();
+        let value = pool(&deps);
+        assert_eq!((Uint128::zero(), Uint128::zero(), Uint128::zero()), (value.reserve_a, value.reserve_b, value.total_shares));
+        assert_eq!((Uint128::from(749u128), Uint128::from(251u128)), (balance(&deps, "addr0000"), balance(&deps, "addr0003")));
+        assert_eq!((Uint128::from(950u128), Uint128::from(50u128)), (balance(&deps, "addr0001"), balance(&deps, "addr0002")));
+        assert_eq!(Uint128::zero(), shares(&deps, "addr0000"));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), remove("addr0002", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+
+        // the empty pool is seeded again
+        add_liquidity(&mut deps, "addr0000", 100, Some(20)).unwrap();
+        add_liquidity(&mut deps, "addr0001", 20, None).unwrap();
+        assert_eq!(Some(Decimal::from_ratio(1u128, 5u128)), spot_price(&deps));
+    }
+
+    #[test]
+    fn seed_pool() {
+        let mut deps = setup();
+        let msg = ExecuteMsg::CreatePool { denom_a: "USD".to_string(), denom_b: "USD".to_string(), fee_bps: 100 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwap { .. }));
+
+        // the first deposit needs both sides, the other one has to match it
+        let err = add_liquidity(&mut deps, "addr0000", 500, None).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwap { .. }));
+        add_liquidity(&mut deps, "addr0000", 500, Some(100)).unwrap();
+        let err = add_liquidity(&mut deps, "addr0000", 100, Some(20)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwap { .. }));
+        let err = add_liquidity(&mut deps, "addr0001", 90, None).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwap { .. }));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolSeed { pool_id: "RUB/USD".to_string() }).unwrap();
+        let seed = from_binary::<Option<PoolSeed>>(&res).unwrap().unwrap();
+        assert_eq!((Addr::unchecked("addr0000"), Uint128::from(100u128)), (seed.provider, seed.other_amount));
+
+        // shares of an unseeded pool are not there to remove
+        let remove = ExecuteMsg::RemoveLiquidity { pool_id: "RUB/USD".to_string(), shares: Uint128::one(), receiver: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), remove).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughBalance { .. }));
+
+        let cancel = ExecuteMsg::CancelPoolSeed { pool_id: "RUB/USD".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), cancel.clone()).unwrap();
+        assert_eq!(Uint128::from(1000u128), balance(&deps, "addr0000"));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), cancel).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwap { .. }));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PoolSeed { pool_id: "RUB/USD".to_string() }).unwrap();
+        assert_eq!(None, from_binary::<Option<PoolSeed>>(&res).unwrap());
+    }
+
+    #[test]
+    fn settlement_through_pool() {
+        let mut deps = setup();
+        let admin = mock_info("creator", &[]);
+        add_liquidity(&mut deps, "addr0000", 400, Some(100)).unwrap();
+        add_liquidity(&mut deps, "addr0001", 100, None).unwrap();
+        let msg = ExecuteMsg::CreateBank(BankInfo { id: "bank0000".to_string(), name: "Bank".to_string(), balance: Uint128::from(1000u128) });
+        execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetBankFee { bank_id: "bank0000".to_string(), fee_bps: 1000 };
+        execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap();
+
+        let routing = |max_slippage_bps: u64| ExecuteMsg::SetBankPoolRouting {
+            bank_id: "bank0000".to_string(),
+            max_slippage_bps: Some(max_slippage_bps),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), routing(0)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(deps.as_mut(), mock_env(), admin.clone(), routing(10_001)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwap { .. }));
+        execute(deps.as_mut(), mock_env(), admin.clone(), routing(0)).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+        assert_eq!(Some(0), from_binary::<BankInfoResponse>(&res).unwrap().pool_routing);
+
+        let msg = ExecuteMsg::SendToBank(TransactionMsg {
+            bank_id: Some("bank0000".to_string()),
+            from: Addr::unchecked("addr0000"),
+            to: Addr::unchecked("addr0001"),
+            amount: Uint128::from(100u128),
+            idempotency_key: None,
+        });
+        execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap();
+        // the route is quoted the way the settlement pays
+        let msg = QueryMsg::RoutePreview { from: "addr0000".to_string(), to: "addr0001".to_string(), amount: Uint128::from(100u128) };
+        let err = query(deps.as_ref(), mock_env(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NoRouteAvailable { .. }));
+        execute(deps.as_mut(), mock_env(), admin.clone(), routing(500)).unwrap();
+        let decision = from_binary::<RouteDecision>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!((Uint128::from(17u128), Uint128::from(100u128)), (decision.chosen.received, decision.chosen.reserves));
+        execute(deps.as_mut(), mock_env(), admin.clone(), routing(0)).unwrap();
+
+        // the pool pays less than the exchange rate, which the bank does not accept without slippage
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "1".to_string(), amount: None };
+        let err = execute(deps.as_mut(), mock_env(), admin.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::SlippageExceeded { .. }));
+        execute(deps.as_mut(), mock_env(), admin.clone(), routing(500)).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap();
+        assert_eq!("swap", res.events[0].ty);
+
+        // the pool pays 100 * 99 / 499 instead of the 20 of the exchange rate, the bank keeps 10% of it
+        assert_eq!(Uint128::from(917u128), balance(&deps, "addr0001"));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::TransactionInfo { id: "1".to_string() }).unwrap();
+        let tranche = from_binary::<TransactionInfoResponse>(&res).unwrap().tranches[0].clone();
+        assert_eq!((Uint128::from(17u128), 190_000, 6), (tranche.received, tranche.rate, tranche.precision));
+        let value = pool(&deps);
+        assert_eq!((Uint128::from(500u128), Uint128::from(81u128)), (value.reserve_a, value.reserve_b));
+
+        // no USD is lost, it is held by the recipient, the pool and the bank
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+        let bank = from_binary::<BankInfoResponse>(&res).unwrap();
+        let reserves = bank.reserves.iter().find(|reserve| reserve.denom == "USD").unwrap().amount;
+        assert_eq!(Uint128::from(2u128), reserves);
+        assert_eq!(Uint128::from(1000u128), balance(&deps, "addr0001") + value.reserve_b + reserves);
+
+        // a tranche the pool pays nothing for fails before the bank books it
+        let msg = ExecuteMsg::SendToBank(TransactionMsg {
+            bank_id: Some("bank0000".to_string()),
+            from: Addr::unchecked("addr0000"),
+            to: Addr::unchecked("addr0001"),
+            amount: Uint128::one(),
+            idempotency_key: None,
+        });
+        execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap();
+        execute(deps.as_mut(), mock_env(), admin.clone(), routing(10_000)).unwrap();
+        let msg = ExecuteMsg::SendToRecipient { transaction_id: "2".to_string(), amount: None };
+        let err = execute(deps.as_mut(), mock_env(), admin, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientLiquidity { .. }));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::BankInfo { id: "bank0000".to_string() }).unwrap();
+        assert_eq!(bank.balance + Uint128::one(), from_binary::<BankInfoResponse>(&res).unwrap().balance);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::TransactionInfo { id: "2".to_string() }).unwrap();
+        assert_eq!(Uint128::zero(), from_binary::<TransactionInfoResponse>(&res).unwrap().settled);
+    }
+}
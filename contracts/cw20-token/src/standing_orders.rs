@@ -0,0 +1,330 @@
+use cosmwasm_std::{
+    from_binary, to_binary, Binary, Deps, DepsMut, Env, Event, MessageInfo, Order as StorageOrder, Response,
+    StdResult,
+};
+use cw_storage_plus::Bound;
+
+use crate::contract::execute::execute_send_to_bank;
+use crate::error::ContractError;
+use crate::helpers::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::msg::{
+    StandingOrderHistoryResponse, StandingOrderMsg, StandingOrdersResponse, TransactionIdResponse, TransactionMsg,
+    UpcomingExecution, UpcomingExecutionsResponse,
+};
+use crate::state::{
+    StandingOrder, StandingOrderExecution, StandingOrderStatus, BALANCES, BANKS, OWNER_STANDING_ORDERS,
+    STANDING_ORDERS, STANDING_ORDER_COUNT, STANDING_ORDER_HISTORY, STANDING_ORDER_SCHEDULE,
+};
+
+fn load_order(deps: Deps, id: u64) -> Result<StandingOrder, ContractError> {
+    match STANDING_ORDERS.may_load(deps.storage, id)? {
+        Some(order) => Ok(order),
+        None => Err(ContractError::StandingOrderDoesNotExist { id }),
+    }
+}
+
+pub fn execute_create_standing_order(deps: DepsMut, env: Env, info: MessageInfo, msg: StandingOrderMsg) -> Result<Response, ContractError> {
+    let to = deps.api.addr_validate(&msg.to)?;
+    if msg.amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    if msg.interval == 0 {
+        return Err(ContractError::InvalidStandingOrder { reason: "interval must not be zero".to_string() });
+    }
+    if msg.start < env.block.time {
+        return Err(ContractError::InvalidStandingOrder { reason: "start has already passed".to_string() });
+    }
+    if msg.end.is_some_and(|end| end < msg.start) {
+        return Err(ContractError::InvalidStandingOrder { reason: "end is before start".to_string() });
+    }
+    match BALANCES.may_load(deps.storage, &info.sender)? {
+        Some(balance) if balance.denom != msg.denom => return Err(ContractError::TokenAlreadyAssigned { denom: balance.denom }),
+        Some(_) => {},
+        None => return Err(ContractError::AccountDoesNotExist { account: info.sender.to_string() }),
+    }
+    if let Some(bank_id) = msg.bank_id.clone().filter(|bank_id| !BANKS.has(deps.storage, bank_id.clone())) {
+        return Err(ContractError::BankNotRegistered { id: bank_id });
+    }
+
+    let id = STANDING_ORDER_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    STANDING_ORDER_COUNT.save(deps.storage, &id)?;
+    let order = StandingOrder {
+        id,
+        owner: info.sender,
+        to,
+        amount: msg.amount,
+        denom: msg.denom,
+        bank_id: msg.bank_id,
+        interval: msg.interval,
+        next_execution: msg.start,
+        end: msg.end,
+        status: StandingOrderStatus::Active,
+        executed: 0,
+        failed: 0,
+    };
+    STANDING_ORDERS.save(deps.storage, id, &order)?;
+    OWNER_STANDING_ORDERS.save(deps.storage, (&order.owner, id), &())?;
+    STANDING_ORDER_SCHEDULE.save(deps.storage, (order.next_execution.seconds(), id), &())?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_standing_order")
+        .add_attribute("order_id", id.to_string())
+        .add_attribute("next_execution", order.next_execution.seconds().to_string()))
+}
+
+pub fn execute_cancel_standing_order(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+    let mut order = load_order(deps.as_ref(), id)?;
+    if info.sender != order.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if order.status != StandingOrderStatus::Active {
+        return Err(ContractError::StandingOrderNotActive { id });
+    }
+    STANDING_ORDER_SCHEDULE.remove(deps.storage, (order.next_execution.seconds(), id));
+    order.status = StandingOrderStatus::Cancelled;
+    STANDING_ORDERS.save(deps.storage, id, &order)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_standing_order")
+        .add_attribute("order_id", id.to_string()))
+}
+
+// Each due order runs once through SendToBank, a failed run is recorded and skipped,
+// the order moves to its next execution either way
+pub fn execute_crank(mut deps: DepsMut, env: Env, info: MessageInfo, limit: Option<u32>) -> Result<Response, ContractError> {
+    // the orders executed per crank are bounded like a page
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let due = STANDING_ORDER_SCHEDULE
+        .keys(deps.storage, None, Some(Bound::inclusive((env.block.time.seconds(), u64::MAX))), StorageOrder::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut events = vec![];
+    let mut failed = 0;
+    for (scheduled_at, id) in due.iter().copied() {
+        let mut order = STANDING_ORDERS.load(deps.storage, id)?;
+        let transaction = TransactionMsg {
+            bank_id: order.bank_id.clone(),
+            from: order.owner.clone(),
+            to: order.to.clone(),
+            amount: order.amount,
+            idempotency_key: None,
+        };
+        // SendToBank fails before its first write, a short balance leaves nothing behind
+        let result = execute_send_to_bank(deps.branch(), env.clone(), info.clone(), transaction)
+            .and_then(|res| Ok(from_binary::<TransactionIdResponse>(&res.data.unwrap_or_default())?.transaction_id));
+        let mut event = Event::new("standing_order")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("scheduled_at", scheduled_at.to_string());
+        let (transaction_id, error) = match result {
+            Ok(transaction_id) => {
+                order.executed += 1;
+                event = event.add_attribute("transaction_id", transaction_id.clone());
+                (Some(transaction_id), None)
+            },
+            Err(err) => {
+                order.failed += 1;
+                failed += 1;
+                event = event.add_attribute("error", err.to_string());
+                (None, Some(err.to_string()))
+            },
+        };
+        STANDING_ORDER_HISTORY.save(deps.storage, (id, scheduled_at), &StandingOrderExecution {
+            scheduled_at: order.next_execution,
+            executed_at: env.block.time,
+            transaction_id,
+            error,
+        })?;
+
+        STANDING_ORDER_SCHEDULE.remove(deps.storage, (scheduled_at, id));
+        order.next_execution = order.next_execution.plus_seconds(order.interval);
+        if order.end.is_some_and(|end| order.next_execution > end) {
+            order.status = StandingOrderStatus::Completed;
+        } else {
+            STANDING_ORDER_SCHEDULE.save(deps.storage, (order.next_execution.seconds(), id), &())?;
+        }
+        STANDING_ORDERS.save(deps.storage, id, &order)?;
+        events.push(event);
+    }
+
+    Ok(Response::new()
+        .add_events(events)
+        .add_attribute("action", "crank")
+        .add_attribute("executed", (due.len() - failed).to_string())
+        .add_attribute("failed", failed.to_string()))
+}
+
+pub fn query_standing_order(deps: Deps, id: u64) -> Result<Binary, ContractError> {
+    Ok(to_binary(&load_order(deps, id)?)?)
+}
+
+pub fn query_standing_orders(deps: Deps, owner: String, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let orders = OWNER_STANDING_ORDERS
+        .prefix(&owner)
+        .keys(deps.storage, start, None, StorageOrder::Ascending)
+        .take(limit)
+        .map(|id| STANDING_ORDERS.load(deps.storage, id?))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&StandingOrdersResponse { orders })?)
+}
+
+pub fn query_upcoming_executions(deps: Deps, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let executions = STANDING_ORDER_SCHEDULE
+        .keys(deps.storage, None, None, StorageOrder::Ascending)
+        .take(limit)
+        .map(|key| {
+            let (_, order_id) = key?;
+            let order = STANDING_ORDERS.load(deps.storage, order_id)?;
+            Ok(UpcomingExecution { order_id, scheduled_at: order.next_execution })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&UpcomingExecutionsResponse { executions })?)
+}
+
+pub fn query_standing_order_history(deps: Deps, id: u64, start_after: Option<u64>, limit: Option<u32>) -> Result<Binary, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let executions = STANDING_ORDER_HISTORY
+        .prefix(id)
+        .range(deps.storage, start, None, StorageOrder::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, execution)| execution))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&StandingOrderHistoryResponse { executions })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, query};
+    use crate::msg::{ExecuteMsg, QueryMsg};
+    use crate::state::BankInfo;
+    use crate::testing::{self, balance, MockDeps};
+
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{Timestamp, Uint128};
+
+    // the shared setup and bank0000
+    fn setup() -> MockDeps {
+        let mut deps = testing::setup();
+        let msg = ExecuteMsg::CreateBank(BankInfo { id: "bank0000".to_string(), name: "Bank".to_string(), balance: Uint128::from(1000u128) });
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        deps
+    }
+
+    fn at(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    fn order_msg(amount: u128, denom: &str, bank_id: &str, interval: u64, start: u64, end: Option<u64>) -> ExecuteMsg {
+        ExecuteMsg::CreateStandingOrder(StandingOrderMsg {
+            to: "addr0001".to_string(),
+            amount: Uint128::from(amount),
+            denom: denom.to_string(),
+            bank_id: Some(bank_id.to_string()),
+            interval,
+            start: at(start).block.time,
+            end: end.map(|end| at(end).block.time),
+        })
+    }
+
+    fn crank(deps: &mut MockDeps, seconds: u64) -> Response {
+        execute(deps.as_mut(), at(seconds), mock_info("anyone", &[]), ExecuteMsg::Crank { limit: None }).unwrap()
+    }
+
+    fn upcoming(deps: &MockDeps) -> Vec<(u64, Timestamp)> {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::UpcomingExecutions { limit: None }).unwrap();
+        from_binary::<UpcomingExecutionsResponse>(&res).unwrap().executions
+            .into_iter()
+            .map(|execution| (execution.order_id, execution.scheduled_at))
+            .collect()
+    }
+
+    #[test]
+    fn standing_orders_run_on_schedule() {
+        let mut deps = setup();
+        let owner = mock_info("addr0000", &[]);
+
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), order_msg(400, "RUB", "bank0000", 0, 10, None)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidStandingOrder { .. }));
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), order_msg(400, "USD", "bank0000", 100, 10, None)).unwrap_err();
+        assert!(matches!(err, ContractError::TokenAlreadyAssigned { .. }));
+        let err = execute(deps.as_mut(), mock_env(), owner.clone(), order_msg(400, "RUB", "bank0001", 100, 10, None)).unwrap_err();
+        assert!(matches!(err, ContractError::BankNotRegistered { .. }));
+
+        // three executions at 10, 110 and 210, and an open ended one from 50
+        execute(deps.as_mut(), mock_env(), owner.clone(), order_msg(400, "RUB", "bank0000", 100, 10, Some(210))).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), order_msg(100, "RUB", "bank0000", 100, 50, None)).unwrap();
+        assert_eq!(vec![(1, at(10).block.time), (2, at(50).block.time)], upcoming(&deps));
+
+        let res = crank(&mut deps, 0);
+        assert!(res.events.is_empty());
+        crank(&mut deps, 10);
+        assert_eq!(Uint128::from(600u128), balance(&deps, "addr0000"));
+        crank(&mut deps, 110);
+        assert_eq!(Uint128::from(100u128), balance(&deps, "addr0000"));
+
+        // order 2 takes the last 100 first, the short order 1 is recorded and skipped
+        let res = crank(&mut deps, 210);
+        assert!(res.attributes.iter().any(|attr| attr.key == "failed" && attr.value == "1"));
+        assert_eq!(Uint128::zero(), balance(&deps, "addr0000"));
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::StandingOrderHistory { id: 1, start_after: None, limit: None }).unwrap();
+        let history = from_binary::<StandingOrderHistoryResponse>(&res).unwrap().executions;
+        let transactions = history.iter().map(|execution| execution.transaction_id.clone()).collect::<Vec<_>>();
+        assert_eq!(vec![Some("1".to_string()), Some("3".to_string()), None], transactions);
+        assert_eq!(Some(ContractError::NotEnoughBalance { required: Uint128::from(400u128), available: Uint128::zero() }.to_string()), history[2].error);
+        let order: StandingOrder = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::StandingOrder { id: 1 }).unwrap()).unwrap();
+        assert_eq!((StandingOrderStatus::Completed, 2, 1), (order.status, order.executed, order.failed));
+        assert_eq!(vec![(2, at(250).block.time)], upcoming(&deps));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), ExecuteMsg::CancelStandingOrder { id: 2 }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::CancelStandingOrder { id: 2 }).unwrap();
+        assert!(upcoming(&deps).is_empty());
+        let err = execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::CancelStandingOrder { id: 2 }).unwrap_err();
+        assert!(matches!(err, ContractError::StandingOrderNotActive { id: 2 }));
+
+        let msg = QueryMsg::StandingOrders { owner: "addr0000".to_string(), start_after: Some(1), limit: None };
+        let orders = from_binary::<StandingOrdersResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().orders;
+        assert_eq!(vec![(2, StandingOrderStatus::Cancelled)], orders.into_iter().map(|order| (order.id, order.status)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn crank_goes_past_a_failing_order() {
+        let mut deps = setup();
+        let owner = mock_info("addr0000", &[]);
+        for amount in [100, 950, 100] {
+            execute(deps.as_mut(), mock_env(), owner.clone(), order_msg(amount, "RUB", "bank0000", 100, 10, None)).unwrap();
+        }
+        let history = |deps: &MockDeps, id: u64| -> Vec<(Option<String>, bool)> {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::StandingOrderHistory { id, start_after: None, limit: None }).unwrap();
+            from_binary::<StandingOrderHistoryResponse>(&res).unwrap().executions
+                .into_iter()
+                .map(|execution| (execution.transaction_id, execution.error.is_some()))
+                .collect()
+        };
+
+        // the second of two orders fails and the one after it waits for the next crank
+        let msg = ExecuteMsg::Crank { limit: Some(2) };
+        let res = execute(deps.as_mut(), at(10), mock_info("anyone", &[]), msg).unwrap();
+        assert!(res.attributes.iter().any(|attr| attr.key == "failed" && attr.value == "1"));
+        assert_eq!(Uint128::from(900u128), balance(&deps, "addr0000"));
+        assert_eq!(vec![(3, at(10).block.time), (1, at(110).block.time), (2, at(110).block.time)], upcoming(&deps));
+
+        // the failed run left no transaction behind
+        crank(&mut deps, 10);
+        assert_eq!(Uint128::from(800u128), balance(&deps, "addr0000"));
+        assert_eq!(vec![(Some("1".to_string()), false)], history(&deps, 1));
+        assert_eq!(vec![(None, true)], history(&deps, 2));
+        assert_eq!(vec![(Some("2".to_string()), false)], history(&deps, 3));
+        let order: StandingOrder = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::StandingOrder { id: 2 }).unwrap()).unwrap();
+        assert_eq!((StandingOrderStatus::Active, 0, 1), (order.status, order.executed, order.failed));
+    }
+}
@@ -1,8 +1,170 @@
 extern crate serde;
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Coin, Decimal, IbcEndpoint, Timestamp, Uint128};
 use cw20::Cw20Coin;
-use cw_storage_plus::Map;
+use cw_storage_plus::{Item, Map};
+
+#[cw_serde]
+pub struct Config {
+    pub admin: Addr,
+    // may pause the contract, only the admin unpauses
+    pub guardian: Option<Addr>,
+}
+
+#[cw_serde]
+pub enum PauseScope {
+    Global,
+    Denom { denom: String },
+    Bank { id: String },
+    ExchangeRate { id: String },
+}
+
+impl PauseScope {
+    pub fn key(&self) -> (String, String) {
+        match self {
+            PauseScope::Global => ("global".to_string(), String::new()),
+            PauseScope::Denom { denom } => ("denom".to_string(), denom.clone()),
+            PauseScope::Bank { id } => ("bank".to_string(), id.clone()),
+            PauseScope::ExchangeRate { id } => ("exchange_rate".to_string(), id.clone()),
+        }
+    }
+
+    // A pair is paused in both directions
+    pub fn exchange_rates(denom_from: &str, denom_to: &str) -> [PauseScope; 2] {
+        [
+            PauseScope::ExchangeRate { id: denom_from.to_owned() + denom_to },
+            PauseScope::ExchangeRate { id: denom_to.to_owned() + denom_from },
+        ]
+    }
+}
+
+#[cw_serde]
+pub enum Role {
+    // freezes and unfreezes accounts
+    Compliance,
+    // assigns KYC tiers
+    Attestor,
+    // approves or rejects transactions under review
+    Reviewer,
+    // sets the exchange rates of pairs without oracle feeders
+    Oracle,
+}
+
+impl Role {
+    pub fn key(&self) -> String {
+        match self {
+            Role::Compliance => "compliance".to_string(),
+            Role::Attestor => "attestor".to_string(),
+            Role::Reviewer => "reviewer".to_string(),
+            Role::Oracle => "oracle".to_string(),
+        }
+    }
+}
+
+#[cw_serde]
+pub struct FrozenAccount {
+    pub address: Addr,
+    // frozen for every denom
+    pub all: bool,
+    pub denoms: Vec<String>,
+}
+
+impl FrozenAccount {
+    pub fn is_frozen(&self, denom: &str) -> bool {
+        self.all || self.denoms.iter().any(|frozen| frozen == denom)
+    }
+}
+
+#[cw_serde]
+pub enum FreezeAction {
+    Freeze,
+    Unfreeze,
+}
+
+#[cw_serde]
+pub struct FreezeRecord {
+    pub address: Addr,
+    // None for every denom
+    pub denom: Option<String>,
+    pub action: FreezeAction,
+    pub reason: String,
+    pub actor: Addr,
+    pub timestamp: Timestamp,
+}
+
+// None means unlimited
+#[cw_serde]
+pub struct TierLimits {
+    pub max_transfer: Option<Uint128>,
+    pub daily_volume: Option<Uint128>,
+    pub monthly_volume: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct VolumeEntry {
+    pub amount: Uint128,
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub struct ClearingCycle {
+    pub id: u64,
+    pub opened_at: Timestamp,
+}
+
+// Gross flows of a bank in a denom during a cycle
+#[cw_serde]
+#[derive(Default)]
+pub struct Position {
+    // received from senders
+    pub credit: Uint128,
+    // paid to recipients
+    pub debit: Uint128,
+}
+
+#[cw_serde]
+pub struct NetPosition {
+    pub bank_id: String,
+    pub denom: String,
+    pub credit: Uint128,
+    pub debit: Uint128,
+}
+
+impl NetPosition {
+    // surplus the bank hands over to the others
+    pub fn payable(&self) -> Uint128 {
+        self.credit.saturating_sub(self.debit)
+    }
+
+    // shortfall the others cover
+    pub fn receivable(&self) -> Uint128 {
+        self.debit.saturating_sub(self.credit)
+    }
+}
+
+#[cw_serde]
+pub struct NetTransfer {
+    pub denom: String,
+    pub from_bank: String,
+    pub to_bank: String,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct ClearingCycleInfo {
+    pub id: u64,
+    pub opened_at: Timestamp,
+    pub closed_at: Timestamp,
+    pub positions: Vec<NetPosition>,
+    pub transfers: Vec<NetTransfer>,
+}
+
+#[cw_serde]
+pub struct PauseInfo {
+    pub scope: PauseScope,
+    pub paused_by: Addr,
+    pub paused_at: Timestamp,
+}
 
 
 #[cw_serde]
@@ -29,12 +191,53 @@ pub struct BankInfo {
     pub balance: Uint128,
 }
 
+#[cw_serde]
+pub struct CollateralConfig {
+    pub denom: String,
+    // locked from the creator's balance when a bank is registered
+    pub amount: Uint128,
+    // outstanding exposure of a bank is capped at collateral * exposure_multiple
+    pub exposure_multiple: u64,
+    // share of a slashed default paid to whoever claims it, in basis points
+    #[serde(default)]
+    pub keeper_bounty_bps: u64,
+}
+
 #[cw_serde]
 pub enum TransactionStatus {
     Initial,
     SentToBank,
     SentToRecipient,
     RejectedByBank,
+    // held until a reviewer approves or rejects it, funds stay with the bank
+    UnderReview,
+    // withdrawn by the sender before settlement
+    Cancelled,
+    // some tranches were paid, the rest is still with the bank
+    PartiallySettled,
+    // missed its settlement deadline, the sender was compensated from the bank collateral
+    Defaulted,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub enum CancellationPolicy {
+    // the sender can cancel until the transaction is settled
+    #[default]
+    UntilSettled,
+    // the sender can cancel until the bank acknowledges the transaction
+    UntilAcknowledged,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub enum BankStatus {
+    #[default]
+    Active,
+    // no new transactions, pending ones can still settle
+    Suspended,
+    // retired for good, kept for the transaction history
+    Closed,
 }
 
 // Float rate = rate / 10^precision
@@ -48,6 +251,86 @@ pub struct ExchangeRateInfo {
     pub rate: u64,
 }
 
+#[cw_serde]
+pub enum Aggregation {
+    Median,
+    // Drop `trim` lowest and highest prices before averaging
+    TrimmedMean { trim: u32 },
+}
+
+#[cw_serde]
+pub struct OracleConfig {
+    // PK, same as the exchange rate id
+    pub id: String,
+    pub denom_from: String,
+    pub denom_to: String,
+    pub feeders: Vec<Addr>,
+    // fresh submissions required to publish a rate
+    pub quorum: u32,
+    pub precision: u32,
+    // seconds a submission stays fresh
+    pub max_age: u64,
+    // max distance from the median of the other feeders, in basis points
+    pub max_deviation_bps: u64,
+    pub aggregation: Aggregation,
+}
+
+#[cw_serde]
+pub struct PriceSubmission {
+    pub feeder: Addr,
+    pub rate: u64,
+    // when the price was observed by the feeder
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub struct RatePoint {
+    pub rate: u64,
+    pub precision: u32,
+    pub timestamp: Timestamp,
+}
+
+impl RatePoint {
+    // Rate expressed with another precision, to compare rates published with different ones
+    pub fn scaled(&self, precision: u32) -> u128 {
+        self.rate as u128 * 10_u128.pow(precision) / 10_u128.pow(self.precision)
+    }
+}
+
+#[cw_serde]
+pub enum BreachKind {
+    // single update moved too far from the previous rate
+    Update,
+    // rate moved too far from a rate published within the window
+    Window,
+}
+
+#[cw_serde]
+pub struct RateBreach {
+    pub kind: BreachKind,
+    pub reference_rate: u64,
+    pub new_rate: u64,
+    pub deviation_bps: u64,
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub struct CircuitBreakerInfo {
+    // PK, same as the exchange rate id
+    pub id: String,
+    pub denom_from: String,
+    pub denom_to: String,
+    pub max_update_deviation_bps: u64,
+    pub max_window_deviation_bps: u64,
+    // seconds of rate history checked against
+    pub window: u64,
+    // rates published within the window, oldest first
+    pub history: Vec<RatePoint>,
+    // settlements of the pair are paused while set
+    pub breach: Option<RateBreach>,
+    pub breach_count: u64,
+}
+
 #[cw_serde]
 pub struct TransactionInfo {
     // PK
@@ -59,9 +342,319 @@ pub struct TransactionInfo {
     pub denom_from: String,
     pub denom_to: String,
     pub status: TransactionStatus,
+    // set when the bank lives on a counterparty chain
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub reviews: Vec<ReviewRecord>,
+    // set when the bank acknowledges the transaction
+    #[serde(default)]
+    pub acknowledged_at: Option<Timestamp>,
+    // part of amount already paid to the recipient
+    #[serde(default)]
+    pub settled: Uint128,
+    // part of amount paid back to the sender after the bank defaulted
+    #[serde(default)]
+    pub compensated: Uint128,
+    #[serde(default)]
+    pub tranches: Vec<Tranche>,
+    // the bank settlement SLA runs out at this time
+    #[serde(default)]
+    pub deadline: Option<Timestamp>,
+    #[serde(default)]
+    pub created_at: Option<Timestamp>,
+    // set when the contract picked the bank
+    #[serde(default)]
+    pub route: Option<RouteDecision>,
+}
+
+#[cw_serde]
+pub enum StandingOrderStatus {
+    Active,
+    // past its end
+    Completed,
+    // by the owner
+    Cancelled,
+}
+
+#[cw_serde]
+pub struct StandingOrder {
+    pub id: u64,
+    // pays the orders from its account
+    pub owner: Addr,
+    pub to: Addr,
+    pub amount: Uint128,
+    pub denom: String,
+    // routed to the best bank when none
+    pub bank_id: Option<String>,
+    // seconds between executions
+    pub interval: u64,
+    pub next_execution: Timestamp,
+    pub end: Option<Timestamp>,
+    pub status: StandingOrderStatus,
+    pub executed: u64,
+    pub failed: u64,
+}
+
+#[cw_serde]
+pub struct StandingOrderExecution {
+    pub scheduled_at: Timestamp,
+    pub executed_at: Timestamp,
+    // the SendToBank transaction, none if it failed
+    pub transaction_id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[cw_serde]
+pub enum HashLockStatus {
+    Locked,
+    Claimed,
+    Refunded,
+}
+
+#[cw_serde]
+pub struct HashLock {
+    pub id: u64,
+    pub sender: Addr,
+    pub recipient: Addr,
+    pub amount: Uint128,
+    pub denom: String,
+    // hex encoded sha256 of the preimage
+    pub hashlock: String,
+    // claims are accepted before, refunds from then on
+    pub timelock: Timestamp,
+    pub status: HashLockStatus,
+    // revealed by the claim, hex encoded
+    pub preimage: Option<String>,
+    pub created_at: Timestamp,
+}
+
+#[cw_serde]
+pub enum SwapStatus {
+    Open,
+    Accepted,
+    // by the proposer, the escrow is refunded
+    Cancelled,
+}
+
+#[cw_serde]
+pub struct SwapOffer {
+    pub id: u64,
+    pub proposer: Addr,
+    pub counterparty: Addr,
+    // escrowed from the proposer
+    pub give: Coin,
+    pub want: Coin,
+    // credited with want, holds want.denom
+    pub receiver: Addr,
+    pub expires: Timestamp,
+    pub status: SwapStatus,
+    pub created_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct Pool {
+    // "denom_a/denom_b" with the denoms sorted
+    pub id: String,
+    pub denom_a: String,
+    pub denom_b: String,
+    pub reserve_a: Uint128,
+    pub reserve_b: Uint128,
+    // liquidity provider shares issued
+    pub total_shares: Uint128,
+    // taken from the swapped amount and left in the pool
+    pub fee_bps: u64,
+}
+
+// First deposit of an empty pool, it sets the price the other side has to match
+#[cw_serde]
+pub struct PoolSeed {
+    pub provider: Addr,
+    pub denom: String,
+    pub amount: Uint128,
+    pub other_amount: Uint128,
+}
+
+impl Pool {
+    pub fn has(&self, denom: &str) -> bool {
+        denom == self.denom_a || denom == self.denom_b
+    }
+
+    pub fn reserve(&self, denom: &str) -> Uint128 {
+        if denom == self.denom_a { self.reserve_a } else { self.reserve_b }
+    }
+
+    pub fn other(&self, denom: &str) -> String {
+        if denom == self.denom_a { self.denom_b.clone() } else { self.denom_a.clone() }
+    }
+
+    pub fn set_reserve(&mut self, denom: &str, reserve: Uint128) {
+        if denom == self.denom_a { self.reserve_a = reserve } else { self.reserve_b = reserve }
+    }
+
+    // Constant product output for amount of denom_in, after the fee
+    pub fn swap_out(&self, denom_in: &str, amount: Uint128) -> Uint128 {
+        let reserve_in = self.reserve(denom_in);
+        let reserve_out = self.reserve(&self.other(denom_in));
+        let amount = amount - amount.multiply_ratio(self.fee_bps, 10_000u128);
+        if (reserve_in + amount).is_zero() {
+            return Uint128::zero();
+        }
+        reserve_out.multiply_ratio(amount, reserve_in + amount)
+    }
+
+    // Amount of the other token paid per unit of denom_from at the current reserves
+    pub fn spot_price(&self, denom_from: &str) -> Option<Decimal> {
+        let reserve_from = self.reserve(denom_from);
+        let reserve_to = self.reserve(&self.other(denom_from));
+        if reserve_from.is_zero() || reserve_to.is_zero() {
+            return None;
+        }
+        Some(Decimal::from_ratio(reserve_to, reserve_from))
+    }
+}
+
+#[cw_serde]
+pub struct Order {
+    pub id: u64,
+    pub owner: Addr,
+    // credited with the bought tokens, holds buy_denom
+    pub receiver: Addr,
+    pub sell_denom: String,
+    pub buy_denom: String,
+    pub amount: Uint128,
+    // still escrowed and offered, in sell_denom
+    pub remaining: Uint128,
+    // lowest buy_denom accepted per sell_denom
+    pub price: Decimal,
+    pub created_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct RouteQuote {
+    pub bank_id: String,
+    pub fee_bps: u64,
+    // paid to the recipient after the fee, in denom_to
+    pub received: Uint128,
+    // bank reserves in denom_to, or the pool reserve for banks settling through a pool
+    pub reserves: Uint128,
+}
+
+#[cw_serde]
+pub struct RouteDecision {
+    pub chosen: RouteQuote,
+    // the other eligible banks, best first
+    pub alternatives: Vec<RouteQuote>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct BankStats {
+    pub accepted: u64,
+    // fully paid to the recipient
+    pub settled: u64,
+    pub rejected: u64,
+    pub defaulted: u64,
+    // seconds from acceptance to full settlement, summed over the settled transactions
+    pub total_settlement_time: u64,
+}
+
+impl BankStats {
+    pub fn average_settlement_time(&self) -> Option<u64> {
+        self.total_settlement_time.checked_div(self.settled)
+    }
+
+    pub fn settlement_rate_bps(&self) -> Option<u64> {
+        (self.settled * 10_000).checked_div(self.accepted)
+    }
+
+    pub fn default_rate_bps(&self) -> Option<u64> {
+        (self.defaulted * 10_000).checked_div(self.accepted)
+    }
+}
+
+#[cw_serde]
+pub struct DefaultRecord {
+    pub transaction_id: String,
+    // paid to the sender out of the collateral, in the collateral token
+    pub compensation: Uint128,
+    // paid back to the sender out of the funds the bank received, in denom_from
+    pub refunded: Uint128,
+    // taken from the bank collateral, bounty included
+    pub slashed: Uint128,
+    pub bounty: Uint128,
+    pub keeper: Addr,
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub struct Tranche {
+    // in denom_from
+    pub amount: Uint128,
+    // in denom_to
+    pub received: Uint128,
+    pub rate: u64,
+    pub precision: u32,
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub enum ReviewAction {
+    Flagged,
+    Approved,
+    Rejected,
+}
+
+#[cw_serde]
+pub struct ReviewRecord {
+    pub action: ReviewAction,
+    pub actor: Addr,
+    pub note: String,
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub struct ReviewQueueEntry {
+    pub transaction_id: String,
+    pub bank_id: String,
+    pub amount: Uint128,
+    pub denom_from: String,
+    pub flagged_at: Timestamp,
+    pub note: String,
+}
+
+#[cw_serde]
+pub struct ChannelInfo {
+    // PK
+    pub id: String,
+    pub counterparty_endpoint: IbcEndpoint,
+    pub connection_id: String,
+    // negotiated protocol, ICS-20 or a cw20-trading version
+    pub version: String,
+}
+
+// Tokens escrowed on a channel, per denom
+#[cw_serde]
+#[derive(Default)]
+pub struct ChannelState {
+    pub outstanding: Uint128,
+    pub total_sent: Uint128,
 }
 
 impl TransactionInfo {
+    pub fn flagged_at(&self) -> Option<Timestamp> {
+        self.reviews.iter().rev()
+            .find(|review| review.action == ReviewAction::Flagged)
+            .map(|review| review.timestamp)
+    }
+
+    pub fn outstanding(&self) -> Uint128 {
+        self.amount - self.settled - self.compensated
+    }
+
+    pub fn is_approved(&self) -> bool {
+        self.reviews.iter().any(|review| review.action == ReviewAction::Approved)
+    }
+
     pub fn update_status(&mut self, status: TransactionStatus) -> TransactionInfo {
         self.status = status;
         self.clone()
@@ -81,8 +674,93 @@ impl BankInfo {
 }
 
 
+pub const CONFIG: Item<Config> = Item::new("config");
+pub const PAUSES: Map<(String, String), PauseInfo> = Map::new("pauses");
+pub const ROLES: Map<(String, &Addr), bool> = Map::new("roles");
+pub const FROZEN_ACCOUNTS: Map<&Addr, FrozenAccount> = Map::new("frozen_accounts");
+// audit trail, keyed by address and a global sequence
+pub const FREEZE_HISTORY: Map<(&Addr, u64), FreezeRecord> = Map::new("freeze_history");
+pub const FREEZE_HISTORY_COUNT: Item<u64> = Item::new("freeze_history_count");
+// accounts without an attestation are tier 0
+pub const KYC_TIERS: Map<&Addr, u32> = Map::new("kyc_tiers");
+pub const TIER_LIMITS: Map<(u32, String), TierLimits> = Map::new("tier_limits");
+// tokens only accounts with a KYC tier may hold
+pub const KYC_REQUIRED: Map<String, ()> = Map::new("kyc_required");
+// transfers of the last month, by sender and denom
+pub const TRANSFER_VOLUMES: Map<(&Addr, String), Vec<VolumeEntry>> = Map::new("transfer_volumes");
 pub const TOKENS: Map<String, TokenInfo> = Map::new("tokens");
 pub const BALANCES: Map<&Addr, BalanceInfo> = Map::new("balance");
 pub const BANKS: Map<String, BankInfo> = Map::new("banks");
+// external contracts asked before accepting and settling a transaction of the bank
+pub const BANK_CHECKERS: Map<String, Vec<Addr>> = Map::new("bank_checkers");
+pub const BANK_OPERATORS: Map<String, Addr> = Map::new("bank_operators");
+pub const CANCELLATION_POLICIES: Map<String, CancellationPolicy> = Map::new("cancellation_policies");
+pub const BANK_STATUSES: Map<String, BankStatus> = Map::new("bank_statuses");
+pub const COLLATERAL_CONFIG: Item<CollateralConfig> = Item::new("collateral_config");
+// collateral locked by each bank, in the configured denom
+pub const BANK_COLLATERAL: Map<String, Uint128> = Map::new("bank_collateral");
+// seconds a bank commits to settle a transaction in
+pub const SETTLEMENT_SLAS: Map<String, u64> = Map::new("settlement_slas");
+// taken from the settled amount, in basis points
+pub const BANK_FEES: Map<String, u64> = Map::new("bank_fees");
+pub const BANK_STATS: Map<String, BankStats> = Map::new("bank_stats");
+pub const STANDING_ORDER_COUNT: Item<u64> = Item::new("standing_order_count");
+pub const STANDING_ORDERS: Map<u64, StandingOrder> = Map::new("standing_orders");
+pub const OWNER_STANDING_ORDERS: Map<(&Addr, u64), ()> = Map::new("owner_standing_orders");
+// active orders by next execution in seconds and id, soonest first
+pub const STANDING_ORDER_SCHEDULE: Map<(u64, u64), ()> = Map::new("standing_order_schedule");
+// by order id and scheduled time in seconds
+pub const STANDING_ORDER_HISTORY: Map<(u64, u64), StandingOrderExecution> = Map::new("standing_order_history");
+pub const HASH_LOCK_COUNT: Item<u64> = Item::new("hash_lock_count");
+pub const HASH_LOCKS: Map<u64, HashLock> = Map::new("hash_locks");
+// locks by hashlock and id, claims name the lock so the same hash can lock more than once
+pub const HASH_LOCKS_BY_HASH: Map<(&str, u64), ()> = Map::new("hash_locks_by_hash");
+// locks sent by or to the address
+pub const USER_HASH_LOCKS: Map<(&Addr, u64), ()> = Map::new("user_hash_locks");
+pub const SWAP_COUNT: Item<u64> = Item::new("swap_count");
+pub const SWAP_OFFERS: Map<u64, SwapOffer> = Map::new("swap_offers");
+// swaps proposed by or to the address
+pub const USER_SWAPS: Map<(&Addr, u64), ()> = Map::new("user_swaps");
+pub const POOLS: Map<String, Pool> = Map::new("pools");
+pub const POOL_SEEDS: Map<String, PoolSeed> = Map::new("pool_seeds");
+// by pool and liquidity provider
+pub const LP_SHARES: Map<(String, &Addr), Uint128> = Map::new("lp_shares");
+// channels a bank settles remote transactions from, by bank and channel, to the counterparty port
+pub const BANK_CHANNELS: Map<(String, String), String> = Map::new("bank_channels");
+// banks converting their settlements through the pool of the pair, to the slippage they accept in bps
+pub const BANK_POOL_ROUTING: Map<String, u64> = Map::new("bank_pool_routing");
+// spot price of denom_a in denom_b after each change of the reserves, by pool and block time
+pub const POOL_PRICES: Map<(String, u64), RatePoint> = Map::new("pool_prices");
+pub const ORDER_COUNT: Item<u64> = Item::new("order_count");
+pub const ORDERS: Map<u64, Order> = Map::new("orders");
+// resting orders by "sell_denom/buy_denom", price atomics and id, best price first
+pub const ORDER_BOOK: Map<(String, u128, u64), ()> = Map::new("order_book");
+pub const OWNER_ORDERS: Map<(&Addr, u64), ()> = Map::new("owner_orders");
+// accepted amount by bank and denom_from
+pub const BANK_VOLUMES: Map<(String, String), Uint128> = Map::new("bank_volumes");
+// by bank and transaction id
+pub const BANK_DEFAULTS: Map<(String, String), DefaultRecord> = Map::new("bank_defaults");
+// liquidity the bank pays recipients from, funded by the operator and grown by what senders pay in
+pub const BANK_RESERVES: Map<(String, String), Uint128> = Map::new("bank_reserves");
+// amount of pending transactions the bank may still have to refund, by bank and denom_from
+pub const BANK_OUTSTANDING: Map<(String, String), Uint128> = Map::new("bank_outstanding");
+pub const CLEARING_CYCLE: Item<ClearingCycle> = Item::new("clearing_cycle");
+// by cycle, bank and denom
+pub const POSITIONS: Map<(u64, String, String), Position> = Map::new("positions");
+pub const CLEARING_HISTORY: Map<u64, ClearingCycleInfo> = Map::new("clearing_history");
+// transactions of at least this amount of denom_from go to review, by bank and denom
+pub const REVIEW_THRESHOLDS: Map<(String, String), Uint128> = Map::new("review_thresholds");
+// oldest first, by flagged time in seconds and transaction id
+pub const REVIEW_QUEUE: Map<(u64, String), ReviewQueueEntry> = Map::new("review_queue");
 pub const TRANSACTIONS: Map<String, TransactionInfo> = Map::new("transactions");
+// last assigned transaction id
+pub const TRANSACTION_COUNT: Item<u64> = Item::new("transaction_count");
+// transaction id by sender and client idempotency key
+pub const IDEMPOTENCY_KEYS: Map<(&Addr, String), String> = Map::new("idempotency_keys");
 pub const EXCHANGE_RATES: Map<String, ExchangeRateInfo> = Map::new("exchange_rates");
+pub const CHANNEL_INFO: Map<String, ChannelInfo> = Map::new("channel_info");
+pub const CHANNEL_STATE: Map<(String, String), ChannelState> = Map::new("channel_state");
+pub const ORACLES: Map<String, OracleConfig> = Map::new("oracles");
+pub const PRICE_SUBMISSIONS: Map<(String, &Addr), PriceSubmission> = Map::new("price_submissions");
+pub const CIRCUIT_BREAKERS: Map<String, CircuitBreakerInfo> = Map::new("circuit_breakers");
+pub const RATE_BREACHES: Map<(String, u64), RateBreach> = Map::new("rate_breaches");
@@ -0,0 +1,34 @@
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{from_binary, DepsMut, Env, OwnedDeps, Uint128};
+use cw20::Cw20Coin;
+
+use crate::contract::{execute, instantiate, query};
+use crate::msg::{BalanceResponse, ExecuteMsg, InstantiateMsg, QueryMsg, TokenInfoMsg};
+
+pub type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+// Registers denom with 1000 for each of the holders, creator is the admin
+pub fn create_token(deps: DepsMut, env: Env, denom: &str, holders: &[&str]) {
+    let msg = ExecuteMsg::CreateToken(TokenInfoMsg {
+        name: denom.to_string(),
+        denom: denom.to_string(),
+        initial_balances: holders.iter()
+            .map(|address| Cw20Coin { address: address.to_string(), amount: Uint128::from(1000u128) })
+            .collect(),
+    });
+    execute(deps, env, mock_info("creator", &[]), msg).unwrap();
+}
+
+// RUB holder addr0000, USD holder addr0001
+pub fn setup() -> MockDeps {
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), InstantiateMsg {}).unwrap();
+    create_token(deps.as_mut(), mock_env(), "RUB", &["addr0000"]);
+    create_token(deps.as_mut(), mock_env(), "USD", &["addr0001"]);
+    deps
+}
+
+pub fn balance(deps: &MockDeps, address: &str) -> Uint128 {
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Balance { address: address.to_string() }).unwrap();
+    from_binary::<BalanceResponse>(&res).unwrap().amount
+}